@@ -1,11 +1,37 @@
 use std::env;
+use std::ffi::OsStr;
 use std::fs;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
 use super::ConfigError;
 use serde::de::DeserializeOwned;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// Infers the format from the file extension (case-insensitive). Paths without an extension
+    /// are read as TOML.
+    pub fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        let Some(extension) = path.extension().map(OsStr::to_string_lossy) else {
+            return Ok(Format::Toml);
+        };
+
+        match extension.to_ascii_lowercase().as_str() {
+            "toml" => Ok(Format::Toml),
+            "json" => Ok(Format::Json),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(ConfigError::UnsupportedFormat(extension.into_owned())),
+        }
+    }
+}
+
 pub struct Config;
 impl Config {
     pub fn from_env<T>(key: &'static str) -> Result<T, ConfigError>
@@ -22,13 +48,160 @@ impl Config {
         Ok(value)
     }
 
+    /// Reads a boolean, accepting `true`/`false`, `1`/`0`, `yes`/`no` and `on`/`off` in any case.
+    pub fn bool_from_env(key: &'static str) -> Result<bool, ConfigError> {
+        let value: String = env::var(key)?;
+        Self::parse_bool(&value).ok_or_else(|| Self::invalid(key, value, "a boolean such as true, 1, yes or on"))
+    }
+
+    /// Reads a duration written as a whole number followed by `ms`, `s`, `m` or `h`, such as
+    /// `500ms` or `30s`.
+    pub fn duration_from_env(key: &'static str) -> Result<Duration, ConfigError> {
+        let value: String = env::var(key)?;
+        Self::parse_duration(&value).ok_or_else(|| Self::invalid(key, value, "a duration such as 500ms, 30s or 5m"))
+    }
+
+    fn parse_bool(value: &str) -> Option<bool> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Some(true),
+            "false" | "0" | "no" | "off" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn parse_duration(value: &str) -> Option<Duration> {
+        let value: &str = value.trim();
+        let unit_start: usize = value.find(|c: char| !c.is_ascii_digit())?;
+        let amount: u64 = value[..unit_start].parse().ok()?;
+
+        let seconds: u64 = match &value[unit_start..] {
+            "ms" => return Some(Duration::from_millis(amount)),
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            _ => return None,
+        };
+
+        amount.checked_mul(seconds).map(Duration::from_secs)
+    }
+
+    fn invalid(key: &'static str, value: String, expected: &'static str) -> ConfigError {
+        ConfigError::InvalidValue { key, value, expected }
+    }
+
     pub fn from_file<T, P>(path: P) -> Result<T, ConfigError>
+    where
+        T: DeserializeOwned,
+        P: AsRef<Path>,
+    {
+        let format: Format = Format::from_path(path.as_ref())?;
+        Self::from_file_as(path, format)
+    }
+
+    pub fn from_file_as<T, P>(path: P, format: Format) -> Result<T, ConfigError>
     where
         T: DeserializeOwned,
         P: AsRef<Path>,
     {
         let content: String = fs::read_to_string(path.as_ref())?;
-        let config: T = toml::from_str(&content)?;
+
+        let config: T = match format {
+            Format::Toml => toml::from_str(&content)?,
+            Format::Json => serde_json::from_str(&content)?,
+            Format::Yaml => serde_yaml_ng::from_str(&content)?,
+        };
+
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+    use std::process;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Settings {
+        port: u16,
+        hosts: Vec<String>,
+    }
+
+    fn write_temp(name: &str, content: &str) -> PathBuf {
+        let path: PathBuf = env::temp_dir().join(format!("forge-config-{}-{name}", process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(Format::from_path(Path::new("app.toml")).unwrap(), Format::Toml);
+        assert_eq!(Format::from_path(Path::new("app.JSON")).unwrap(), Format::Json);
+        assert_eq!(Format::from_path(Path::new("app.yml")).unwrap(), Format::Yaml);
+        assert_eq!(Format::from_path(Path::new("conf/app")).unwrap(), Format::Toml);
+        assert!(matches!(
+            Format::from_path(Path::new("app.ini")),
+            Err(ConfigError::UnsupportedFormat(extension)) if extension == "ini"
+        ));
+    }
+
+    #[test]
+    fn test_parse_bool_spellings() {
+        for value in ["true", "TRUE", "1", "yes", "On", " on "] {
+            assert_eq!(Config::parse_bool(value), Some(true), "{value}");
+        }
+
+        for value in ["false", "0", "No", "off"] {
+            assert_eq!(Config::parse_bool(value), Some(false), "{value}");
+        }
+
+        assert_eq!(Config::parse_bool("enabled"), None);
+        assert_eq!(Config::parse_bool(""), None);
+    }
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(Config::parse_duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(Config::parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(Config::parse_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(Config::parse_duration("2h"), Some(Duration::from_secs(7200)));
+
+        for value in ["30", "s", "1.5s", "-1s", "10 s", "3d", ""] {
+            assert_eq!(Config::parse_duration(value), None, "{value}");
+        }
+
+        let error: ConfigError = Config::invalid("READ_TIMEOUT", String::from("30"), "a duration");
+        assert_eq!(error.to_string(), "Invalid value \"30\" for READ_TIMEOUT, expected a duration");
+    }
+
+    #[test]
+    fn test_from_file_reads_each_format() {
+        let expected: Settings = Settings {
+            port: 8080,
+            hosts: vec![String::from("a"), String::from("b")],
+        };
+
+        let files: [(&str, &str); 3] = [
+            ("settings.toml", "port = 8080\nhosts = [\"a\", \"b\"]\n"),
+            ("settings.json", r#"{ "port": 8080, "hosts": ["a", "b"] }"#),
+            ("settings.yaml", "port: 8080\nhosts:\n  - a\n  - b\n"),
+        ];
+
+        for (name, content) in files {
+            let path: PathBuf = write_temp(name, content);
+            let settings: Result<Settings, ConfigError> = Config::from_file(&path);
+            fs::remove_file(&path).ok();
+            assert_eq!(settings.unwrap(), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_from_file_as_overrides_extension() {
+        let path: PathBuf = write_temp("settings.conf", r#"{ "port": 1, "hosts": [] }"#);
+        let settings: Result<Settings, ConfigError> = Config::from_file_as(&path, Format::Json);
+        fs::remove_file(&path).ok();
+
+        assert_eq!(settings.unwrap().port, 1);
+    }
+}
@@ -12,9 +12,25 @@ pub enum ConfigError {
     #[error("Failed to parse string value: {0}")]
     StringParse(#[source] Box<dyn std::error::Error>),
 
+    #[error("Invalid value \"{value}\" for {key}, expected {expected}")]
+    InvalidValue {
+        key: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+
     #[error("Failed to parse TOML content: {0}")]
     TomlParse(#[from] toml::de::Error),
 
+    #[error("Failed to parse JSON content: {0}")]
+    JsonParse(#[from] serde_json::Error),
+
+    #[error("Failed to parse YAML content: {0}")]
+    YamlParse(#[from] serde_yaml_ng::Error),
+
+    #[error("Unsupported config file extension \".{0}\", expected .toml, .json, .yaml or .yml")]
+    UnsupportedFormat(String),
+
     #[error("I/O Error: {0}")]
     Io(#[from] io::Error),
 }
@@ -0,0 +1,69 @@
+use std::env;
+use std::time::{Duration, Instant};
+
+use forge_database::{Database, DatabaseError, DatabaseOptions, RowSet, sql_args};
+use tokio::runtime::{Builder, Runtime};
+use tokio::task::JoinSet;
+
+const ROUNDS: usize = 5;
+const DISTINCT_QUERIES: usize = 64;
+const INFLIGHT_PER_CONN: usize = 32;
+
+// Every round issues `DISTINCT_QUERIES` statements the worker has never seen, all at once, so each
+// one misses the statement cache. Needs a reachable Postgres in `DB_URL`.
+async fn measure(database: &Database, round: usize) -> Duration {
+    let mut queries: JoinSet<Result<RowSet, DatabaseError>> = JoinSet::new();
+    let start: Instant = Instant::now();
+
+    for idx in 0..DISTINCT_QUERIES {
+        let database: Database = database.clone();
+        let query: String = format!("SELECT $1::int8 + {idx} AS round_{round}");
+        queries.spawn(async move { database.query(query, sql_args![idx as i64]).await });
+    }
+
+    while let Some(result) = queries.join_next().await {
+        result.expect("query task panicked").expect("query failed");
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    let Ok(url) = env::var("DB_URL") else {
+        println!("DB_URL is not set, skipping");
+        return;
+    };
+
+    let database: Database = Database::new(DatabaseOptions {
+        url,
+        threads: 1,
+        queue_size: DISTINCT_QUERIES,
+        inflight_per_conn: INFLIGHT_PER_CONN,
+        application_name: Some(String::from("forge-prepare-bench")),
+        session_settings: Vec::new(),
+        connect_timeout: Some(Duration::from_secs(5)),
+        prepare_error_ttl: None,
+        warmup_statements: Vec::new(),
+        queue_wait: None,
+    })
+    .expect("failed to connect to DB_URL");
+
+    let runtime: Runtime = Builder::new_current_thread().enable_all().build().unwrap();
+
+    let elapsed: Vec<Duration> = runtime.block_on(async {
+        let mut elapsed: Vec<Duration> = Vec::with_capacity(ROUNDS);
+
+        for round in 0..ROUNDS {
+            elapsed.push(measure(&database, round).await);
+        }
+
+        elapsed
+    });
+
+    let best: Duration = elapsed.iter().copied().min().unwrap_or_default();
+    let mean: Duration = elapsed.iter().sum::<Duration>() / ROUNDS as u32;
+
+    println!("{DISTINCT_QUERIES} uncached queries per round, {INFLIGHT_PER_CONN} in flight, {ROUNDS} rounds");
+    println!("best: {:>8.2} ms/round", best.as_secs_f64() * 1000.0);
+    println!("mean: {:>8.2} ms/round", mean.as_secs_f64() * 1000.0);
+}
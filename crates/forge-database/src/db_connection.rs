@@ -1,89 +1,487 @@
-use std::sync::Arc;
+use std::future::{self, Future};
+use std::pin::{Pin, pin};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
 use super::RowSet;
-use super::database::DbCommand;
+use super::database::{DatabaseOptions, DbCommand, DbReplySender};
 use super::error::DatabaseError;
+use super::pool_status::WorkerStats;
+use super::query_builder::QueryBuilder;
 use super::sql_args::SqlArg;
-use forge_utils::LruCache;
-use tokio::sync::{Semaphore, mpsc::Receiver};
+use forge_utils::{CatchUnwind, LruCache, PanicPayload, panic_message};
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore, mpsc::Receiver};
 use tokio_postgres::tls::NoTlsStream;
 use tokio_postgres::types::ToSql;
-use tokio_postgres::{Client, Connection, Error, NoTls, Socket, Statement};
+use tokio_postgres::{Client, Config, Connection, Error, NoTls, Socket, Statement};
 
 const LRU_CACHE_SIZE: usize = 256;
+const PREPARE_ERROR_CACHE_SIZE: usize = 64;
+const HOT_STATEMENT_LIMIT: usize = LRU_CACHE_SIZE / 2;
 
+type StatementCache = Mutex<LruCache<Arc<str>, Statement>>;
+type PrepareErrorCache = LruCache<Arc<str>, (Instant, Arc<str>)>;
+type AcquirePermit = Pin<Box<dyn Future<Output = Result<OwnedSemaphorePermit, AcquireError>> + Send>>;
+
+/// A place in the in-flight semaphore's queue, taken by the dispatch loop in the order commands are
+/// received. The semaphore hands out permits first come, first served, so queries still start in
+/// arrival order even though their tasks are spawned and scheduled independently.
+struct PermitReservation {
+    acquire: AcquirePermit,
+    acquired: Option<Result<OwnedSemaphorePermit, AcquireError>>,
+}
+
+impl PermitReservation {
+    async fn new(semaphore: Arc<Semaphore>) -> Self {
+        let mut acquire: AcquirePermit = Box::pin(semaphore.acquire_owned());
+        let acquired: Poll<Result<OwnedSemaphorePermit, AcquireError>> =
+            future::poll_fn(|cx: &mut Context| Poll::Ready(acquire.as_mut().poll(cx))).await;
+
+        Self {
+            acquire,
+            acquired: match acquired {
+                Poll::Ready(acquired) => Some(acquired),
+                Poll::Pending => None,
+            },
+        }
+    }
+
+    async fn acquire<C>(mut self, mut poll_closed: C) -> Option<OwnedSemaphorePermit>
+    where
+        C: FnMut(&mut Context) -> Poll<()>,
+    {
+        if let Some(acquired) = self.acquired {
+            return acquired.ok();
+        }
+
+        future::poll_fn(|cx: &mut Context| {
+            if let Poll::Ready(acquired) = self.acquire.as_mut().poll(cx) {
+                return Poll::Ready(acquired.ok());
+            }
+
+            poll_closed(cx).map(|()| None)
+        })
+        .await
+    }
+}
+
+/// Queries that recently failed to prepare, with when they failed and the error Postgres returned.
+/// Entries are never removed early; an expired one is ignored and overwritten by the next failure.
+#[derive(Debug)]
+struct PrepareErrors {
+    ttl: Duration,
+    recent: Mutex<PrepareErrorCache>,
+}
+
+impl PrepareErrors {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            recent: Mutex::new(LruCache::new(PREPARE_ERROR_CACHE_SIZE)),
+        }
+    }
+
+    fn check(&self, query: &Arc<str>) -> Result<(), DatabaseError> {
+        let recent: Option<(Instant, Arc<str>)> = self.lock().get(query);
+
+        match recent {
+            Some((failed_at, message)) if failed_at.elapsed() < self.ttl => {
+                Err(DatabaseError::CachedPrepareError(message))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn record(&self, query: &Arc<str>, error: &DatabaseError) {
+        if let DatabaseError::Postgres(e) = error
+            && e.as_db_error().is_some()
+        {
+            self.lock()
+                .insert(query.clone(), (Instant::now(), Arc::from(error.to_string())));
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PrepareErrorCache> {
+        self.recent.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Query texts that some worker has prepared, shared by the whole pool so every other worker
+/// prepares them too, ahead of its own first request for them. The list only grows, up to
+/// `HOT_STATEMENT_LIMIT` entries (half the statement cache, so hot statements are not evicted by
+/// each other), and is seeded with `DatabaseOptions::warmup_statements`.
+///
+/// `len` mirrors the length of the list, so a worker finds out whether anything was added since it
+/// last looked with one atomic load per command and only takes the lock when something was. Workers
+/// publish only after a prepare that missed their cache, which already costs a round trip.
+#[derive(Debug, Default)]
+pub(crate) struct HotStatements {
+    len: AtomicUsize,
+    queries: Mutex<Vec<Arc<str>>>,
+}
+
+impl HotStatements {
+    pub(crate) fn new<I>(warmup: I) -> Self
+    where
+        I: IntoIterator<Item = Arc<str>>,
+    {
+        let hot_statements: Self = Self::default();
+        warmup
+            .into_iter()
+            .for_each(|query: Arc<str>| hot_statements.publish(&query));
+        hot_statements
+    }
+
+    fn publish(&self, query: &Arc<str>) {
+        let mut queries: MutexGuard<Vec<Arc<str>>> = self.lock();
+
+        if queries.len() < HOT_STATEMENT_LIMIT && !queries.contains(query) {
+            queries.push(query.clone());
+            self.len.store(queries.len(), Ordering::Release);
+        }
+    }
+
+    /// The queries published after the first `seen`.
+    fn since(&self, seen: usize) -> Vec<Arc<str>> {
+        match self.len.load(Ordering::Acquire) > seen {
+            true => self.lock()[seen..].to_vec(),
+            false => Vec::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Arc<str>>> {
+        self.queries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Statements are prepared inside each query's task rather than in the dispatch loop, so queries
+/// missing the cache prepare concurrently up to `inflight_per_conn`. Each cached query holds a
+/// once-cell: concurrent callers of the same query share a single prepare, and a failed prepare
+/// leaves the cell empty for the next caller to retry.
+///
+/// Before dispatching each command, a worker prepares the statements other workers published to
+/// `HotStatements` since it last checked. Those prepares run in a background task of their own and
+/// take no in-flight permit, and a failure only leaves the statement to be prepared on first use.
+///
+/// The dispatch loop never waits for an in-flight permit. It keeps draining the channel, and each
+/// query waits for its permit in its own task, so up to `queue_size` received commands can be
+/// waiting at once on top of the ones still in the channel.
 #[derive(Debug)]
 pub struct DbConnection {
     client: Arc<Client>,
     semaphore: Arc<Semaphore>,
+    waiting: Arc<Semaphore>,
+    stats: Arc<WorkerStats>,
     receiver: Receiver<DbCommand>,
-    cache: LruCache<Arc<str>, Statement>,
+    cache: Arc<StatementCache>,
+    prepare_errors: Option<Arc<PrepareErrors>>,
+    hot_statements: Arc<HotStatements>,
+    hot_seen: usize,
 }
 
 impl DbConnection {
-    pub async fn new(
-        database_url: String,
-        inflight_per_conn: usize,
+    pub(crate) async fn new(
+        config: &Config,
+        options: &DatabaseOptions,
         receiver: Receiver<DbCommand>,
+        stats: Arc<WorkerStats>,
+        hot_statements: Arc<HotStatements>,
     ) -> Result<Self, DatabaseError> {
-        let (client, connection): (Client, Connection<Socket, NoTlsStream>) =
-            tokio_postgres::connect(&database_url, NoTls).await?;
+        let (client, connection): (Client, Connection<Socket, NoTlsStream>) = config.connect(NoTls).await?;
+        let connection_stats: Arc<WorkerStats> = stats.clone();
 
         tokio::spawn(async move {
-            connection.await?;
-            Ok::<(), Error>(())
+            let result: Result<(), Error> = connection.await;
+            connection_stats.alive.store(false, Ordering::Relaxed);
+            result
         });
 
+        Self::apply_session_settings(&client, &options.session_settings).await?;
+        stats.alive.store(true, Ordering::Relaxed);
+
         Ok(Self {
+            stats,
             receiver,
+            hot_statements,
+            hot_seen: 0,
             client: Arc::new(client),
-            cache: LruCache::new(LRU_CACHE_SIZE),
-            semaphore: Arc::new(Semaphore::new(inflight_per_conn)),
+            cache: Arc::new(Mutex::new(LruCache::new(LRU_CACHE_SIZE))),
+            prepare_errors: options
+                .prepare_error_ttl
+                .map(|ttl: Duration| Arc::new(PrepareErrors::new(ttl))),
+            semaphore: Arc::new(Semaphore::new(options.inflight_per_conn)),
+            waiting: Arc::new(Semaphore::new(options.queue_size)),
         })
     }
 
-    async fn prepare_statement(&mut self, query: Arc<str>) -> Result<Statement, DatabaseError> {
-        let client: &Arc<Client> = &self.client;
+    async fn apply_session_settings(client: &Client, settings: &[(String, String)]) -> Result<(), DatabaseError> {
+        if settings.is_empty() {
+            return Ok(());
+        }
+
+        let (query, args): (String, Vec<SqlArg>) = settings
+            .iter()
+            .enumerate()
+            .fold(
+                QueryBuilder::new("SELECT "),
+                |builder: QueryBuilder, (idx, (name, value)): (usize, &(String, String))| {
+                    builder
+                        .push(if idx == 0 { "set_config(" } else { ", set_config(" })
+                        .push_arg(name.as_str())
+                        .push(", ")
+                        .push_arg(value.as_str())
+                        .push(", false)")
+                },
+            )
+            .build();
+
+        let params: Vec<&(dyn ToSql + Sync)> = args.iter().map(|arg: &SqlArg| arg.as_sql()).collect();
+
+        client
+            .query(&query, &params)
+            .await
+            .map(|_| ())
+            .map_err(DatabaseError::SessionSettings)
+    }
 
-        self.cache
-            .get_or_fetch(query, move |key: &Arc<str>| {
-                let client: Arc<Client> = client.clone();
-                let query: Arc<str> = key.clone();
-                async move { client.prepare(&query).await.map_err(DatabaseError::Postgres) }
+    async fn prepare_statement(
+        client: &Client,
+        cache: &StatementCache,
+        prepare_errors: Option<&PrepareErrors>,
+        hot_statements: &HotStatements,
+        query: &Arc<str>,
+    ) -> Result<Statement, DatabaseError> {
+        if let Some(prepare_errors) = prepare_errors {
+            prepare_errors.check(query)?;
+        }
+
+        let mut fetched: bool = false;
+        let prepared: Result<Statement, DatabaseError> =
+            LruCache::get_or_fetch_shared(cache, query.clone(), |_: &Arc<str>| {
+                fetched = true;
+                client.prepare(query)
             })
             .await
+            .map_err(DatabaseError::Postgres);
+
+        match (&prepared, prepare_errors) {
+            (Ok(_), _) if fetched => hot_statements.publish(query),
+            (Err(e), Some(prepare_errors)) => prepare_errors.record(query, e),
+            _ => {}
+        }
+
+        prepared
+    }
+
+    fn prepare_hot_statements(&mut self) {
+        let queries: Vec<Arc<str>> = self.hot_statements.since(self.hot_seen);
+
+        if queries.is_empty() {
+            return;
+        }
+
+        self.hot_seen += queries.len();
+        let client: Arc<Client> = self.client.clone();
+        let cache: Arc<StatementCache> = self.cache.clone();
+        let prepare_errors: Option<Arc<PrepareErrors>> = self.prepare_errors.clone();
+        let hot_statements: Arc<HotStatements> = self.hot_statements.clone();
+
+        tokio::spawn(async move {
+            for query in queries {
+                Self::prepare_statement(&client, &cache, prepare_errors.as_deref(), &hot_statements, &query)
+                    .await
+                    .ok();
+            }
+        });
     }
 
     pub async fn process_queue(&mut self) {
+        self.prepare_hot_statements();
+
         while let Some(cmd) = self.receiver.recv().await {
-            let Ok(permit) = self.semaphore.clone().acquire_owned().await else {
+            self.prepare_hot_statements();
+
+            let Ok(waiting) = self.waiting.clone().acquire_owned().await else {
                 break;
             };
 
-            match cmd {
-                DbCommand::Execute { query, args, reply } => {
-                    let statement: Statement = match self.prepare_statement(query.clone()).await {
-                        Ok(statement) => statement,
-                        Err(e) => {
-                            reply.send(Err(e)).ok();
-                            continue;
-                        }
-                    };
+            // Simple commands carry no arguments: they skip `prepare` and the statement cache and
+            // run through the simple query protocol, which accepts several statements at once.
+            let (query, args, mut reply): (Arc<str>, Option<Vec<SqlArg>>, DbReplySender) = match cmd {
+                DbCommand::Execute { query, args, reply } => (query, Some(args), reply),
+                DbCommand::Simple { sql, reply } => (sql, None, reply),
+            };
 
-                    let client: Arc<Client> = self.client.clone();
-                    tokio::spawn(async move {
-                        let params: Vec<&(dyn ToSql + Sync)> = args.iter().map(|arg: &SqlArg| arg.as_sql()).collect();
+            if reply.is_closed() {
+                continue;
+            }
+
+            let reservation: PermitReservation = PermitReservation::new(self.semaphore.clone()).await;
+            let client: Arc<Client> = self.client.clone();
+            let cache: Arc<StatementCache> = self.cache.clone();
+            let prepare_errors: Option<Arc<PrepareErrors>> = self.prepare_errors.clone();
+            let hot_statements: Arc<HotStatements> = self.hot_statements.clone();
+            let stats: Arc<WorkerStats> = self.stats.clone();
+            stats.waiting.fetch_add(1, Ordering::Relaxed);
 
-                        let row_set: Result<RowSet, DatabaseError> = match client.query(&statement, &params).await {
-                            Ok(rows) => Ok(RowSet::from_pg_rows(rows)),
+            tokio::spawn(async move {
+                let permit: Option<OwnedSemaphorePermit> =
+                    reservation.acquire(|cx: &mut Context| reply.poll_closed(cx)).await;
+
+                stats.waiting.fetch_sub(1, Ordering::Relaxed);
+                drop(waiting);
+
+                let Some(permit) = permit else {
+                    return;
+                };
+
+                stats.inflight.fetch_add(1, Ordering::Relaxed);
+                let prepared: Arc<str> = query.clone();
+                let execution = CatchUnwind::new(Box::pin(async move {
+                    let Some(args) = args else {
+                        return match client.batch_execute(&prepared).await {
+                            Ok(()) => Ok(RowSet::from_pg_rows(Vec::new())),
                             Err(e) => Err(DatabaseError::Postgres(e)),
                         };
+                    };
 
-                        reply.send(row_set).ok();
-                        drop(permit);
+                    let statement: Statement =
+                        Self::prepare_statement(&client, &cache, prepare_errors.as_deref(), &hot_statements, &prepared)
+                            .await?;
+                    let params: Vec<&(dyn ToSql + Sync)> = args.iter().map(|arg: &SqlArg| arg.as_sql()).collect();
+
+                    match client.query(&statement, &params).await {
+                        Ok(rows) => Ok(RowSet::from_pg_rows(rows)),
+                        Err(e) => Err(DatabaseError::Postgres(e)),
+                    }
+                }));
+
+                let mut execution = pin!(execution);
+                let completed: Option<Result<Result<RowSet, DatabaseError>, PanicPayload>> =
+                    future::poll_fn(|cx: &mut Context| {
+                        if let Poll::Ready(output) = execution.as_mut().poll(cx) {
+                            return Poll::Ready(Some(output));
+                        }
+
+                        reply.poll_closed(cx).map(|()| None)
+                    })
+                    .await;
+
+                if let Some(output) = completed {
+                    let row_set: Result<RowSet, DatabaseError> = output.unwrap_or_else(|payload: PanicPayload| {
+                        Err(DatabaseError::WorkerPanic {
+                            message: panic_message(&payload).into(),
+                            query,
+                        })
                     });
+
+                    reply.send(row_set).ok();
                 }
-            }
+
+                stats.inflight.fetch_sub(1, Ordering::Relaxed);
+                drop(permit);
+            });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::runtime::{Builder, Runtime};
+    use tokio::task::JoinHandle;
+
+    #[test]
+    fn test_reservations_acquire_in_arrival_order() {
+        let runtime: Runtime = Builder::new_multi_thread().worker_threads(2).build().unwrap();
+        let order: Arc<Mutex<Vec<usize>>> = Arc::default();
+
+        runtime.block_on(async {
+            let semaphore: Arc<Semaphore> = Arc::new(Semaphore::new(1));
+            let held: OwnedSemaphorePermit = semaphore.clone().acquire_owned().await.unwrap();
+
+            let mut reservations: Vec<PermitReservation> = Vec::new();
+            for _ in 0..4 {
+                reservations.push(PermitReservation::new(semaphore.clone()).await);
+            }
+
+            let tasks: Vec<JoinHandle<()>> = reservations
+                .into_iter()
+                .enumerate()
+                .rev()
+                .map(|(idx, reservation): (usize, PermitReservation)| {
+                    let order: Arc<Mutex<Vec<usize>>> = order.clone();
+
+                    tokio::spawn(async move {
+                        let permit: Option<OwnedSemaphorePermit> = reservation.acquire(|_| Poll::Pending).await;
+                        order.lock().unwrap().push(idx);
+                        drop(permit);
+                    })
+                })
+                .collect();
+
+            drop(held);
+
+            for task in tasks {
+                task.await.unwrap();
+            }
+        });
+
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_reservation_gives_up_when_caller_is_gone() {
+        let runtime: Runtime = Builder::new_current_thread().build().unwrap();
+
+        runtime.block_on(async {
+            let semaphore: Arc<Semaphore> = Arc::new(Semaphore::new(1));
+            let _held: OwnedSemaphorePermit = semaphore.clone().acquire_owned().await.unwrap();
+
+            let reservation: PermitReservation = PermitReservation::new(semaphore.clone()).await;
+            assert!(reservation.acquire(|_| Poll::Ready(())).await.is_none());
+            assert_eq!(semaphore.available_permits(), 0);
+        });
+    }
+
+    #[test]
+    fn test_prepare_errors_ignore_non_sql_failures_and_expire() {
+        let query: Arc<str> = Arc::from("SELECT * FROM missing_table");
+        let prepare_errors: PrepareErrors = PrepareErrors::new(Duration::from_secs(60));
+
+        prepare_errors.record(&query, &DatabaseError::PoolClosed);
+        assert!(prepare_errors.check(&query).is_ok());
+
+        prepare_errors
+            .lock()
+            .insert(query.clone(), (Instant::now(), Arc::from("relation does not exist")));
+        assert!(matches!(
+            prepare_errors.check(&query),
+            Err(DatabaseError::CachedPrepareError(message)) if &*message == "relation does not exist"
+        ));
+
+        let expired: PrepareErrors = PrepareErrors::new(Duration::ZERO);
+        expired
+            .lock()
+            .insert(query.clone(), (Instant::now(), Arc::from("relation does not exist")));
+        assert!(expired.check(&query).is_ok());
+    }
+
+    #[test]
+    fn test_hot_statements_are_published_once_and_capped() {
+        let users: Arc<str> = Arc::from("SELECT * FROM users WHERE id = $1");
+        let hot_statements: HotStatements = HotStatements::new([users.clone()]);
+
+        assert_eq!(hot_statements.since(0), vec![users.clone()]);
+        assert!(hot_statements.since(1).is_empty());
+
+        hot_statements.publish(&users);
+        hot_statements.publish(&Arc::from("SELECT 1"));
+        assert_eq!(hot_statements.since(1), vec![Arc::<str>::from("SELECT 1")]);
+
+        (0..HOT_STATEMENT_LIMIT).for_each(|idx: usize| hot_statements.publish(&Arc::from(format!("SELECT {idx}"))));
+        assert_eq!(hot_statements.since(0).len(), HOT_STATEMENT_LIMIT);
+    }
+}
@@ -1,4 +1,8 @@
+use std::borrow::Cow;
+
 use super::decode;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::ser::{Serialize, Serializer};
 use serde_json::Value;
@@ -55,6 +59,28 @@ impl DbValue {
             _ => DbValue::Null,
         }
     }
+
+    /// The value as text: dates and times in ISO 8601 (RFC 3339 for `TimestampTz`), bytes in
+    /// standard base64 and JSON in its compact form. `Null` has no text.
+    pub fn to_text(&self) -> Option<Cow<'_, str>> {
+        let text: Cow<str> = match self {
+            DbValue::Null => return None,
+            DbValue::Bool(v) => Cow::Borrowed(if *v { "true" } else { "false" }),
+            DbValue::I64(v) => Cow::Owned(v.to_string()),
+            DbValue::U64(v) => Cow::Owned(v.to_string()),
+            DbValue::F64(v) => Cow::Owned(v.to_string()),
+            DbValue::String(v) => Cow::Borrowed(v),
+            DbValue::Json(v) => Cow::Owned(v.to_string()),
+            DbValue::Uuid(v) => Cow::Owned(v.to_string()),
+            DbValue::Date(v) => Cow::Owned(v.to_string()),
+            DbValue::Time(v) => Cow::Owned(v.to_string()),
+            DbValue::Timestamp(v) => Cow::Owned(v.to_string()),
+            DbValue::TimestampTz(v) => Cow::Owned(v.to_rfc3339()),
+            DbValue::Bytes(v) => Cow::Owned(BASE64.encode(v)),
+        };
+
+        Some(text)
+    }
 }
 
 impl Serialize for DbValue {
@@ -71,10 +97,9 @@ impl Serialize for DbValue {
             DbValue::String(v) => serializer.serialize_str(v),
             DbValue::Json(v) => v.serialize(serializer),
             DbValue::Uuid(v) => serializer.collect_str(v),
-            DbValue::Date(v) => serializer.serialize_str(&v.to_string()),
-            DbValue::Time(v) => serializer.serialize_str(&v.to_string()),
-            DbValue::Timestamp(v) => serializer.serialize_str(&v.to_string()),
-            DbValue::TimestampTz(v) => serializer.serialize_str(&v.to_rfc3339()),
+            DbValue::Date(_) | DbValue::Time(_) | DbValue::Timestamp(_) | DbValue::TimestampTz(_) => {
+                serializer.serialize_str(&self.to_text().unwrap_or_default())
+            }
             DbValue::Bytes(v) => serializer.serialize_bytes(v),
         }
     }
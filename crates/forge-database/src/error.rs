@@ -1,21 +1,80 @@
-use std::{fmt::Debug, io};
+use std::{fmt::Debug, io, sync::Arc, time::Duration};
 
-use super::database::DbCommand;
+use super::database::{DbCommand, MAX_CHANNEL_LEN, MAX_NOTIFY_PAYLOAD_LEN};
 use thiserror::Error;
-use tokio::sync::{mpsc::error::SendError, oneshot::error::RecvError};
+use tokio::sync::{mpsc::error::TrySendError, oneshot::error::RecvError};
+use tokio::task::JoinError;
 use tokio_postgres::error::DbError;
 
 #[derive(Error, Debug)]
 pub enum DatabaseError {
-    #[error("database connection pool is closed or shutting down: {0}")]
-    PoolClosed(#[from] SendError<DbCommand>),
+    #[error("invalid database options: \"{0}\" must be greater than 0")]
+    InvalidOptions(&'static str),
+
+    #[error("invalid database url: {0}")]
+    InvalidUrl(String),
+
+    #[error("database connection pool is closed or shutting down")]
+    PoolClosed,
+
+    #[error("database connection pool is exhausted: worker #{0} queue is full")]
+    PoolExhausted(usize),
 
     #[error("database worker terminated without responding")]
     NoResponse(#[from] RecvError),
 
+    #[error("database worker panicked while executing \"{query}\": {message}")]
+    WorkerPanic { query: Arc<str>, message: String },
+
+    #[error("invalid notification channel \"{0}\": expected 1 to {MAX_CHANNEL_LEN} bytes without NUL characters")]
+    InvalidNotifyChannel(String),
+
+    #[error("notification payload is {0} bytes, must be shorter than {MAX_NOTIFY_PAYLOAD_LEN} bytes")]
+    NotifyPayloadTooLarge(usize),
+
+    #[error("expected exactly one row, got {0}")]
+    UnexpectedRowCount(usize),
+
+    #[error("column \"{column}\" is not {expected}")]
+    UnexpectedColumnType { column: Arc<str>, expected: &'static str },
+
+    #[error("database query timed out after {0:?}")]
+    Timeout(Duration),
+
+    #[error("{0} (cached from a recent failed prepare)")]
+    CachedPrepareError(Arc<str>),
+
+    #[error("database worker #{0} failed to start: {1}")]
+    WorkerStartup(usize, Box<DatabaseError>),
+
+    #[error("timed out after {0:?} waiting for the first database connection")]
+    ConnectTimeout(Duration),
+
+    #[error("failed to apply database session settings: {0}")]
+    SessionSettings(tokio_postgres::Error),
+
+    #[error("database worker #{0} failed health check: {1}")]
+    Unhealthy(usize, Box<DatabaseError>),
+
+    #[error("database task failed to complete: {0}")]
+    Task(#[from] JoinError),
+
     #[error("database transport layer error: {0}")]
     Transport(#[from] io::Error),
 
     #[error("{}", .0.as_db_error().map(|db_err: &DbError| db_err.to_string()).unwrap_or_else(|| .0.to_string()))]
     Postgres(#[from] tokio_postgres::Error),
 }
+
+impl DatabaseError {
+    pub fn from_try_send(idx: usize, e: TrySendError<DbCommand>) -> Self {
+        match e {
+            TrySendError::Full(_) => DatabaseError::PoolExhausted(idx),
+            TrySendError::Closed(_) => DatabaseError::PoolClosed,
+        }
+    }
+
+    pub fn is_overloaded(&self) -> bool {
+        matches!(self, DatabaseError::PoolExhausted(_))
+    }
+}
@@ -3,6 +3,8 @@ mod db_connection;
 mod db_value;
 mod error;
 mod macros;
+mod pool_status;
+mod query_builder;
 mod row_set;
 mod sql_args;
 
@@ -10,5 +12,7 @@ pub use database::{Database, DatabaseOptions};
 pub use db_connection::DbConnection;
 pub use db_value::DbValue;
 pub use error::DatabaseError;
-pub use row_set::RowSet;
+pub use pool_status::{PoolStatus, WorkerStatus};
+pub use query_builder::QueryBuilder;
+pub use row_set::{ColumnType, CsvRecords, RowSet};
 pub use sql_args::SqlArg;
@@ -4,3 +4,35 @@ macro_rules! decode {
         $ctx.0.get::<usize, Option<$t>>($ctx.1).map($v).unwrap_or(DbValue::Null)
     };
 }
+
+#[macro_export]
+macro_rules! sql_args {
+    ($($arg:expr),* $(,)?) => {
+        ::std::vec![$($crate::SqlArg::from($arg)),*]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SqlArg;
+
+    #[test]
+    fn test_sql_args_mixed_types() {
+        let username: &str = "john_doe";
+        let active: bool = true;
+        let email: Option<String> = None;
+        let age: Option<i32> = Some(42);
+
+        let args: Vec<SqlArg> = sql_args![username, active, email, age];
+
+        assert!(matches!(args.as_slice(), [
+            SqlArg::Text(username),
+            SqlArg::Bool(true),
+            SqlArg::Null,
+            SqlArg::Integer(42),
+        ] if username == "john_doe"));
+
+        let empty: Vec<SqlArg> = sql_args![];
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use serde::Serialize;
+
+#[derive(Debug, Default)]
+pub(crate) struct WorkerStats {
+    pub inflight: AtomicUsize,
+    pub waiting: AtomicUsize,
+    pub alive: AtomicBool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WorkerStatus {
+    pub alive: bool,
+    pub inflight: usize,
+    pub queued: usize,
+}
+
+impl WorkerStatus {
+    pub(crate) fn new(stats: &WorkerStats, queued: usize) -> Self {
+        Self {
+            queued: queued + stats.waiting.load(Ordering::Relaxed),
+            alive: stats.alive.load(Ordering::Relaxed),
+            inflight: stats.inflight.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolStatus {
+    pub alive: usize,
+    pub total: usize,
+    pub inflight: usize,
+    pub queued: usize,
+    pub workers: Vec<WorkerStatus>,
+}
+
+impl PoolStatus {
+    pub fn is_healthy(&self) -> bool {
+        self.alive == self.total
+    }
+}
+
+impl FromIterator<WorkerStatus> for PoolStatus {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = WorkerStatus>,
+    {
+        let workers: Vec<WorkerStatus> = iter.into_iter().collect();
+
+        Self {
+            total: workers.len(),
+            alive: workers.iter().filter(|worker: &&WorkerStatus| worker.alive).count(),
+            inflight: workers.iter().map(|worker: &WorkerStatus| worker.inflight).sum(),
+            queued: workers.iter().map(|worker: &WorkerStatus| worker.queued).sum(),
+            workers,
+        }
+    }
+}
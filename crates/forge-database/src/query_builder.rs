@@ -0,0 +1,91 @@
+use std::fmt::Write;
+
+use super::sql_args::SqlArg;
+
+const EMPTY_LIST: &str = "NULL";
+
+#[derive(Debug, Default, Clone)]
+pub struct QueryBuilder {
+    sql: String,
+    args: Vec<SqlArg>,
+}
+
+impl QueryBuilder {
+    pub fn new(sql: impl Into<String>) -> Self {
+        Self {
+            sql: sql.into(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn push(mut self, sql: &str) -> Self {
+        self.sql.push_str(sql);
+        self
+    }
+
+    pub fn push_arg(mut self, arg: impl Into<SqlArg>) -> Self {
+        self.args.push(arg.into());
+        write!(self.sql, "${}", self.args.len()).ok();
+        self
+    }
+
+    pub fn push_list<I>(mut self, args: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<SqlArg>,
+    {
+        let start: usize = self.args.len();
+        self.args.extend(args.into_iter().map(Into::into));
+
+        if start == self.args.len() {
+            return self.push(EMPTY_LIST);
+        }
+
+        for idx in start + 1..=self.args.len() {
+            let separator: &str = if idx == start + 1 { "" } else { ", " };
+            write!(self.sql, "{separator}${idx}").ok();
+        }
+
+        self
+    }
+
+    pub fn build(self) -> (String, Vec<SqlArg>) {
+        (self.sql, self.args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_list_expands_placeholders() {
+        let (sql, args): (String, Vec<SqlArg>) = QueryBuilder::new("SELECT * FROM users WHERE org = ")
+            .push_arg("acme")
+            .push(" AND id IN (")
+            .push_list([7, 8, 9])
+            .push(") LIMIT ")
+            .push_arg(10)
+            .build();
+
+        assert_eq!(sql, "SELECT * FROM users WHERE org = $1 AND id IN ($2, $3, $4) LIMIT $5");
+        assert!(matches!(args.as_slice(), [
+            SqlArg::Text(org),
+            SqlArg::Integer(7),
+            SqlArg::Integer(8),
+            SqlArg::Integer(9),
+            SqlArg::Integer(10),
+        ] if org == "acme"));
+    }
+
+    #[test]
+    fn test_empty_list_matches_nothing() {
+        let (sql, args): (String, Vec<SqlArg>) = QueryBuilder::new("SELECT * FROM users WHERE id IN (")
+            .push_list(Vec::<i64>::new())
+            .push(")")
+            .build();
+
+        assert_eq!(sql, "SELECT * FROM users WHERE id IN (NULL)");
+        assert!(args.is_empty());
+    }
+}
@@ -4,33 +4,188 @@ use super::DbValue;
 use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
 use tokio_postgres::{Column, Row};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnType {
+    pub oid: u32,
+    pub name: Arc<str>,
+}
+
+impl Serialize for ColumnType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.name)
+    }
+}
+
 #[derive(Debug)]
 pub struct RowSet {
     pub columns: Arc<[Arc<str>]>,
+    pub column_types: Arc<[ColumnType]>,
     pub rows: Vec<Vec<DbValue>>,
 }
 
 impl RowSet {
     pub fn from_pg_rows(rows: Vec<Row>) -> Self {
-        let columns: Arc<[Arc<str>]> = rows.first().map_or_else(
-            || Arc::from([]),
-            |row: &Row| {
-                row.columns()
-                    .iter()
-                    .map(|column: &Column| Arc::from(column.name()))
-                    .collect::<Arc<[Arc<str>]>>()
-            },
-        );
+        let (columns, column_types): (Vec<Arc<str>>, Vec<ColumnType>) = rows
+            .first()
+            .map(Row::columns)
+            .unwrap_or_default()
+            .iter()
+            .map(|column: &Column| {
+                let column_type: ColumnType = ColumnType {
+                    oid: column.type_().oid(),
+                    name: Arc::from(column.type_().name()),
+                };
+
+                (Arc::from(column.name()), column_type)
+            })
+            .unzip();
 
         Self {
-            columns,
+            columns: Arc::from(columns),
+            column_types: Arc::from(column_types),
             rows: rows.iter().map(DbValue::decode_row).collect(),
         }
     }
 
+    pub fn column_types(&self) -> &[ColumnType] {
+        &self.column_types
+    }
+
     pub fn as_objects(&self) -> RowSetAsObjects<'_> {
         RowSetAsObjects(self)
     }
+
+    pub fn as_objects_with_meta(&self) -> RowSetWithMeta<'_> {
+        RowSetWithMeta(self)
+    }
+
+    /// The rows as an RFC 4180 CSV document, with a header row of column names. See `csv_records`.
+    pub fn as_csv(&self) -> String {
+        self.csv_records().collect()
+    }
+
+    /// The header row followed by one CSV record per row, each ending in CRLF, for writing a large
+    /// result out as it is formatted instead of building the whole document first. A field is
+    /// quoted when it contains a comma, a double quote, CR or LF, and quotes inside it are
+    /// doubled. `NULL` is an empty field while an empty string is `""`, so the two stay
+    /// distinguishable. Values are formatted with `DbValue::to_text`.
+    pub fn csv_records(&self) -> CsvRecords<'_> {
+        CsvRecords {
+            row_set: self,
+            next: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CsvRecords<'a> {
+    row_set: &'a RowSet,
+    next: Option<usize>,
+}
+
+impl<'a> CsvRecords<'a> {
+    fn record<I, T>(fields: I) -> String
+    where
+        I: IntoIterator<Item = Option<T>>,
+        T: AsRef<str>,
+    {
+        let mut record: String = String::new();
+
+        for (idx, field) in fields.into_iter().enumerate() {
+            if idx > 0 {
+                record.push(',');
+            }
+
+            let Some(field) = field else {
+                continue;
+            };
+
+            let field: &str = field.as_ref();
+
+            if field.is_empty() || field.contains([',', '"', '\r', '\n']) {
+                record.push('"');
+                record.push_str(&field.replace('"', "\"\""));
+                record.push('"');
+            } else {
+                record.push_str(field);
+            }
+        }
+
+        record.push_str("\r\n");
+        record
+    }
+}
+
+impl<'a> Iterator for CsvRecords<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let record: String = match self.next {
+            None => Self::record(self.row_set.columns.iter().map(Some)),
+            Some(idx) => Self::record(self.row_set.rows.get(idx)?.iter().map(DbValue::to_text)),
+        };
+
+        self.next = Some(self.next.map_or(0, |idx: usize| idx + 1));
+        Some(record)
+    }
+}
+
+#[derive(Debug)]
+pub struct RowSetWithMeta<'a>(pub &'a RowSet);
+
+impl<'a> Serialize for RowSetWithMeta<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let row_set: &RowSet = self.0;
+        let mut map: <S as Serializer>::SerializeMap = serializer.serialize_map(Some(2))?;
+
+        map.serialize_entry("columns", &ColumnsMeta(row_set))?;
+        map.serialize_entry("rows", &RowSetAsObjects(row_set))?;
+        map.end()
+    }
+}
+
+#[derive(Debug)]
+struct ColumnMeta<'a> {
+    name: &'a str,
+    column_type: &'a ColumnType,
+}
+
+impl<'a> Serialize for ColumnMeta<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map: <S as Serializer>::SerializeMap = serializer.serialize_map(Some(2))?;
+
+        map.serialize_entry("name", self.name)?;
+        map.serialize_entry("type", self.column_type)?;
+        map.end()
+    }
+}
+
+#[derive(Debug)]
+struct ColumnsMeta<'a>(&'a RowSet);
+
+impl<'a> Serialize for ColumnsMeta<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let row_set: &RowSet = self.0;
+        let mut sequence: <S as Serializer>::SerializeSeq = serializer.serialize_seq(Some(row_set.columns.len()))?;
+
+        for (name, column_type) in row_set.columns.iter().zip(row_set.column_types.iter()) {
+            sequence.serialize_element(&ColumnMeta { name, column_type })?;
+        }
+
+        sequence.end()
+    }
 }
 
 #[derive(Debug)]
@@ -75,3 +230,118 @@ impl<'a> Serialize for RowAsObject<'a> {
         map.end()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, NaiveDate};
+    use serde_json::json;
+
+    #[test]
+    fn test_csv_quotes_only_fields_that_need_it() {
+        let row_set: RowSet = RowSet {
+            columns: Arc::from([Arc::from("id"), Arc::from("note"), Arc::from("extra")]),
+            column_types: Arc::from([]),
+            rows: vec![
+                vec![DbValue::I64(1), DbValue::String(String::from("plain")), DbValue::Null],
+                vec![
+                    DbValue::I64(2),
+                    DbValue::String(String::from("a, \"quoted\"\r\nline")),
+                    DbValue::String(String::new()),
+                ],
+                vec![
+                    DbValue::Bool(true),
+                    DbValue::Json(json!({ "a": 1, "b": [2] })),
+                    DbValue::Bytes(b"\x00\xffhi".to_vec()),
+                ],
+                vec![
+                    DbValue::Date(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()),
+                    DbValue::TimestampTz(DateTime::from_timestamp(784_111_777, 0).unwrap()),
+                    DbValue::F64(-0.5),
+                ],
+            ],
+        };
+
+        assert_eq!(
+            row_set.as_csv(),
+            "id,note,extra\r\n\
+             1,plain,\r\n\
+             2,\"a, \"\"quoted\"\"\r\nline\",\"\"\r\n\
+             true,\"{\"\"a\"\":1,\"\"b\"\":[2]}\",AP9oaQ==\r\n\
+             2024-02-29,1994-11-06T08:49:37+00:00,-0.5\r\n"
+        );
+
+        assert_eq!(row_set.csv_records().count(), 5);
+    }
+
+    fn users() -> RowSet {
+        RowSet {
+            columns: Arc::from([Arc::from("id"), Arc::from("username"), Arc::from("active")]),
+            column_types: Arc::from([
+                ColumnType {
+                    oid: 20,
+                    name: Arc::from("int8"),
+                },
+                ColumnType {
+                    oid: 25,
+                    name: Arc::from("text"),
+                },
+                ColumnType {
+                    oid: 16,
+                    name: Arc::from("bool"),
+                },
+            ]),
+            rows: vec![
+                vec![
+                    DbValue::I64(1),
+                    DbValue::String(String::from("john_doe")),
+                    DbValue::Bool(true),
+                ],
+                vec![DbValue::I64(2), DbValue::Null, DbValue::Null],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_column_types_follow_column_order() {
+        let row_set: RowSet = users();
+        let names: Vec<&str> = row_set
+            .column_types()
+            .iter()
+            .map(|column_type: &ColumnType| column_type.name.as_ref())
+            .collect();
+
+        assert_eq!(names, ["int8", "text", "bool"]);
+        assert_eq!(row_set.column_types()[1].oid, 25);
+    }
+
+    #[test]
+    fn test_objects_with_meta_pair_names_types_and_nulls() {
+        let row_set: RowSet = users();
+
+        assert_eq!(
+            serde_json::to_value(row_set.as_objects_with_meta()).unwrap(),
+            json!({
+                "columns": [
+                    { "name": "id", "type": "int8" },
+                    { "name": "username", "type": "text" },
+                    { "name": "active", "type": "bool" },
+                ],
+                "rows": [
+                    { "id": 1, "username": "john_doe", "active": true },
+                    { "id": 2, "username": null, "active": null },
+                ],
+            })
+        );
+
+        let empty: RowSet = RowSet {
+            columns: Arc::from([]),
+            column_types: Arc::from([]),
+            rows: Vec::new(),
+        };
+        assert_eq!(
+            serde_json::to_value(empty.as_objects_with_meta()).unwrap(),
+            json!({ "columns": [], "rows": [] })
+        );
+    }
+}
@@ -33,3 +33,41 @@ impl SqlArg {
         }
     }
 }
+
+macro_rules! impl_from_for_sql_arg {
+    ($($t:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$t> for SqlArg {
+                fn from(value: $t) -> Self {
+                    SqlArg::$variant(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_sql_arg! {
+    bool => Bool,
+    i16 => Integer,
+    i32 => Integer,
+    i64 => Integer,
+    f32 => Float,
+    f64 => Float,
+    Vec<u8> => Binary,
+    &[u8] => Binary,
+    Value => Json,
+    String => Text,
+    &str => Text,
+    DateTime<Utc> => Timestamp,
+    NaiveDate => Date,
+    Uuid => Uuid,
+}
+
+impl<T> From<Option<T>> for SqlArg
+where
+    T: Into<SqlArg>,
+{
+    fn from(value: Option<T>) -> Self {
+        value.map_or(SqlArg::Null, Into::into)
+    }
+}
@@ -0,0 +1,500 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Formatter};
+use std::future;
+use std::mem;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use super::{HttpError, HttpStatus};
+use serde::de::DeserializeOwned;
+
+#[derive(Default)]
+struct Shared {
+    chunks: VecDeque<Vec<u8>>,
+    finished: bool,
+    error: Option<HttpError>,
+    waiting: bool,
+    waker: Option<Waker>,
+}
+
+enum Source {
+    Buffered(Option<Vec<u8>>),
+    Channel(Rc<RefCell<Shared>>),
+}
+
+/// A request body read chunk by chunk, returned by `Request::body_stream`. On routes registered
+/// with `stream_body = true` the chunks are read from the connection only when the handler asks
+/// for the next one, so a large upload is never held in memory as a whole. Everywhere else the
+/// already-buffered body is yielded as a single chunk.
+///
+/// `next_json` decodes a body of JSON values one after another (such as newline-delimited JSON),
+/// and `next_array_item` the elements of a body that is one JSON array, such as a bulk import.
+/// Both buffer one value at a time, so the values must own their data: unlike `Request::json`,
+/// which keeps small bodies buffered and can lend out their strings, the bytes a streamed value
+/// was decoded from are dropped as soon as it is parsed. Any other single JSON document is
+/// buffered whole before it is parsed.
+pub struct BodyStream {
+    source: Source,
+    pending: Vec<u8>,
+    scan: JsonScan,
+    array: ArrayState,
+}
+
+/// How far `next_array_item` has read through the array.
+#[derive(Default)]
+enum ArrayState {
+    #[default]
+    Before,
+    Open,
+    Separated,
+    Item,
+    Closed,
+}
+
+/// Follows the JSON in `BodyStream::pending` just closely enough to tell where its first value
+/// ends, so that value is parsed once, after it has fully arrived.
+#[derive(Default)]
+struct JsonScan {
+    scanned: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    in_scalar: bool,
+}
+
+impl JsonScan {
+    /// Scans the bytes appended since the last call and returns where the first value ends, once
+    /// it has. A number or literal is only known to end at the byte after it.
+    fn value_end(&mut self, pending: &[u8]) -> Option<usize> {
+        for (idx, &byte) in pending.iter().enumerate().skip(self.scanned) {
+            self.scanned = idx + 1;
+
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => {
+                        self.in_string = false;
+
+                        if self.depth == 0 {
+                            return Some(idx + 1);
+                        }
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            // A number or literal only ends at whitespace or at whatever closes or separates it.
+            if self.depth == 0 && self.in_scalar && (byte.is_ascii_whitespace() || matches!(byte, b',' | b']' | b'}')) {
+                return Some(idx);
+            }
+
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+
+                    if self.depth == 0 {
+                        return Some(idx + 1);
+                    }
+                }
+                _ if byte.is_ascii_whitespace() => {}
+                _ => self.in_scalar |= self.depth == 0,
+            }
+        }
+
+        None
+    }
+
+    /// Whether the bytes scanned so far end in the middle of a number or literal.
+    fn ends_in_scalar(&self) -> bool {
+        self.in_scalar && self.depth == 0 && !self.in_string
+    }
+}
+
+/// The connection's end of a streamed body. Chunks are only wanted while the handler is waiting
+/// for one, which is what keeps the connection from reading ahead of the handler.
+pub struct BodySender {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl BodyStream {
+    pub fn channel() -> (BodySender, BodyStream) {
+        let shared: Rc<RefCell<Shared>> = Rc::default();
+
+        let sender: BodySender = BodySender {
+            shared: Rc::clone(&shared),
+        };
+
+        (
+            sender,
+            BodyStream {
+                source: Source::Channel(shared),
+                pending: Vec::new(),
+                scan: JsonScan::default(),
+                array: ArrayState::default(),
+            },
+        )
+    }
+
+    pub(crate) fn buffered(body: Vec<u8>) -> Self {
+        Self {
+            source: Source::Buffered(Some(body).filter(|body: &Vec<u8>| !body.is_empty())),
+            pending: Vec::new(),
+            scan: JsonScan::default(),
+            array: ArrayState::default(),
+        }
+    }
+
+    /// Returns the next chunk, or `None` once the whole body has been read. Malformed framing is
+    /// reported as a `400 Bad Request` error.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, HttpError> {
+        // Bytes `next_json` read past the last value it returned.
+        if !self.pending.is_empty() {
+            self.scan = JsonScan::default();
+            return Ok(Some(mem::take(&mut self.pending)));
+        }
+
+        future::poll_fn(|cx: &mut Context| self.poll_chunk(cx)).await
+    }
+
+    pub async fn read_to_end(&mut self) -> Result<Vec<u8>, HttpError> {
+        let mut body: Vec<u8> = Vec::new();
+
+        while let Some(chunk) = self.chunk().await? {
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(body)
+    }
+
+    /// Decodes the next JSON value, or returns `None` once the body ends. Values own their data,
+    /// since the bytes they were decoded from are dropped as soon as they are parsed. A value that
+    /// is malformed or cut off by the end of the body is a `400 Bad Request` error, and one that
+    /// grows past `max_value_size` bytes before it ends is a `413 Payload Too Large` error.
+    pub async fn next_json<T>(&mut self, max_value_size: usize) -> Result<Option<T>, HttpError>
+    where
+        T: DeserializeOwned,
+    {
+        loop {
+            if let Some(end) = self.scan.value_end(&self.pending) {
+                return self.take_json(end).map(Some);
+            }
+
+            if self.pending.len() > max_value_size {
+                return Err(HttpError::new(
+                    HttpStatus::PayloadTooLarge,
+                    format!("JSON value exceeds {max_value_size} bytes"),
+                ));
+            }
+
+            match future::poll_fn(|cx: &mut Context| self.poll_chunk(cx)).await? {
+                Some(chunk) => self.pending.extend_from_slice(&chunk),
+                None if self.pending.iter().all(u8::is_ascii_whitespace) => {
+                    self.pending.clear();
+                    self.scan = JsonScan::default();
+                    return Ok(None);
+                }
+                None if self.scan.ends_in_scalar() => return self.take_json(self.pending.len()).map(Some),
+                None => return Err(Self::invalid_json("Body ends in the middle of a value")),
+            }
+        }
+    }
+
+    /// Decodes the next element of a body that is a single JSON array, or returns `None` after its
+    /// closing `]`. A body that is not one well-formed array is a `400 Bad Request` error, and an
+    /// element that grows past `max_value_size` bytes before it ends is a `413 Payload Too Large`
+    /// error.
+    pub async fn next_array_item<T>(&mut self, max_value_size: usize) -> Result<Option<T>, HttpError>
+    where
+        T: DeserializeOwned,
+    {
+        loop {
+            let token: Option<u8> = self.next_token().await?;
+
+            self.array = match (&self.array, token) {
+                (ArrayState::Closed, None) => return Ok(None),
+                (ArrayState::Closed, Some(_)) => return Err(Self::invalid_json("Unexpected data after the array")),
+                (ArrayState::Before, None) => return Err(Self::invalid_json("Expected a JSON array")),
+                (_, None) => return Err(Self::invalid_json("Body ends in the middle of the array")),
+                (ArrayState::Before, Some(b'[')) => ArrayState::Open,
+                (ArrayState::Before, Some(_)) => return Err(Self::invalid_json("Expected a JSON array")),
+                (ArrayState::Open | ArrayState::Item, Some(b']')) => ArrayState::Closed,
+                (ArrayState::Item, Some(b',')) => ArrayState::Separated,
+                (ArrayState::Item, Some(_)) => return Err(Self::invalid_json("Expected `,` or `]` after an element")),
+                (ArrayState::Open | ArrayState::Separated, Some(_)) => {
+                    let item: Option<T> = self.next_json(max_value_size).await?;
+                    self.array = ArrayState::Item;
+                    return Ok(item);
+                }
+            };
+
+            self.pending.drain(..1);
+        }
+    }
+
+    /// Drops the whitespace at the start of `pending`, reading more of the body as needed, and
+    /// returns the byte that follows it, or `None` once the body ends.
+    async fn next_token(&mut self) -> Result<Option<u8>, HttpError> {
+        loop {
+            let start: usize = self
+                .pending
+                .iter()
+                .position(|byte: &u8| !byte.is_ascii_whitespace())
+                .unwrap_or(self.pending.len());
+            self.pending.drain(..start);
+            self.scan = JsonScan::default();
+
+            if let Some(&byte) = self.pending.first() {
+                return Ok(Some(byte));
+            }
+
+            match future::poll_fn(|cx: &mut Context| self.poll_chunk(cx)).await? {
+                Some(chunk) => self.pending = chunk,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn take_json<T>(&mut self, end: usize) -> Result<T, HttpError>
+    where
+        T: DeserializeOwned,
+    {
+        let value: T = serde_json::from_slice(&self.pending[..end]).map_err(Self::invalid_json)?;
+        self.pending.drain(..end);
+        self.scan = JsonScan::default();
+        Ok(value)
+    }
+
+    fn invalid_json<E: fmt::Display>(e: E) -> HttpError {
+        HttpError::new(HttpStatus::BadRequest, format!("Invalid JSON body: {e}"))
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context) -> Poll<Result<Option<Vec<u8>>, HttpError>> {
+        let shared: &Rc<RefCell<Shared>> = match &mut self.source {
+            Source::Buffered(body) => return Poll::Ready(Ok(body.take())),
+            Source::Channel(shared) => shared,
+        };
+
+        let mut shared = shared.borrow_mut();
+
+        if let Some(chunk) = shared.chunks.pop_front() {
+            return Poll::Ready(Ok(Some(chunk)));
+        }
+
+        if let Some(e) = &shared.error {
+            return Poll::Ready(Err(HttpError::new(e.status, e.message.clone())));
+        }
+
+        if shared.finished {
+            return Poll::Ready(Ok(None));
+        }
+
+        shared.waiting = true;
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Debug for BodyStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let kind: &str = match self.source {
+            Source::Buffered(_) => "Buffered",
+            Source::Channel(_) => "Channel",
+        };
+
+        f.debug_struct("BodyStream").field("source", &kind).finish()
+    }
+}
+
+impl BodySender {
+    pub fn wants_data(&self) -> bool {
+        let shared = self.shared.borrow();
+        shared.waiting && shared.chunks.is_empty() && !shared.finished
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.shared.borrow().finished
+    }
+
+    pub fn send(&self, chunk: Vec<u8>) {
+        if !chunk.is_empty() {
+            self.shared.borrow_mut().chunks.push_back(chunk);
+            self.wake();
+        }
+    }
+
+    pub fn finish(&self) {
+        self.shared.borrow_mut().finished = true;
+        self.wake();
+    }
+
+    pub fn fail(&self, error: HttpError) {
+        let mut shared = self.shared.borrow_mut();
+        shared.error = Some(error);
+        shared.finished = true;
+        drop(shared);
+        self.wake();
+    }
+
+    fn wake(&self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.waiting = false;
+
+        if let Some(waker) = shared.waker.take() {
+            drop(shared);
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HttpStatus;
+    use std::pin::pin;
+    use std::str;
+
+    fn poll<F: Future>(future: F) -> Poll<F::Output> {
+        pin!(future).poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_channel_only_wants_data_while_consumer_waits() {
+        let (sender, mut stream): (BodySender, BodyStream) = BodyStream::channel();
+        assert!(!sender.wants_data());
+
+        assert!(poll(stream.chunk()).is_pending());
+        assert!(sender.wants_data());
+
+        sender.send(b"hello".to_vec());
+        assert!(!sender.wants_data());
+        assert_eq!(poll(stream.chunk()).map(Result::unwrap), Poll::Ready(Some(b"hello".to_vec())));
+
+        sender.finish();
+        assert_eq!(poll(stream.chunk()).map(Result::unwrap), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_channel_reports_errors_on_every_read() {
+        let (sender, mut stream): (BodySender, BodyStream) = BodyStream::channel();
+        sender.fail(HttpError::new(HttpStatus::BadRequest, "Malformed chunk"));
+
+        for _ in 0..2 {
+            let Poll::Ready(Err(e)) = poll(stream.chunk()) else {
+                panic!("Expected the framing error");
+            };
+            assert_eq!(e.status, HttpStatus::BadRequest);
+        }
+    }
+
+    #[test]
+    fn test_buffered_body_is_a_single_chunk() {
+        let mut stream: BodyStream = BodyStream::buffered(b"payload".to_vec());
+        assert_eq!(poll(stream.read_to_end()).map(Result::unwrap), Poll::Ready(b"payload".to_vec()));
+        assert_eq!(poll(stream.chunk()).map(Result::unwrap), Poll::Ready(None));
+
+        let mut empty: BodyStream = BodyStream::buffered(Vec::new());
+        assert_eq!(poll(empty.chunk()).map(Result::unwrap), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_json_values_are_decoded_across_chunks() {
+        let (sender, mut stream): (BodySender, BodyStream) = BodyStream::channel();
+        sender.send(b"{\"id\": 1}\n{\"id\"".to_vec());
+        sender.send(b": 2}\n[3".to_vec());
+
+        let first: Poll<Result<Option<serde_json::Value>, HttpError>> = poll(stream.next_json(1024));
+        assert_eq!(first.map(Result::unwrap), Poll::Ready(Some(serde_json::json!({"id": 1}))));
+
+        let second: Poll<Result<Option<serde_json::Value>, HttpError>> = poll(stream.next_json(1024));
+        assert_eq!(second.map(Result::unwrap), Poll::Ready(Some(serde_json::json!({"id": 2}))));
+
+        assert!(poll(stream.next_json::<serde_json::Value>(1024)).is_pending());
+        sender.send(b"]\n".to_vec());
+        sender.finish();
+
+        let third: Poll<Result<Option<Vec<u32>>, HttpError>> = poll(stream.next_json(1024));
+        assert_eq!(third.map(Result::unwrap), Poll::Ready(Some(vec![3])));
+        assert_eq!(poll(stream.next_json::<Vec<u32>>(1024)).map(Result::unwrap), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_array_items_are_decoded_one_at_a_time() {
+        let (sender, mut stream): (BodySender, BodyStream) = BodyStream::channel();
+        sender.send(b" [ {\"id\": 1}, 2".to_vec());
+
+        let first: Poll<Result<Option<serde_json::Value>, HttpError>> = poll(stream.next_array_item(1024));
+        assert_eq!(first.map(Result::unwrap), Poll::Ready(Some(serde_json::json!({"id": 1}))));
+        assert!(poll(stream.next_array_item::<serde_json::Value>(1024)).is_pending());
+
+        sender.send(b"3,\"a,]\"\n]\n".to_vec());
+        sender.finish();
+
+        let second: Poll<Result<Option<serde_json::Value>, HttpError>> = poll(stream.next_array_item(1024));
+        assert_eq!(second.map(Result::unwrap), Poll::Ready(Some(serde_json::json!(23))));
+
+        let third: Poll<Result<Option<serde_json::Value>, HttpError>> = poll(stream.next_array_item(1024));
+        assert_eq!(third.map(Result::unwrap), Poll::Ready(Some(serde_json::json!("a,]"))));
+
+        let end: Poll<Result<Option<serde_json::Value>, HttpError>> = poll(stream.next_array_item(1024));
+        assert_eq!(end.map(Result::unwrap), Poll::Ready(None));
+
+        let mut empty: BodyStream = BodyStream::buffered(b"[]".to_vec());
+        assert_eq!(poll(empty.next_array_item::<u32>(1024)).map(Result::unwrap), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_malformed_arrays_are_rejected() {
+        for body in [&b"{\"id\": 1}"[..], b"[1 2]", b"[1,", b"[1,]", b"[1] 2", b""] {
+            let mut stream: BodyStream = BodyStream::buffered(body.to_vec());
+            let result: Result<(), HttpError> = loop {
+                match poll(stream.next_array_item::<u32>(1024)) {
+                    Poll::Ready(Ok(Some(_))) => continue,
+                    Poll::Ready(Ok(None)) => panic!("Expected {:?} to be rejected", str::from_utf8(body)),
+                    Poll::Ready(Err(e)) => break Err(e),
+                    Poll::Pending => panic!("A buffered body never waits"),
+                }
+            };
+
+            assert_eq!(result.unwrap_err().status, HttpStatus::BadRequest);
+        }
+    }
+
+    #[test]
+    fn test_truncated_json_value_is_rejected() {
+        let mut stream: BodyStream = BodyStream::buffered(b"{\"id\": 1} {\"id\":".to_vec());
+        assert!(matches!(
+            poll(stream.next_json::<serde_json::Value>(1024)),
+            Poll::Ready(Ok(Some(_)))
+        ));
+
+        let Poll::Ready(Err(e)) = poll(stream.next_json::<serde_json::Value>(1024)) else {
+            panic!("Expected the truncated value to fail");
+        };
+        assert_eq!(e.status, HttpStatus::BadRequest);
+    }
+
+    #[test]
+    fn test_json_scalars_wait_for_their_end_and_values_are_capped() {
+        let (sender, mut stream): (BodySender, BodyStream) = BodyStream::channel();
+        sender.send(b"12".to_vec());
+        assert!(poll(stream.next_json::<u32>(1024)).is_pending());
+
+        sender.send(b"3 45".to_vec());
+        sender.finish();
+        assert_eq!(poll(stream.next_json::<u32>(1024)).map(Result::unwrap), Poll::Ready(Some(123)));
+        assert_eq!(poll(stream.next_json::<u32>(1024)).map(Result::unwrap), Poll::Ready(Some(45)));
+        assert_eq!(poll(stream.next_json::<u32>(1024)).map(Result::unwrap), Poll::Ready(None));
+
+        let mut large: BodyStream = BodyStream::buffered(format!("[\"{}\"", "a".repeat(64)).into_bytes());
+        let Poll::Ready(Err(e)) = poll(large.next_json::<Vec<String>>(16)) else {
+            panic!("Expected the value to exceed the limit");
+        };
+        assert_eq!(e.status, HttpStatus::PayloadTooLarge);
+    }
+}
@@ -0,0 +1,36 @@
+use std::borrow::Cow;
+use std::mem;
+use std::ops::Deref;
+
+use super::{HttpError, HttpStatus, Request};
+
+/// The raw request body, taken as a handler argument by `#[route]` handlers. Bodies are limited by
+/// the listener's `with_max_body_size` unless the route sets its own `max_body_size`, and the
+/// server answers larger ones with `413 Payload Too Large` while reading them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytes<'a>(pub Cow<'a, [u8]>);
+
+impl<'a> Bytes<'a> {
+    /// Moves the body out of `request`, leaving `Request::body` empty. `limit` is checked again here
+    /// for requests that did not come through the server.
+    pub fn from_request(request: &mut Request<'a>, limit: Option<usize>) -> Result<Self, HttpError> {
+        if let Some(limit) = limit
+            && request.body.len().max(request.content_length().unwrap_or(0)) > limit
+        {
+            return Err(HttpError::new(
+                HttpStatus::PayloadTooLarge,
+                format!("Request body exceeds {limit} bytes"),
+            ));
+        }
+
+        Ok(Self(mem::take(&mut request.body)))
+    }
+}
+
+impl Deref for Bytes<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
@@ -0,0 +1,48 @@
+const GZIP_CODINGS: [&str; 2] = ["gzip", "x-gzip"];
+const WILDCARD: &str = "*";
+
+pub fn accepts_gzip(accept_encoding: &str) -> bool {
+    let mut wildcard: Option<bool> = None;
+
+    for (coding, quality) in accept_encoding.split(',').filter_map(parse_coding) {
+        if GZIP_CODINGS.iter().any(|gzip: &&str| coding.eq_ignore_ascii_case(gzip)) {
+            return quality > 0.0;
+        }
+
+        if coding == WILDCARD {
+            wildcard = Some(quality > 0.0);
+        }
+    }
+
+    wildcard.unwrap_or(false)
+}
+
+fn parse_coding(part: &str) -> Option<(&str, f32)> {
+    let mut params = part.split(';').map(str::trim);
+    let coding: &str = params.next().filter(|coding: &&str| !coding.is_empty())?;
+
+    let quality: f32 = params
+        .find_map(|param: &str| param.strip_prefix("q=").or_else(|| param.strip_prefix("Q=")))
+        .map_or(Some(1.0), |quality: &str| quality.parse::<f32>().ok())?;
+
+    Some((coding, quality))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_gzip() {
+        assert!(accepts_gzip("gzip"));
+        assert!(accepts_gzip("deflate, GZIP;q=0.5"));
+        assert!(accepts_gzip("x-gzip"));
+        assert!(accepts_gzip("br, *"));
+
+        assert!(!accepts_gzip(""));
+        assert!(!accepts_gzip("br, deflate"));
+        assert!(!accepts_gzip("gzip;q=0"));
+        assert!(!accepts_gzip("gzip;q=0, *"));
+        assert!(!accepts_gzip("*;q=0"));
+    }
+}
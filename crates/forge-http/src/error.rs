@@ -1,8 +1,10 @@
 use std::io::Error;
 
-use super::HttpStatus;
+use super::{HttpStatus, Response};
 use thiserror::Error;
 
+const MAX_ECHOED_LEN: usize = 64;
+
 #[derive(Debug, Error)]
 #[error("[{}] {}: {}", u16::from(self.status), status, message)]
 pub struct HttpError {
@@ -17,6 +19,11 @@ impl HttpError {
             message: msg.into(),
         }
     }
+
+    /// Builds the JSON error response described on `Response::error`.
+    pub fn into_json_response<'a>(self) -> Response<'a> {
+        Response::error(self.status, self.message)
+    }
 }
 
 impl From<Error> for HttpError {
@@ -24,3 +31,81 @@ impl From<Error> for HttpError {
         HttpError::new(HttpStatus::InternalServerError, e.to_string())
     }
 }
+
+#[derive(Debug, Error, PartialEq)]
+pub enum HttpParseError {
+    #[error("Request line is empty or missing")]
+    EmptyRequest,
+
+    #[error("Request head is not valid UTF-8 at byte {0}")]
+    InvalidUtf8(usize),
+
+    #[error("Request line missing HTTP Method at byte {0}")]
+    MissingMethod(usize),
+
+    #[error("Request line missing URI Path at byte {0}")]
+    MissingPath(usize),
+
+    #[error("Request line missing HTTP Version at byte {0}")]
+    MissingVersion(usize),
+
+    #[error("Unsupported HTTP version \"{version}\" at byte {offset}")]
+    UnsupportedVersion { version: String, offset: usize },
+
+    #[error("Unknown or unsupported HTTP method \"{method}\" at byte {offset}")]
+    UnknownMethod { method: String, offset: usize },
+
+    #[error("Invalid CONNECT target \"{target}\" at byte {offset}, expected host:port")]
+    InvalidConnectTarget { target: String, offset: usize },
+
+    #[error("Invalid header format \"{header}\" at byte {offset}")]
+    InvalidHeader { header: String, offset: usize },
+
+    #[error("HTTP/1.1 request is missing the Host header")]
+    MissingHost,
+
+    #[error("Invalid Content-Length value \"{0}\"")]
+    InvalidContentLength(String),
+
+    #[error("Conflicting Content-Length values {0} and {1}")]
+    ConflictingContentLength(usize, usize),
+
+    #[error("Request has both Content-Length and Transfer-Encoding headers")]
+    AmbiguousFraming,
+
+    #[error("Unsupported Transfer-Encoding \"{0}\"")]
+    UnsupportedTransferEncoding(String),
+
+    #[error("Invalid chunk framing at byte {0}")]
+    InvalidChunk(usize),
+
+    #[error("Request body is incomplete: expected {expected} bytes, received {received}")]
+    IncompleteBody { expected: usize, received: usize },
+}
+
+/// Client bytes quoted in a parse error, cut to `MAX_ECHOED_LEN` characters and with quotes and
+/// control characters escaped, since the message is sent back to the client and may be logged.
+pub(crate) fn echoed(value: &str) -> String {
+    let mut echoed: String = value
+        .chars()
+        .take(MAX_ECHOED_LEN)
+        .flat_map(char::escape_debug)
+        .collect();
+
+    if value.chars().nth(MAX_ECHOED_LEN).is_some() {
+        echoed.push_str("...");
+    }
+
+    echoed
+}
+
+impl From<HttpParseError> for HttpError {
+    fn from(e: HttpParseError) -> Self {
+        let status: HttpStatus = match e {
+            HttpParseError::UnsupportedVersion { .. } => HttpStatus::HttpVersionNotSupported,
+            _ => HttpStatus::BadRequest,
+        };
+
+        HttpError::new(status, e.to_string())
+    }
+}
@@ -0,0 +1,49 @@
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+const WEAK_PREFIX: &str = "W/";
+
+pub fn from_bytes(bytes: &[u8]) -> String {
+    let hash: u64 = bytes.iter().fold(FNV_OFFSET_BASIS, |hash: u64, byte: &u8| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    });
+
+    format!("\"{hash:016x}\"")
+}
+
+pub fn weak_match(if_none_match: &str, etag: &str) -> bool {
+    let etag: &str = opaque_tag(etag);
+
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate: &str| candidate == "*" || opaque_tag(candidate) == etag)
+}
+
+fn opaque_tag(etag: &str) -> &str {
+    let etag: &str = etag.trim();
+    etag.strip_prefix(WEAK_PREFIX).unwrap_or(etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes_is_stable_and_quoted() {
+        assert_eq!(from_bytes(b""), "\"cbf29ce484222325\"");
+        assert_eq!(from_bytes(b"hello"), from_bytes(b"hello"));
+        assert_ne!(from_bytes(b"hello"), from_bytes(b"hellO"));
+    }
+
+    #[test]
+    fn test_weak_match() {
+        assert!(weak_match("\"abc\"", "\"abc\""));
+        assert!(weak_match("W/\"abc\"", "\"abc\""));
+        assert!(weak_match("\"abc\"", "W/\"abc\""));
+        assert!(weak_match("\"xyz\", W/\"abc\"", "\"abc\""));
+        assert!(weak_match("*", "\"abc\""));
+
+        assert!(!weak_match("\"abd\"", "\"abc\""));
+        assert!(!weak_match("abc", "\"abc\""));
+    }
+}
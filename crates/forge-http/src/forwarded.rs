@@ -0,0 +1,263 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use super::Headers;
+use thiserror::Error;
+
+const FORWARDED: &str = "forwarded";
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+const X_FORWARDED_HOST: &str = "x-forwarded-host";
+
+#[derive(Debug, Error, PartialEq)]
+#[error("Invalid trusted proxy \"{0}\", expected an IP address or a CIDR range")]
+pub struct InvalidProxy(String);
+
+/// An IP address or CIDR range such as `10.0.0.0/8` or `fd00::/8`. A bare address matches only
+/// itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => Self::prefix_matches(net.to_bits(), ip.to_bits(), self.prefix),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => Self::prefix_matches(net.to_bits(), ip.to_bits(), self.prefix),
+            _ => false,
+        }
+    }
+
+    fn prefix_matches<B>(net: B, ip: B, prefix: u8) -> bool
+    where
+        B: Into<u128>,
+    {
+        let shift: u32 = 128 - u32::from(prefix);
+        let (net, ip): (u128, u128) = (net.into(), ip.into());
+        net.checked_shr(shift).unwrap_or(0) == ip.checked_shr(shift).unwrap_or(0)
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = InvalidProxy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidProxy(s.into());
+        let (addr, prefix): (&str, Option<&str>) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+
+        let addr: IpAddr = addr.parse::<IpAddr>().map_err(|_| invalid())?.to_canonical();
+        let max_prefix: u8 = if addr.is_ipv4() { 32 } else { 128 };
+
+        let prefix: u8 = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|&prefix: &u8| prefix <= max_prefix)
+                .ok_or_else(invalid)?,
+            None => max_prefix,
+        };
+
+        // IPv4 bits are compared as the low 32 of 128, so shift the prefix to match.
+        let prefix: u8 = if addr.is_ipv4() { prefix + 96 } else { prefix };
+
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// The proxies whose `Forwarded` and `X-Forwarded-*` headers are believed, parsed from a
+/// comma-separated list of addresses and CIDR ranges. Empty by default, which ignores those headers
+/// from every peer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn new(nets: Vec<IpNet>) -> Self {
+        Self(nets)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|net: &IpNet| net.contains(ip))
+    }
+}
+
+impl FromStr for TrustedProxies {
+    type Err = InvalidProxy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|net: &&str| !net.is_empty())
+            .map(IpNet::from_str)
+            .collect::<Result<Vec<IpNet>, InvalidProxy>>()
+            .map(Self)
+    }
+}
+
+/// What the headers of a request received from a trusted proxy say about the original client.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Forwarded<'h> {
+    pub(crate) client: Option<IpAddr>,
+    pub(crate) proto: Option<&'h str>,
+    pub(crate) host: Option<&'h str>,
+}
+
+impl<'h> Forwarded<'h> {
+    /// Walks the forwarding chain from the nearest hop back and stops at the first address that is
+    /// not a trusted proxy, since everything before it may have been made up by the client. The
+    /// scheme and host are taken from the nearest hop. `Forwarded` is used when present, otherwise
+    /// the `X-Forwarded-*` headers.
+    pub(crate) fn from_headers(headers: &'h Headers, trusted: &TrustedProxies) -> Self {
+        let header = |key: &str| headers.get(key).map(|value| value.as_ref());
+
+        let (hops, proto, host): (Vec<Option<IpAddr>>, Option<&str>, Option<&str>) = match header(FORWARDED) {
+            Some(forwarded) => {
+                let elements: Vec<&str> = forwarded.split(',').collect();
+                let nearest: &str = elements.last().copied().unwrap_or_default();
+
+                (
+                    elements
+                        .iter()
+                        .map(|element: &&str| Self::parameter(element, "for").and_then(Self::node_ip))
+                        .collect(),
+                    Self::parameter(nearest, "proto"),
+                    Self::parameter(nearest, "host"),
+                )
+            }
+            None => (
+                header(X_FORWARDED_FOR)
+                    .into_iter()
+                    .flat_map(|value: &str| value.split(','))
+                    .map(|node: &str| Self::node_ip(node.trim()))
+                    .collect(),
+                header(X_FORWARDED_PROTO).and_then(Self::nearest),
+                header(X_FORWARDED_HOST).and_then(Self::nearest),
+            ),
+        };
+
+        let mut client: Option<IpAddr> = None;
+
+        for hop in hops.into_iter().rev() {
+            let Some(ip) = hop else {
+                break;
+            };
+
+            client = Some(ip);
+
+            if !trusted.contains(ip) {
+                break;
+            }
+        }
+
+        Self {
+            client,
+            host,
+            proto: proto
+                .filter(|proto: &&str| proto.eq_ignore_ascii_case("http") || proto.eq_ignore_ascii_case("https")),
+        }
+    }
+
+    fn parameter<'v>(element: &'v str, name: &str) -> Option<&'v str> {
+        element
+            .split(';')
+            .filter_map(|pair: &str| pair.split_once('='))
+            .find(|(key, _): &(&str, &str)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value): (&str, &str)| value.trim().trim_matches('"'))
+            .filter(|value: &&str| !value.is_empty())
+    }
+
+    fn nearest(value: &str) -> Option<&str> {
+        value
+            .rsplit(',')
+            .next()
+            .map(str::trim)
+            .filter(|value: &&str| !value.is_empty())
+    }
+
+    /// Accepts `192.0.2.1`, `192.0.2.1:8080`, `2001:db8::1` and `[2001:db8::1]:8080`. Obfuscated
+    /// and `unknown` nodes yield `None`.
+    fn node_ip(node: &str) -> Option<IpAddr> {
+        if let Some(bracketed) = node.strip_prefix('[') {
+            return bracketed.split(']').next()?.parse().ok();
+        }
+
+        node.parse().ok().or_else(|| node.rsplit_once(':')?.0.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> Headers<'static> {
+        pairs
+            .iter()
+            .map(|(key, value): &(&'static str, &'static str)| (Cow::Borrowed(*key), Cow::Borrowed(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_ip_net_contains() {
+        let net: IpNet = "10.0.0.0/8".parse().unwrap();
+        assert!(net.contains("10.1.2.3".parse().unwrap()));
+        assert!(net.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!net.contains("11.0.0.1".parse().unwrap()));
+
+        let v6: IpNet = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!(!v6.contains("10.1.2.3".parse().unwrap()));
+
+        let any: IpNet = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("203.0.113.9".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("proxy.internal".parse::<TrustedProxies>().is_err());
+        assert_eq!("".parse::<TrustedProxies>(), Ok(TrustedProxies::default()));
+    }
+
+    #[test]
+    fn test_forwarded_stops_at_first_untrusted_hop() {
+        let trusted: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+        let headers: Headers = headers(&[(
+            "forwarded",
+            "for=198.51.100.7, for=\"[2001:db8::17]:4711\";proto=http, for=10.0.0.5;proto=https;host=example.com",
+        )]);
+
+        assert_eq!(
+            Forwarded::from_headers(&headers, &trusted),
+            Forwarded {
+                client: Some("2001:db8::17".parse().unwrap()),
+                proto: Some("https"),
+                host: Some("example.com"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_x_forwarded_headers() {
+        let trusted: TrustedProxies = "10.0.0.1, 10.0.0.2".parse().unwrap();
+        let headers: Headers = headers(&[
+            ("x-forwarded-for", "203.0.113.9, 10.0.0.2"),
+            ("x-forwarded-proto", "gopher, https"),
+            ("x-forwarded-host", "example.com"),
+        ]);
+
+        assert_eq!(
+            Forwarded::from_headers(&headers, &trusted),
+            Forwarded {
+                client: Some("203.0.113.9".parse().unwrap()),
+                proto: Some("https"),
+                host: Some("example.com"),
+            }
+        );
+    }
+}
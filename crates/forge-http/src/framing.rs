@@ -0,0 +1,474 @@
+use std::str;
+
+use super::HttpParseError;
+use super::error::echoed;
+
+const CHUNKED: &str = "chunked";
+const CONTENT_LENGTH: &str = "content-length";
+const TRANSFER_ENCODING: &str = "transfer-encoding";
+const MAX_CHUNK_LINE: usize = 4096;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BodyFraming {
+    Empty,
+    Chunked,
+    Length(usize),
+}
+
+impl BodyFraming {
+    pub fn from_headers<'a, I>(headers: I) -> Result<Self, HttpParseError>
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut chunked: bool = false;
+        let mut length: Option<usize> = None;
+
+        for (key, value) in headers {
+            if key.eq_ignore_ascii_case(TRANSFER_ENCODING) {
+                let last_coding: &str = value.rsplit(',').next().unwrap_or_default().trim();
+
+                if !last_coding.eq_ignore_ascii_case(CHUNKED) {
+                    return Err(HttpParseError::UnsupportedTransferEncoding(echoed(value)));
+                }
+
+                chunked = true;
+            } else if key.eq_ignore_ascii_case(CONTENT_LENGTH) {
+                for part in value.split(',') {
+                    let parsed: usize = parse_content_length(part.trim())
+                        .ok_or_else(|| HttpParseError::InvalidContentLength(echoed(value)))?;
+
+                    if let Some(previous) = length.filter(|&previous: &usize| previous != parsed) {
+                        return Err(HttpParseError::ConflictingContentLength(previous, parsed));
+                    }
+
+                    length = Some(parsed);
+                }
+            }
+        }
+
+        match (chunked, length) {
+            (true, Some(_)) => Err(HttpParseError::AmbiguousFraming),
+            (true, None) => Ok(BodyFraming::Chunked),
+            (false, Some(length)) => Ok(BodyFraming::Length(length)),
+            (false, None) => Ok(BodyFraming::Empty),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Decoded {
+    Data(Vec<u8>),
+    NeedMore,
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChunkState {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+/// Decodes a chunked body as it arrives instead of waiting for all of it like `decode_chunked`.
+/// Each call consumes what it can from the front of `input`, leaving any bytes past the end of the
+/// body (such as a pipelined request) in place once it reports `Done`.
+#[derive(Debug)]
+pub struct ChunkedDecoder {
+    state: ChunkState,
+    consumed: usize,
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: ChunkState::Size,
+            consumed: 0,
+        }
+    }
+
+    pub fn decode(&mut self, input: &mut Vec<u8>) -> Result<Decoded, HttpParseError> {
+        loop {
+            if let ChunkState::Data(remaining) = self.state {
+                if input.is_empty() {
+                    return Ok(Decoded::NeedMore);
+                }
+
+                let len: usize = remaining.min(input.len());
+                self.consumed += len;
+                self.state = match remaining - len {
+                    0 => ChunkState::DataEnd,
+                    remaining => ChunkState::Data(remaining),
+                };
+
+                return Ok(Decoded::Data(input.drain(..len).collect()));
+            }
+
+            if self.state == ChunkState::Done {
+                return Ok(Decoded::Done);
+            }
+
+            let Some((line, next_pos)) = read_line(input, 0) else {
+                if input.len() > MAX_CHUNK_LINE {
+                    return Err(HttpParseError::InvalidChunk(self.consumed));
+                }
+
+                return Ok(Decoded::NeedMore);
+            };
+
+            self.state = match self.state {
+                ChunkState::Size => match parse_chunk_size(line) {
+                    Some(0) => ChunkState::Trailers,
+                    Some(size) => ChunkState::Data(size),
+                    None => return Err(HttpParseError::InvalidChunk(self.consumed)),
+                },
+                ChunkState::DataEnd if line.is_empty() => ChunkState::Size,
+                ChunkState::Trailers if line.is_empty() => ChunkState::Done,
+                ChunkState::Trailers if line.contains(&b':') => ChunkState::Trailers,
+                _ => return Err(HttpParseError::InvalidChunk(self.consumed)),
+            };
+
+            self.consumed += next_pos;
+            input.drain(..next_pos);
+        }
+    }
+}
+
+pub fn header_fields(head: &str) -> impl Iterator<Item = (&str, &str)> {
+    head.lines()
+        .skip(1)
+        .filter_map(|line: &str| line.split_once(':'))
+        .map(|(key, value): (&str, &str)| (key.trim(), value.trim()))
+}
+
+pub fn find_head_end(raw: &[u8]) -> Option<usize> {
+    raw.iter()
+        .enumerate()
+        .filter(|(_, byte): &(usize, &u8)| **byte == b'\n')
+        .find_map(|(idx, _): (usize, &u8)| match &raw[idx + 1..] {
+            [b'\n', ..] => Some(idx + 2),
+            [b'\r', b'\n', ..] => Some(idx + 3),
+            _ => None,
+        })
+}
+
+pub fn message_len(raw: &[u8]) -> Result<Option<usize>, HttpParseError> {
+    let Some(head_end) = find_head_end(raw) else {
+        return Ok(None);
+    };
+
+    let Ok(head) = str::from_utf8(&raw[..head_end]) else {
+        return Ok(Some(raw.len()));
+    };
+
+    match BodyFraming::from_headers(header_fields(head))? {
+        BodyFraming::Empty => Ok(Some(head_end)),
+        BodyFraming::Length(length) => Ok(head_end.checked_add(length).filter(|&end: &usize| end <= raw.len())),
+        BodyFraming::Chunked => scan_chunks(raw, head_end, |_| {}),
+    }
+}
+
+pub fn decode_chunked(raw: &[u8], start: usize) -> Result<Option<(Vec<u8>, usize)>, HttpParseError> {
+    let mut body: Vec<u8> = Vec::new();
+    let end: Option<usize> = scan_chunks(raw, start, |data: &[u8]| body.extend_from_slice(data))?;
+    Ok(end.map(|end: usize| (body, end)))
+}
+
+/// The chunk that ends a chunked body, with no trailers.
+pub const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
+
+/// Appends `data` to `buffer` as one chunk of a chunked body. Empty data is skipped, since a
+/// zero-length chunk would end the body.
+pub fn encode_chunk(data: &[u8], buffer: &mut Vec<u8>) {
+    if data.is_empty() {
+        return;
+    }
+
+    buffer.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+    buffer.extend_from_slice(data);
+    buffer.extend_from_slice(b"\r\n");
+}
+
+/// Returns where the chunked body whose next chunk starts at `*pos` ends, or `None` while it is
+/// incomplete. `*pos` is moved past every chunk that has fully arrived, so calling again once more
+/// bytes were appended to `raw` carries on from there instead of from the start of the body.
+pub fn chunked_body_end(raw: &[u8], pos: &mut usize) -> Result<Option<usize>, HttpParseError> {
+    scan_chunks_from(raw, pos, |_| {})
+}
+
+fn scan_chunks<F>(raw: &[u8], mut start: usize, on_data: F) -> Result<Option<usize>, HttpParseError>
+where
+    F: FnMut(&[u8]),
+{
+    scan_chunks_from(raw, &mut start, on_data)
+}
+
+fn scan_chunks_from<F>(raw: &[u8], pos: &mut usize, mut on_data: F) -> Result<Option<usize>, HttpParseError>
+where
+    F: FnMut(&[u8]),
+{
+    loop {
+        let Some((size_line, data_start)) = read_line(raw, *pos) else {
+            return Ok(None);
+        };
+
+        let size: usize = parse_chunk_size(size_line).ok_or(HttpParseError::InvalidChunk(*pos))?;
+
+        if size == 0 {
+            return skip_trailers(raw, data_start);
+        }
+
+        let data_end: usize = data_start.checked_add(size).ok_or(HttpParseError::InvalidChunk(*pos))?;
+
+        let Some((terminator, next_pos)) = read_line(raw, data_end) else {
+            return Ok(None);
+        };
+
+        if !terminator.is_empty() {
+            return Err(HttpParseError::InvalidChunk(data_end));
+        }
+
+        on_data(&raw[data_start..data_end]);
+        *pos = next_pos;
+    }
+}
+
+fn skip_trailers(raw: &[u8], mut pos: usize) -> Result<Option<usize>, HttpParseError> {
+    loop {
+        let Some((line, next_pos)) = read_line(raw, pos) else {
+            return Ok(None);
+        };
+
+        if line.is_empty() {
+            return Ok(Some(next_pos));
+        }
+
+        if !line.contains(&b':') {
+            return Err(HttpParseError::InvalidChunk(pos));
+        }
+
+        pos = next_pos;
+    }
+}
+
+fn read_line(raw: &[u8], pos: usize) -> Option<(&[u8], usize)> {
+    let remaining: &[u8] = raw.get(pos..)?;
+    let newline: usize = remaining.iter().position(|byte: &u8| *byte == b'\n')?;
+    let line: &[u8] = &remaining[..newline];
+
+    Some((line.strip_suffix(b"\r").unwrap_or(line), pos + newline + 1))
+}
+
+fn parse_content_length(value: &str) -> Option<usize> {
+    if value.is_empty() || !value.bytes().all(|byte: u8| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    value.parse::<usize>().ok()
+}
+
+fn parse_chunk_size(line: &[u8]) -> Option<usize> {
+    let size: &[u8] = line.split(|byte: &u8| *byte == b';').next()?.trim_ascii();
+
+    if size.is_empty() || !size.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+
+    usize::from_str_radix(str::from_utf8(size).ok()?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_head_end() {
+        assert_eq!(find_head_end(b"GET / HTTP/1.1\r\nHost: a\r\n\r\nbody"), Some(27));
+        assert_eq!(find_head_end(b"GET / HTTP/1.1\nHost: a\n\nbody"), Some(24));
+        assert_eq!(find_head_end(b"GET / HTTP/1.1\r\nHost: a\r\n"), None);
+    }
+
+    #[test]
+    fn test_message_len_with_content_length() {
+        let raw: &[u8] = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+
+        assert_eq!(message_len(raw), Ok(Some(raw.len())));
+        assert_eq!(message_len(&raw[..raw.len() - 1]), Ok(None));
+        assert_eq!(message_len(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n"), Ok(Some(18)));
+    }
+
+    #[test]
+    fn test_chunked_body_end_resumes_after_complete_chunks() {
+        let body: &[u8] = b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n";
+        let mut pos: usize = 0;
+
+        assert_eq!(chunked_body_end(&body[..12], &mut pos), Ok(None));
+        assert_eq!(pos, 9);
+        assert_eq!(chunked_body_end(&body[..20], &mut pos), Ok(None));
+        assert_eq!(pos, 19);
+        assert_eq!(chunked_body_end(body, &mut pos), Ok(Some(body.len())));
+    }
+
+    #[test]
+    fn test_message_len_with_chunked_body() {
+        let raw: &[u8] = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n0\r\n\r\n";
+
+        assert_eq!(message_len(raw), Ok(Some(raw.len())));
+        assert_eq!(message_len(&raw[..raw.len() - 2]), Ok(None));
+    }
+
+    #[test]
+    fn test_decode_chunked_with_extensions_and_trailers() {
+        let raw: &[u8] =
+            b"4;name=value\r\nWiki\r\n5\r\npedia\r\nE\r\n in\r\n\r\nchunks.\r\n0\r\nExpires: never\r\n\r\n";
+        let (body, end): (Vec<u8>, usize) = decode_chunked(raw, 0).unwrap().unwrap();
+
+        assert_eq!(body, b"Wikipedia in\r\n\r\nchunks.");
+        assert_eq!(end, raw.len());
+    }
+
+    #[test]
+    fn test_decode_chunked_malformed() {
+        assert_eq!(
+            decode_chunked(b"zz\r\nWiki\r\n0\r\n\r\n", 0),
+            Err(HttpParseError::InvalidChunk(0))
+        );
+        assert_eq!(decode_chunked(b"\r\n", 0), Err(HttpParseError::InvalidChunk(0)));
+        assert_eq!(
+            decode_chunked(b"2\r\nWiki\r\n0\r\n\r\n", 0),
+            Err(HttpParseError::InvalidChunk(5))
+        );
+        assert_eq!(
+            decode_chunked(b"fffffffffffffffffffff\r\n", 0),
+            Err(HttpParseError::InvalidChunk(0))
+        );
+    }
+
+    #[test]
+    fn test_encode_chunk_round_trips() {
+        let mut body: Vec<u8> = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        let start: usize = body.len();
+
+        encode_chunk(b"Wiki", &mut body);
+        encode_chunk(b"", &mut body);
+        encode_chunk(&[b'x'; 26], &mut body);
+        body.extend_from_slice(LAST_CHUNK);
+
+        assert_eq!(&body[start..start + 9], b"4\r\nWiki\r\n");
+        assert_eq!(&body[start + 9..start + 13], b"1a\r\n");
+
+        let (decoded, end): (Vec<u8>, usize) = decode_chunked(&body, start).unwrap().unwrap();
+        assert_eq!(decoded.len(), 30);
+        assert_eq!(end, body.len());
+    }
+
+    #[test]
+    fn test_framing_from_headers() {
+        assert_eq!(BodyFraming::from_headers([]), Ok(BodyFraming::Empty));
+        assert_eq!(
+            BodyFraming::from_headers([("Content-Length", "12")]),
+            Ok(BodyFraming::Length(12))
+        );
+        assert_eq!(
+            BodyFraming::from_headers([("transfer-encoding", "gzip, chunked")]),
+            Ok(BodyFraming::Chunked)
+        );
+        assert_eq!(
+            BodyFraming::from_headers([("Content-Length", "abc")]),
+            Err(HttpParseError::InvalidContentLength("abc".into()))
+        );
+        assert_eq!(
+            BodyFraming::from_headers([("Transfer-Encoding", "gzip")]),
+            Err(HttpParseError::UnsupportedTransferEncoding("gzip".into()))
+        );
+    }
+
+    #[test]
+    fn test_framing_rejects_content_length_with_transfer_encoding() {
+        assert_eq!(
+            BodyFraming::from_headers([("Content-Length", "4"), ("Transfer-Encoding", "chunked")]),
+            Err(HttpParseError::AmbiguousFraming)
+        );
+        assert_eq!(
+            BodyFraming::from_headers([("Transfer-Encoding", "chunked"), ("Content-Length", "4")]),
+            Err(HttpParseError::AmbiguousFraming)
+        );
+    }
+
+    #[test]
+    fn test_framing_rejects_conflicting_content_lengths() {
+        assert_eq!(
+            BodyFraming::from_headers([("Content-Length", "4"), ("Content-Length", "5")]),
+            Err(HttpParseError::ConflictingContentLength(4, 5))
+        );
+        assert_eq!(
+            BodyFraming::from_headers([("Content-Length", "4, 5")]),
+            Err(HttpParseError::ConflictingContentLength(4, 5))
+        );
+        assert_eq!(
+            BodyFraming::from_headers([("Content-Length", "4"), ("Content-Length", "4, 4")]),
+            Ok(BodyFraming::Length(4))
+        );
+    }
+
+    #[test]
+    fn test_framing_rejects_signed_or_empty_content_length() {
+        assert_eq!(
+            BodyFraming::from_headers([("Content-Length", "+4")]),
+            Err(HttpParseError::InvalidContentLength("+4".into()))
+        );
+        assert_eq!(
+            BodyFraming::from_headers([("Content-Length", "4,")]),
+            Err(HttpParseError::InvalidContentLength("4,".into()))
+        );
+    }
+
+    #[test]
+    fn test_message_len_rejects_smuggling_headers() {
+        let raw: &[u8] = b"POST / HTTP/1.1\r\nContent-Length: 6\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\nX";
+
+        assert_eq!(message_len(raw), Err(HttpParseError::AmbiguousFraming));
+    }
+
+    #[test]
+    fn test_chunked_decoder_handles_split_input() {
+        let raw: &[u8] = b"4;name=value\r\nWiki\r\n5\r\npedia\r\n0\r\nExpires: never\r\n\r\nGET / HTTP/1.1\r\n\r\n";
+        let mut decoder: ChunkedDecoder = ChunkedDecoder::new();
+        let mut input: Vec<u8> = Vec::new();
+        let mut body: Vec<u8> = Vec::new();
+
+        for piece in raw.chunks(3) {
+            input.extend_from_slice(piece);
+
+            while let Decoded::Data(data) = decoder.decode(&mut input).unwrap() {
+                body.extend_from_slice(&data);
+            }
+        }
+
+        assert_eq!(decoder.decode(&mut input), Ok(Decoded::Done));
+        assert_eq!(body, b"Wikipedia");
+        assert_eq!(input, b"GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_chunked_decoder_rejects_malformed_input() {
+        let mut missing_terminator: Vec<u8> = b"2\r\nWiki\r\n".to_vec();
+        let mut decoder: ChunkedDecoder = ChunkedDecoder::new();
+
+        assert_eq!(decoder.decode(&mut missing_terminator), Ok(Decoded::Data(b"Wi".to_vec())));
+        assert_eq!(decoder.decode(&mut missing_terminator), Err(HttpParseError::InvalidChunk(5)));
+
+        let mut endless_size_line: Vec<u8> = vec![b'f'; MAX_CHUNK_LINE + 1];
+        assert_eq!(
+            ChunkedDecoder::new().decode(&mut endless_size_line),
+            Err(HttpParseError::InvalidChunk(0))
+        );
+    }
+}
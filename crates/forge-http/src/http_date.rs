@@ -0,0 +1,145 @@
+use std::time::{Duration, SystemTime};
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Formats `time` as an IMF-fixdate such as `Sun, 06 Nov 1994 08:49:37 GMT`, dropping anything
+/// below a second.
+pub fn format(time: SystemTime) -> String {
+    let seconds: i64 = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs_f64().ceil() as i64),
+    };
+
+    let (days, second_of_day): (i64, i64) = (seconds.div_euclid(SECONDS_PER_DAY), seconds.rem_euclid(SECONDS_PER_DAY));
+    let (year, month, day): (i64, u32, u32) = civil_from_days(days);
+
+    format!(
+        "{}, {day:02} {} {year:04} {:02}:{:02}:{:02} GMT",
+        DAYS[days.rem_euclid(7) as usize],
+        MONTHS[month as usize - 1],
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60,
+    )
+}
+
+/// Parses an IMF-fixdate. The obsolete RFC 850 and asctime formats are not accepted, and neither
+/// is a weekday that does not match the date.
+pub fn parse(value: &str) -> Option<SystemTime> {
+    let value: &[u8] = value.trim().as_bytes();
+
+    if value.len() != 29 || &value[3..5] != b", " || &value[25..] != b" GMT" {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| str::from_utf8(&value[range]).ok();
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits: &str = field(range)?;
+        digits
+            .bytes()
+            .all(|byte: u8| byte.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+
+    let separators: [(usize, u8); 5] = [(7, b' '), (11, b' '), (16, b' '), (19, b':'), (22, b':')];
+
+    if separators
+        .iter()
+        .any(|&(idx, separator): &(usize, u8)| value[idx] != separator)
+    {
+        return None;
+    }
+
+    let month: i64 = MONTHS.iter().position(|month: &&str| Some(*month) == field(8..11))? as i64 + 1;
+    let (year, day): (i64, i64) = (number(12..16)?, number(5..7)?);
+    let (hour, minute, second): (i64, i64, i64) = (number(17..19)?, number(20..22)?, number(23..25)?);
+
+    if !(1..=days_in_month(year, month)).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let days: i64 = days_from_civil(year, month, day);
+
+    if Some(DAYS[days.rem_euclid(7) as usize]) != field(0..3) {
+        return None;
+    }
+
+    // A leap second is folded into the second before it.
+    let seconds: i64 = days * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second.min(59);
+
+    match u64::try_from(seconds) {
+        Ok(seconds) => SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds)),
+        Err(_) => SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs())),
+    }
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Howard Hinnant's `days_from_civil` and `civil_from_days`, with eras of 400 years starting in March.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year: i64 = if month <= 2 { year - 1 } else { year };
+    let era: i64 = year.div_euclid(400);
+    let year_of_era: i64 = year.rem_euclid(400);
+    let day_of_year: i64 = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era: i64 = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days: i64 = days + 719_468;
+    let era: i64 = days.div_euclid(146_097);
+    let day_of_era: i64 = days.rem_euclid(146_097);
+    let year_of_era: i64 = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month: i64 = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year: i64 = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_and_parse_round_trip() {
+        let time: SystemTime = SystemTime::UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(format(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+
+        assert_eq!(format(SystemTime::UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(format(time + Duration::from_millis(999)), "Sun, 06 Nov 1994 08:49:37 GMT");
+
+        let leap_day: SystemTime = parse("Thu, 29 Feb 2024 23:59:59 GMT").unwrap();
+        assert_eq!(format(leap_day), "Thu, 29 Feb 2024 23:59:59 GMT");
+    }
+
+    #[test]
+    fn test_parse_rejects_other_formats() {
+        assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), None);
+        assert_eq!(parse("Mon, 06 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse("Thu, 29 Feb 2023 00:00:00 GMT"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 24:00:00 GMT"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse("Sun, +6 Nov 1994 08:49:37 GMT"), None);
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use super::{HttpMethod, HttpStatus, Request, Response};
+
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+enum Entry {
+    InFlight,
+    Completed {
+        response: Response<'static>,
+        expires_at: Instant,
+    },
+}
+
+/// `completed` lists completed keys in the order they expire, which is the order they completed in
+/// since every entry lives for the same `ttl`. Running keys are only in `map`, so nothing that is
+/// still running holds back pruning.
+#[derive(Default)]
+struct Entries {
+    next_seq: u64,
+    map: HashMap<String, (u64, Entry)>,
+    completed: VecDeque<(u64, String)>,
+}
+
+/// Replays responses for requests that carry an `Idempotency-Key` header, so a client can safely
+/// retry a `POST` whose response it never received. The first request with a key runs the handler
+/// and its response is kept for `ttl`; repeats get the stored response with an
+/// `Idempotent-Replayed: true` header instead of running the handler again. A repeat that arrives
+/// while the first is still running gets `409 Conflict`. Server errors (5xx) are not stored, and
+/// neither is a handler that panics or is dropped, so those keys can be retried.
+///
+/// Keys are namespaced by method and request path, so the same key sent to two endpoints does not
+/// collide. The cache is scoped to whatever owns it: keep it in the state passed to
+/// `Listener::with_state` to deduplicate across every worker, since a retry usually arrives on a
+/// new connection that may land on a different worker. Once `capacity` keys are stored, the oldest
+/// completed one is evicted to make room.
+pub struct IdempotencyCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+struct InFlightGuard<'c> {
+    cache: &'c IdempotencyCache,
+    key: String,
+    seq: u64,
+    armed: bool,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.cache.entries().remove(&self.key, self.seq);
+        }
+    }
+}
+
+impl Entries {
+    fn remove(&mut self, key: &str, seq: u64) {
+        if self
+            .map
+            .get(key)
+            .is_some_and(|(entry_seq, _): &(u64, Entry)| *entry_seq == seq)
+        {
+            self.map.remove(key);
+        }
+    }
+
+    /// Drops expired keys and, while the cache is full, the oldest completed ones.
+    fn prune(&mut self, now: Instant, capacity: usize) {
+        while let Some((seq, key)) = self.completed.front() {
+            let evict: bool = match self.map.get(key) {
+                Some((entry_seq, Entry::Completed { expires_at, .. })) if entry_seq == seq => {
+                    *expires_at <= now || self.map.len() >= capacity
+                }
+                _ => true,
+            };
+
+            if !evict {
+                break;
+            }
+
+            let (seq, key): (u64, String) = self.completed.pop_front().unwrap_or_default();
+            self.remove(&key, seq);
+        }
+    }
+}
+
+impl IdempotencyCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Runs `handler` unless `request` repeats a key that is stored or still running. Requests
+    /// without an `Idempotency-Key` header always run the handler.
+    pub async fn run<'a, F, Fut>(&self, request: &Request<'a>, handler: F) -> Response<'a>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Response<'a>>,
+    {
+        let Some(key) = request.headers.get(IDEMPOTENCY_KEY_HEADER) else {
+            return handler().await;
+        };
+
+        let key: String = Self::namespaced_key(request.method, request.path, key);
+        let now: Instant = Instant::now();
+
+        let mut guard: InFlightGuard = {
+            let mut entries: MutexGuard<Entries> = self.entries();
+            entries.prune(now, self.capacity);
+
+            match entries.map.get(&key) {
+                Some((_, Entry::Completed { response, expires_at })) if *expires_at > now => {
+                    return response.duplicate().header("Idempotent-Replayed", "true");
+                }
+                Some((_, Entry::InFlight)) => {
+                    return Response::new(HttpStatus::Conflict)
+                        .text("A request with this Idempotency-Key is already being processed");
+                }
+                _ => {}
+            }
+
+            let seq: u64 = entries.next_seq;
+            entries.next_seq += 1;
+            entries.map.insert(key.clone(), (seq, Entry::InFlight));
+
+            InFlightGuard {
+                cache: self,
+                key,
+                seq,
+                armed: true,
+            }
+        };
+
+        let response: Response<'a> = handler().await;
+
+        if u16::from(response.status()) >= 500 {
+            return response;
+        }
+
+        let stored: Response<'static> = response.into_owned();
+        let reply: Response<'a> = stored.duplicate();
+        let mut entries: MutexGuard<Entries> = self.entries();
+
+        if let Some((seq, entry)) = entries.map.get_mut(&guard.key)
+            && *seq == guard.seq
+        {
+            *entry = Entry::Completed {
+                response: stored,
+                expires_at: Instant::now() + self.ttl,
+            };
+            guard.armed = false;
+            entries.completed.push_back((guard.seq, guard.key.clone()));
+        }
+
+        reply
+    }
+
+    fn namespaced_key(method: HttpMethod, path: &str, key: &str) -> String {
+        format!("{method} {path}\n{key}")
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut cx: Context = Context::from_waker(Waker::noop());
+
+        match pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("Future should complete immediately"),
+        }
+    }
+
+    fn create_payment<'a>(calls: &Cell<usize>) -> impl Future<Output = Response<'a>> {
+        calls.set(calls.get() + 1);
+        future::ready(Response::new(HttpStatus::Created).text(format!("payment #{}", calls.get())))
+    }
+
+    #[test]
+    fn test_repeated_key_replays_stored_response() {
+        let cache: IdempotencyCache = IdempotencyCache::new(16, Duration::from_secs(60));
+        let calls: Cell<usize> = Cell::new(0);
+        let request: Request = Request::builder(HttpMethod::POST, "/payments")
+            .header("Idempotency-Key", "abc")
+            .build();
+
+        let first: Response = block_on(cache.run(&request, || create_payment(&calls)));
+        let second: Response = block_on(cache.run(&request, || create_payment(&calls)));
+
+        assert_eq!(calls.get(), 1);
+        assert_eq!(second.status(), HttpStatus::Created);
+        assert_eq!(second.get_body(), first.get_body());
+        assert_eq!(second.get_header("idempotent-replayed"), Some("true"));
+        assert_eq!(first.get_header("idempotent-replayed"), None);
+    }
+
+    #[test]
+    fn test_keys_are_namespaced_by_method_and_path() {
+        let cache: IdempotencyCache = IdempotencyCache::new(16, Duration::from_secs(60));
+        let calls: Cell<usize> = Cell::new(0);
+
+        for path in ["/payments", "/refunds", "/payments"] {
+            let request: Request = Request::builder(HttpMethod::POST, path)
+                .header("Idempotency-Key", "abc")
+                .build();
+            block_on(cache.run(&request, || create_payment(&calls)));
+        }
+
+        let without_key: Request = Request::builder(HttpMethod::POST, "/payments").build();
+        block_on(cache.run(&without_key, || create_payment(&calls)));
+        block_on(cache.run(&without_key, || create_payment(&calls)));
+
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn test_concurrent_duplicate_conflicts_until_first_finishes_or_drops() {
+        let cache: IdempotencyCache = IdempotencyCache::new(16, Duration::from_secs(60));
+        let calls: Cell<usize> = Cell::new(0);
+        let request: Request = Request::builder(HttpMethod::POST, "/payments")
+            .header("Idempotency-Key", "abc")
+            .build();
+
+        {
+            let mut pending = pin!(cache.run(&request, future::pending::<Response>));
+            let mut cx: Context = Context::from_waker(Waker::noop());
+            assert!(pending.as_mut().poll(&mut cx).is_pending());
+
+            let duplicate: Response = block_on(cache.run(&request, || create_payment(&calls)));
+            assert_eq!(duplicate.status(), HttpStatus::Conflict);
+        }
+
+        let retried: Response = block_on(cache.run(&request, || create_payment(&calls)));
+        assert_eq!(retried.status(), HttpStatus::Created);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_server_errors_and_expired_entries_are_not_replayed() {
+        let cache: IdempotencyCache = IdempotencyCache::new(16, Duration::ZERO);
+        let calls: Cell<usize> = Cell::new(0);
+        let request: Request = Request::builder(HttpMethod::POST, "/payments")
+            .header("Idempotency-Key", "abc")
+            .build();
+
+        let failed: Response =
+            block_on(cache.run(&request, || future::ready(Response::new(HttpStatus::InternalServerError))));
+        assert_eq!(failed.status(), HttpStatus::InternalServerError);
+
+        block_on(cache.run(&request, || create_payment(&calls)));
+        block_on(cache.run(&request, || create_payment(&calls)));
+
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest_completed_key() {
+        let cache: IdempotencyCache = IdempotencyCache::new(2, Duration::from_secs(60));
+        let calls: Cell<usize> = Cell::new(0);
+
+        for key in ["a", "b", "c", "a"] {
+            let request: Request = Request::builder(HttpMethod::POST, "/payments")
+                .header("Idempotency-Key", key)
+                .build();
+            block_on(cache.run(&request, || create_payment(&calls)));
+        }
+
+        assert_eq!(calls.get(), 4);
+        assert_eq!(cache.entries().map.len(), 2);
+    }
+
+    #[test]
+    fn test_dropped_and_expired_keys_are_reclaimed() {
+        let cache: IdempotencyCache = IdempotencyCache::new(16, Duration::ZERO);
+        let calls: Cell<usize> = Cell::new(0);
+        let mut cx: Context = Context::from_waker(Waker::noop());
+        let request = |key: &'static str| -> Request<'static> {
+            Request::builder(HttpMethod::POST, "/payments")
+                .header("Idempotency-Key", key)
+                .build()
+        };
+
+        let slow: Request = request("slow");
+        let mut running = Box::pin(cache.run(&slow, future::pending::<Response>));
+        assert!(running.as_mut().poll(&mut cx).is_pending());
+
+        for key in ["a", "b", "c"] {
+            block_on(cache.run(&request(key), || create_payment(&calls)));
+        }
+
+        // "a" and "b" expired while "slow" was still running and were pruned anyway.
+        assert_eq!(cache.entries().map.len(), 2);
+        assert_eq!(cache.entries().completed.len(), 1);
+
+        for key in ["d", "e"] {
+            let abandoned: Request = request(key);
+            let mut pending = pin!(cache.run(&abandoned, future::pending::<Response>));
+            assert!(pending.as_mut().poll(&mut cx).is_pending());
+        }
+
+        assert_eq!(cache.entries().map.len(), 1);
+        assert!(cache.entries().completed.is_empty());
+
+        drop(running);
+        assert!(cache.entries().map.is_empty());
+    }
+}
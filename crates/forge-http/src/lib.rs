@@ -1,11 +1,36 @@
+pub mod body_stream;
+pub mod bytes;
+pub mod encoding;
 pub mod error;
+pub mod etag;
+pub mod forwarded;
+pub mod framing;
+pub mod http_date;
+pub mod idempotency;
+pub mod merge_patch;
 pub mod method;
+pub mod params;
+pub mod range;
 pub mod request;
 pub mod response;
+pub mod response_writer;
+pub mod server_timing;
 pub mod status;
+pub mod version;
 
-pub use error::HttpError;
+pub use body_stream::{BodySender, BodyStream};
+pub use bytes::Bytes;
+pub use error::{HttpError, HttpParseError};
+pub use forwarded::{InvalidProxy, IpNet, TrustedProxies};
+pub use framing::{BodyFraming, ChunkedDecoder};
+pub use idempotency::IdempotencyCache;
+pub use merge_patch::merge_patch;
 pub use method::HttpMethod;
-pub use request::{Headers, Params, Request};
-pub use response::{IntoResponse, Response};
+pub use params::ParamsExt;
+pub use range::ByteRange;
+pub use request::{Headers, Params, Request, RequestBuilder};
+pub use response::{IntoResponse, Json, Response};
+pub use response_writer::{ResponseSink, ResponseWriter};
+pub use server_timing::ServerTiming;
 pub use status::HttpStatus;
+pub use version::HttpVersion;
@@ -14,6 +14,20 @@ pub enum HttpMethod {
     HEAD,
     OPTIONS,
     TRACE,
+    CONNECT,
+}
+
+impl HttpMethod {
+    /// Safe methods only read: `GET`, `HEAD`, `OPTIONS` and `TRACE` (RFC 9110, section 9.2.1).
+    pub fn is_safe(&self) -> bool {
+        matches!(self, Self::GET | Self::HEAD | Self::OPTIONS | Self::TRACE)
+    }
+
+    /// Repeating an idempotent request has the same effect as sending it once, so it can be
+    /// retried: the safe methods plus `PUT` and `DELETE` (RFC 9110, section 9.2.2).
+    pub fn is_idempotent(&self) -> bool {
+        self.is_safe() || matches!(self, Self::PUT | Self::DELETE)
+    }
 }
 
 impl fmt::Display for HttpMethod {
@@ -27,6 +41,7 @@ impl fmt::Display for HttpMethod {
             HttpMethod::HEAD => "HEAD",
             HttpMethod::OPTIONS => "OPTIONS",
             HttpMethod::TRACE => "TRACE",
+            HttpMethod::CONNECT => "CONNECT",
         };
 
         write!(f, "{msg}")
@@ -46,6 +61,7 @@ impl str::FromStr for HttpMethod {
             "HEAD" => Ok(HttpMethod::HEAD),
             "OPTIONS" => Ok(HttpMethod::OPTIONS),
             "TRACE" => Ok(HttpMethod::TRACE),
+            "CONNECT" => Ok(HttpMethod::CONNECT),
             _ => Err(HttpError::new(
                 HttpStatus::BadRequest,
                 format!("Unknown or unsupported HTTP method: \"{s}\""),
@@ -53,3 +69,28 @@ impl str::FromStr for HttpMethod {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_and_idempotent_methods() {
+        let table: [(HttpMethod, bool, bool); 9] = [
+            (HttpMethod::GET, true, true),
+            (HttpMethod::HEAD, true, true),
+            (HttpMethod::OPTIONS, true, true),
+            (HttpMethod::TRACE, true, true),
+            (HttpMethod::PUT, false, true),
+            (HttpMethod::DELETE, false, true),
+            (HttpMethod::POST, false, false),
+            (HttpMethod::PATCH, false, false),
+            (HttpMethod::CONNECT, false, false),
+        ];
+
+        for (method, safe, idempotent) in table {
+            assert_eq!(method.is_safe(), safe, "{method}");
+            assert_eq!(method.is_idempotent(), idempotent, "{method}");
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::str::FromStr;
+
+use serde::de::value::{Error, MapDeserializer};
+use serde::de::{self, Deserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use super::Params;
+
+/// Typed lookups on `Params`, for handlers that parse a parameter or two by hand instead of
+/// deserializing all of them with `Request::params_as`. `get_as` returns `None` when the route has
+/// no parameter with that name and `Some(Err(_))` when its value does not parse as `T`.
+pub trait ParamsExt {
+    fn get_as<T>(&self, name: &str) -> Option<Result<T, T::Err>>
+    where
+        T: FromStr;
+}
+
+impl ParamsExt for Params<'_> {
+    fn get_as<T>(&self, name: &str) -> Option<Result<T, T::Err>>
+    where
+        T: FromStr,
+    {
+        self.get(name).map(|value: &&str| value.parse::<T>())
+    }
+}
+
+pub struct ParamsDeserializer<'a, I>(MapDeserializer<'a, I, Error>)
+where
+    I: Iterator<Item = (&'a str, ParamValue<'a>)>;
+
+impl<'a, I> ParamsDeserializer<'a, I>
+where
+    I: Iterator<Item = (&'a str, ParamValue<'a>)>,
+{
+    pub fn new(params: I) -> Self {
+        Self(MapDeserializer::new(params))
+    }
+}
+
+impl<'de, I> Deserializer<'de> for ParamsDeserializer<'de, I>
+where
+    I: Iterator<Item = (&'de str, ParamValue<'de>)>,
+{
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let mut map: MapDeserializer<'de, I, Error> = self.0;
+        let value: V::Value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ParamValue<'a>(pub &'a str);
+
+impl<'a> ParamValue<'a> {
+    fn parse<T>(self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.0
+            .parse::<T>()
+            .map_err(|e: T::Err| de::Error::custom(format!("invalid value \"{}\": {e}", self.0)))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ParamValue<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ParamValue<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
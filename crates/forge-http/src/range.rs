@@ -0,0 +1,104 @@
+use std::ops::Range;
+
+const BYTES_UNIT: &str = "bytes=";
+
+/// `Multiple` is a request for several ranges, which is not supported and is answered like an
+/// unsatisfiable range. `parse` returns `None` for a header it cannot read, which is then ignored.
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    Satisfiable(Range<usize>),
+    Unsatisfiable,
+    Multiple,
+}
+
+impl ByteRange {
+    pub fn parse(header: &str, len: usize) -> Option<Self> {
+        let spec: &str = header.trim().strip_prefix(BYTES_UNIT)?.trim();
+
+        if spec.contains(',') {
+            return Some(ByteRange::Multiple);
+        }
+
+        let (start, end): (&str, &str) = spec.split_once('-')?;
+        let (start, end): (&str, &str) = (start.trim(), end.trim());
+
+        let range: Range<usize> = match (start.is_empty(), end.is_empty()) {
+            (true, true) => return None,
+            (true, false) => {
+                let suffix: usize = parse_position(end)?;
+                len.saturating_sub(suffix)..len
+            }
+            (false, _) => {
+                let start: usize = parse_position(start)?;
+
+                let end: usize = if end.is_empty() {
+                    len
+                } else {
+                    parse_position(end)?.saturating_add(1).min(len)
+                };
+
+                if end < start {
+                    return None;
+                }
+
+                start..end
+            }
+        };
+
+        if range.is_empty() {
+            return Some(ByteRange::Unsatisfiable);
+        }
+
+        Some(ByteRange::Satisfiable(range))
+    }
+}
+
+fn parse_position(value: &str) -> Option<usize> {
+    if value.is_empty() || !value.bytes().all(|byte: u8| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    value.parse::<usize>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_closed_and_open_ended_ranges() {
+        assert_eq!(ByteRange::parse("bytes=0-499", 1000), Some(ByteRange::Satisfiable(0..500)));
+        assert_eq!(ByteRange::parse("bytes=500-", 1000), Some(ByteRange::Satisfiable(500..1000)));
+        assert_eq!(
+            ByteRange::parse("bytes=900-5000", 1000),
+            Some(ByteRange::Satisfiable(900..1000))
+        );
+    }
+
+    #[test]
+    fn test_parse_suffix_ranges() {
+        assert_eq!(ByteRange::parse("bytes=-500", 1000), Some(ByteRange::Satisfiable(500..1000)));
+        assert_eq!(ByteRange::parse("bytes=-5000", 1000), Some(ByteRange::Satisfiable(0..1000)));
+        assert_eq!(ByteRange::parse("bytes=-0", 1000), Some(ByteRange::Unsatisfiable));
+    }
+
+    #[test]
+    fn test_parse_unsatisfiable_ranges() {
+        assert_eq!(ByteRange::parse("bytes=1000-", 1000), Some(ByteRange::Unsatisfiable));
+        assert_eq!(ByteRange::parse("bytes=0-0", 0), Some(ByteRange::Unsatisfiable));
+    }
+
+    #[test]
+    fn test_parse_multiple_ranges() {
+        assert_eq!(ByteRange::parse("bytes=0-1, 4-5", 1000), Some(ByteRange::Multiple));
+        assert_eq!(ByteRange::parse("bytes=0-1,-5", 1000), Some(ByteRange::Multiple));
+    }
+
+    #[test]
+    fn test_parse_ignored_ranges() {
+        assert_eq!(ByteRange::parse("items=0-1", 1000), None);
+        assert_eq!(ByteRange::parse("bytes=5-1", 1000), None);
+        assert_eq!(ByteRange::parse("bytes=-", 1000), None);
+        assert_eq!(ByteRange::parse("bytes=+1-2", 1000), None);
+    }
+}
@@ -1,43 +1,164 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::str::{FromStr, Lines, SplitWhitespace};
+use std::net::IpAddr;
+use std::str::{self, FromStr, Lines, SplitWhitespace, Utf8Error};
+use std::time::Duration;
 
+use super::BodyStream;
 use super::HttpError;
 use super::HttpMethod;
+use super::HttpParseError;
 use super::HttpStatus;
-
-type RequestLine<'a> = (&'a str, &'a str, HttpMethod);
+use super::HttpVersion;
+use super::ResponseWriter;
+use super::ServerTiming;
+use super::error::echoed;
+use super::forwarded::{Forwarded, TrustedProxies};
+use super::framing::{self, BodyFraming};
+use super::params::{ParamValue, ParamsDeserializer};
+use serde::Deserialize;
+use serde::de::value::Error;
+
+type RequestLine<'a> = (&'a str, HttpVersion, HttpMethod);
 pub type Headers<'a> = HashMap<Cow<'a, str>, Cow<'a, str>>;
+/// Route parameters keyed by the name used in the route path, so `/users/:id` matched against
+/// `/users/42` yields `id => 42`. Lookups by name, `len`, `is_empty` and iteration are plain
+/// `HashMap` operations; `Request::param` returns a value without the extra reference.
 pub type Params<'a> = HashMap<&'a str, &'a str>;
 
 const HEADERS_SEPARATOR: char = ':';
+const HTTP: &str = "http";
+const HTTPS: &str = "https";
 
 #[derive(Debug)]
 pub struct Request<'a> {
     pub method: HttpMethod,
     pub path: &'a str,
-    pub version: &'a str,
+    pub version: HttpVersion,
     pub headers: Headers<'a>,
     pub params: Params<'a>,
+    pub body: Cow<'a, [u8]>,
+    matched_path: Option<&'a str>,
+    path_tail: Cow<'a, str>,
+    real_ip: Option<IpAddr>,
+    scheme: &'static str,
+    forwarded_host: Option<Cow<'a, str>>,
+    server_timing: Option<ServerTiming>,
+    body_stream: Option<BodyStream>,
+    response_writer: Option<ResponseWriter>,
+}
+
+pub struct RequestBuilder<'a> {
+    request: Request<'a>,
+}
+
+impl<'a> RequestBuilder<'a> {
+    pub fn version(mut self, version: HttpVersion) -> Self {
+        self.request.version = version;
+        self
+    }
+
+    pub fn header(mut self, key: &'a str, value: &'a str) -> Self {
+        self.request
+            .headers
+            .insert(Request::normalize_header(key), Cow::Borrowed(value));
+        self
+    }
+
+    pub fn body<T>(mut self, body: T) -> Self
+    where
+        T: Into<Cow<'a, [u8]>>,
+    {
+        self.request.body = body.into();
+        self
+    }
+
+    pub fn build(self) -> Request<'a> {
+        self.request
+    }
 }
 
 impl<'a> Request<'a> {
-    pub fn new(raw_request: &'a str) -> Result<Self, HttpError> {
+    pub fn builder(method: HttpMethod, path: &'a str) -> RequestBuilder<'a> {
+        RequestBuilder {
+            request: Self {
+                method,
+                path,
+                version: HttpVersion::default(),
+                headers: HashMap::new(),
+                params: HashMap::new(),
+                body: Cow::Borrowed(&[]),
+                matched_path: None,
+                path_tail: Cow::Borrowed(""),
+                real_ip: None,
+                scheme: HTTP,
+                forwarded_host: None,
+                server_timing: None,
+                body_stream: None,
+                response_writer: None,
+            },
+        }
+    }
+
+    pub fn new<R>(raw_request: &'a R) -> Result<Self, HttpError>
+    where
+        R: AsRef<[u8]> + ?Sized,
+    {
+        Ok(Self::parse(raw_request)?)
+    }
+
+    /// Parses a request from its raw bytes. Only the request line and headers have to be valid
+    /// UTF-8; the body is kept as the bytes it was sent as.
+    pub fn parse<R>(raw_request: &'a R) -> Result<Self, HttpParseError>
+    where
+        R: AsRef<[u8]> + ?Sized,
+    {
+        Self::parse_message(raw_request.as_ref(), true)
+    }
+
+    /// Parses only the request line and headers, leaving `body` empty. Used for routes that read
+    /// their body through `body_stream` while it is still arriving.
+    pub fn parse_head<R>(raw_head: &'a R) -> Result<Self, HttpParseError>
+    where
+        R: AsRef<[u8]> + ?Sized,
+    {
+        Self::parse_message(raw_head.as_ref(), false)
+    }
+
+    fn parse_message(raw_bytes: &'a [u8], read_body: bool) -> Result<Self, HttpParseError> {
+        let head_end: usize = framing::find_head_end(raw_bytes).unwrap_or(raw_bytes.len());
+        let raw_request: &str = str::from_utf8(&raw_bytes[..head_end])
+            .map_err(|e: Utf8Error| HttpParseError::InvalidUtf8(e.valid_up_to()))?;
+
         let mut lines: Lines = raw_request.lines();
+        let request_lines: &str = lines.next().ok_or(HttpParseError::EmptyRequest)?;
 
-        let request_lines: &str = lines
-            .next()
-            .ok_or_else(|| HttpError::new(HttpStatus::BadRequest, "Request line is empty or missing"))?;
+        let (path, version, method): RequestLine = Self::parse_request_line(raw_request, request_lines)?;
+        let headers: Headers = Self::parse_headers(raw_request, lines)?;
 
-        let (path, version, method): RequestLine = Self::parse_request_line(request_lines)?;
-        let headers: Headers = Self::parse_headers(lines)?;
+        if version == HttpVersion::Http11 && !headers.contains_key("host") {
+            return Err(HttpParseError::MissingHost);
+        }
+        let body: Cow<[u8]> = match read_body {
+            true => Self::parse_body(raw_bytes, raw_request)?,
+            false => Cow::Borrowed(&[]),
+        };
 
         Ok(Self {
+            body,
             headers,
             path,
             version,
             method,
             params: HashMap::new(),
+            matched_path: None,
+            path_tail: Cow::Borrowed(""),
+            real_ip: None,
+            scheme: HTTP,
+            forwarded_host: None,
+            server_timing: None,
+            body_stream: None,
+            response_writer: None,
         })
     }
 
@@ -45,45 +166,272 @@ impl<'a> Request<'a> {
         self.params.extend(raw_params);
     }
 
-    fn parse_headers(raw_headers: Lines) -> Result<Headers, HttpError> {
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Deserializes the buffered body, which `T` may borrow from. On `stream_body` routes the body
+    /// is not buffered; read it with `BodyStream::next_json` or `BodyStream::next_array_item`
+    /// instead.
+    pub fn json<'b, T>(&'b self) -> Result<T, HttpError>
+    where
+        T: Deserialize<'b>,
+    {
+        serde_json::from_slice(&self.body)
+            .map_err(|e: serde_json::Error| HttpError::new(HttpStatus::BadRequest, format!("Invalid JSON body: {e}")))
+    }
+
+    pub fn set_body_stream(&mut self, body_stream: BodyStream) {
+        self.body_stream = Some(body_stream);
+    }
+
+    /// Returns the body as a stream of chunks. On `stream_body` routes this is the only way to
+    /// read the body, and it can only be taken once; elsewhere it yields a copy of `body`.
+    pub fn body_stream(&mut self) -> BodyStream {
+        self.body_stream
+            .take()
+            .unwrap_or_else(|| BodyStream::buffered(self.body.to_vec()))
+    }
+
+    pub fn set_response_writer(&mut self, response_writer: ResponseWriter) {
+        self.response_writer = Some(response_writer);
+    }
+
+    /// Takes the `ResponseWriter` of a `stream_response` route. `None` on other routes, and once it
+    /// has been taken.
+    pub fn response_writer(&mut self) -> Option<ResponseWriter> {
+        self.response_writer.take()
+    }
+
+    /// Whether the request has a body, either already read into `body` or declared by its headers
+    /// and still to be read from `body_stream`.
+    pub fn has_body(&self) -> bool {
+        !self.body.is_empty()
+            || match self.framing() {
+                Ok(BodyFraming::Length(length)) => length > 0,
+                Ok(BodyFraming::Chunked) => true,
+                Ok(BodyFraming::Empty) | Err(_) => false,
+            }
+    }
+
+    /// The body length the client declared with `Content-Length`. `None` when the header is
+    /// missing or invalid, and for chunked bodies, whose length is unknown until they are read.
+    pub fn content_length(&self) -> Option<usize> {
+        match self.framing() {
+            Ok(BodyFraming::Length(length)) => Some(length),
+            _ => None,
+        }
+    }
+
+    fn framing(&self) -> Result<BodyFraming, HttpParseError> {
+        BodyFraming::from_headers(
+            self.headers
+                .iter()
+                .map(|(key, value): (&Cow<str>, &Cow<str>)| (key.as_ref(), value.as_ref())),
+        )
+    }
+
+    pub fn param(&self, name: &str) -> Option<&'a str> {
+        self.params.get(name).copied()
+    }
+
+    pub fn params_as<T>(&self) -> Result<T, HttpError>
+    where
+        T: Deserialize<'a>,
+    {
+        let params = self
+            .params
+            .iter()
+            .map(|(key, value): (&&'a str, &&'a str)| (*key, ParamValue(value)));
+
+        T::deserialize(ParamsDeserializer::new(params))
+            .map_err(|e: Error| HttpError::new(HttpStatus::BadRequest, format!("Invalid route parameters: {e}")))
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        let has_token = |token: &str| -> bool {
+            self.headers.get("connection").is_some_and(|value: &Cow<str>| {
+                value
+                    .split(',')
+                    .any(|part: &str| part.trim().eq_ignore_ascii_case(token))
+            })
+        };
+
+        match self.version {
+            _ if has_token("close") => false,
+            HttpVersion::Http10 => has_token("keep-alive"),
+            HttpVersion::Http11 => true,
+        }
+    }
+
+    pub fn set_matched_path(&mut self, matched_path: &'a str) {
+        self.matched_path = Some(matched_path);
+    }
+
+    pub fn matched_path(&self) -> Option<&str> {
+        self.matched_path
+    }
+
+    pub fn set_path_tail(&mut self, path_tail: Cow<'a, str>) {
+        self.path_tail = path_tail;
+    }
+
+    /// The part of the path a fallback route did not match, such as `css/site.css` for a request
+    /// to `/static/css/site.css` handled by a fallback registered at `/static`. Empty otherwise.
+    pub fn path_tail(&self) -> &str {
+        &self.path_tail
+    }
+
+    /// Records where the request came from. `peer` is the address of the connection, and its
+    /// `Forwarded` or `X-Forwarded-*` headers are only used when `peer` is one of `trusted_proxies`;
+    /// from anyone else they could be forged.
+    pub fn set_origin(&mut self, peer: IpAddr, secure: bool, trusted_proxies: &TrustedProxies) {
+        self.real_ip = Some(peer);
+        self.scheme = if secure { HTTPS } else { HTTP };
+
+        if trusted_proxies.is_empty() || !trusted_proxies.contains(peer) {
+            return;
+        }
+
+        let forwarded: Forwarded = Forwarded::from_headers(&self.headers, trusted_proxies);
+        let host: Option<String> = forwarded.host.map(str::to_owned);
+
+        if let Some(client) = forwarded.client {
+            self.real_ip = Some(client);
+        }
+
+        if let Some(proto) = forwarded.proto {
+            self.scheme = if proto.eq_ignore_ascii_case(HTTPS) { HTTPS } else { HTTP };
+        }
+
+        self.forwarded_host = host.map(Cow::Owned);
+    }
+
+    /// The client's address, as reported by a trusted proxy or else the address of the connection.
+    /// `None` for requests that did not come from a connection, such as those built in tests.
+    pub fn real_ip(&self) -> Option<IpAddr> {
+        self.real_ip
+    }
+
+    /// `"https"` or `"http"`, as the client used it.
+    pub fn scheme(&self) -> &str {
+        self.scheme
+    }
+
+    /// The host the client asked for: the one a trusted proxy forwarded, or else the `Host` header.
+    pub fn host(&self) -> Option<&str> {
+        self.forwarded_host
+            .as_deref()
+            .or_else(|| self.headers.get("host").map(|host: &Cow<str>| host.as_ref()))
+    }
+
+    pub fn set_server_timing(&mut self, server_timing: ServerTiming) {
+        self.server_timing = Some(server_timing);
+    }
+
+    pub fn record_timing<T>(&self, name: T, duration: Duration)
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        if let Some(server_timing) = &self.server_timing {
+            server_timing.record(name, duration);
+        }
+    }
+
+    fn parse_headers(raw_request: &'a str, raw_headers: Lines<'a>) -> Result<Headers<'a>, HttpParseError> {
         raw_headers
             .take_while(|line: &&str| !line.trim().is_empty())
             .map(|header: &str| {
-                let values: (&str, &str) = header.split_once(HEADERS_SEPARATOR).ok_or_else(|| {
-                    HttpError::new(HttpStatus::BadRequest, format!("Invalid header format: \"{header}\""))
-                })?;
+                let values: (&str, &str) =
+                    header
+                        .split_once(HEADERS_SEPARATOR)
+                        .ok_or_else(|| HttpParseError::InvalidHeader {
+                            header: echoed(header),
+                            offset: Self::offset_of(raw_request, header),
+                        })?;
 
                 let key: &str = values.0.trim();
                 let value: &str = values.1.trim();
 
-                let key_cow: Cow<str> = if key.as_bytes().iter().any(|byte: &u8| byte.is_ascii_uppercase()) {
-                    Cow::Owned(key.to_ascii_lowercase())
-                } else {
-                    Cow::Borrowed(key)
-                };
-
-                Ok((key_cow, Cow::Borrowed(value)))
+                Ok((Self::normalize_header(key), Cow::Borrowed(value)))
             })
-            .collect::<Result<Headers, HttpError>>()
+            .collect::<Result<Headers, HttpParseError>>()
+    }
+
+    fn normalize_header(key: &'a str) -> Cow<'a, str> {
+        if key.as_bytes().iter().any(|byte: &u8| byte.is_ascii_uppercase()) {
+            Cow::Owned(key.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(key)
+        }
+    }
+
+    fn parse_body(raw_request: &'a [u8], head: &str) -> Result<Cow<'a, [u8]>, HttpParseError> {
+        let head_end: usize = head.len();
+        let framing: BodyFraming = BodyFraming::from_headers(framing::header_fields(head))?;
+
+        match framing {
+            BodyFraming::Empty => Ok(Cow::Borrowed(&[])),
+            BodyFraming::Length(length) => raw_request
+                .get(head_end..)
+                .and_then(|body: &[u8]| body.get(..length))
+                .map(Cow::Borrowed)
+                .ok_or(HttpParseError::IncompleteBody {
+                    expected: length,
+                    received: raw_request.len() - head_end,
+                }),
+            BodyFraming::Chunked => match framing::decode_chunked(raw_request, head_end)? {
+                Some((body, _)) => Ok(Cow::Owned(body)),
+                None => Err(HttpParseError::InvalidChunk(raw_request.len())),
+            },
+        }
     }
 
-    fn parse_request_line(raw_request_line: &str) -> Result<RequestLine<'_>, HttpError> {
+    fn parse_request_line(raw_request: &'a str, raw_request_line: &'a str) -> Result<RequestLine<'a>, HttpParseError> {
         let mut parts: SplitWhitespace = raw_request_line.split_whitespace();
+        let line_end: usize = Self::offset_of(raw_request, raw_request_line) + raw_request_line.len();
 
         let method_str: &str = parts
             .next()
-            .ok_or_else(|| HttpError::new(HttpStatus::BadRequest, "Request line missing HTTP Method"))?;
+            .ok_or(HttpParseError::MissingMethod(Self::offset_of(raw_request, raw_request_line)))?;
+
+        let path: &str = parts.next().ok_or(HttpParseError::MissingPath(line_end))?;
+        let version_str: &str = parts.next().ok_or(HttpParseError::MissingVersion(line_end))?;
+
+        let version: HttpVersion =
+            HttpVersion::from_str(version_str).map_err(|_: HttpError| HttpParseError::UnsupportedVersion {
+                version: echoed(version_str),
+                offset: Self::offset_of(raw_request, version_str),
+            })?;
+
+        let method: HttpMethod =
+            HttpMethod::from_str(method_str).map_err(|_: HttpError| HttpParseError::UnknownMethod {
+                method: echoed(method_str),
+                offset: Self::offset_of(raw_request, method_str),
+            })?;
+
+        if method == HttpMethod::CONNECT && !Self::is_authority_form(path) {
+            return Err(HttpParseError::InvalidConnectTarget {
+                target: echoed(path),
+                offset: Self::offset_of(raw_request, path),
+            });
+        }
 
-        let path: &str = parts
-            .next()
-            .ok_or_else(|| HttpError::new(HttpStatus::BadRequest, "Request line missing URI Path"))?;
+        Ok((path, version, method))
+    }
 
-        let version: &str = parts
-            .next()
-            .ok_or_else(|| HttpError::new(HttpStatus::BadRequest, "Request line missing HTTP Version"))?;
+    /// `CONNECT` names the `host:port` to tunnel to instead of a path.
+    fn is_authority_form(target: &str) -> bool {
+        target.rsplit_once(':').is_some_and(|(host, port): (&str, &str)| {
+            !host.is_empty()
+                && !host.contains('/')
+                && !port.is_empty()
+                && port.bytes().all(|byte: u8| byte.is_ascii_digit())
+        })
+    }
 
-        let method: HttpMethod = HttpMethod::from_str(method_str)?;
-        Ok((path, version, method))
+    fn offset_of(raw_request: &str, part: &str) -> usize {
+        part.as_ptr().addr() - raw_request.as_ptr().addr()
     }
 }
 
@@ -98,13 +446,13 @@ mod tests {
 
         assert_eq!(req.method, HttpMethod::GET);
         assert_eq!(req.path, "/index.html");
-        assert_eq!(req.version, "HTTP/1.1");
+        assert_eq!(req.version, HttpVersion::Http11);
         assert_eq!(req.headers.get("host").map(|v| v.as_ref()), Some("localhost"));
     }
 
     #[test]
     fn test_parse_headers_case_insensitivity() {
-        let raw: &str = "POST /submit HTTP/1.1\r\nCONTENT-TYPE: application/json\r\nX-Custom-Header: value\r\n\r\n";
+        let raw: &str = "POST /submit HTTP/1.1\r\nHost: localhost\r\nCONTENT-TYPE: application/json\r\nX-Custom-Header: value\r\n\r\n";
         let req: Request = Request::new(raw).expect("Should parse headers");
 
         assert!(req.headers.contains_key("content-type"));
@@ -123,7 +471,7 @@ mod tests {
 
     #[test]
     fn test_parse_headers_trim_whitespace() {
-        let raw: &str = "GET / HTTP/1.1\r\nKey:    value with spaces    \r\n\r\n";
+        let raw: &str = "GET / HTTP/1.1\r\nHost: localhost\r\nKey:    value with spaces    \r\n\r\n";
         let req: Request = Request::new(raw).unwrap();
 
         assert_eq!(req.headers.get("key").map(|v| v.as_ref()), Some("value with spaces"));
@@ -140,7 +488,7 @@ mod tests {
 
     #[test]
     fn test_request_invalid_method() {
-        let raw: &str = "INVALIDMETHOD /path HTTP/1.1\r\n\r\n";
+        let raw: &str = "INVALIDMETHOD /path HTTP/1.1\r\nHost: localhost\r\n\r\n";
         let result: Result<Request, HttpError> = Request::new(raw);
 
         assert!(result.is_err());
@@ -158,7 +506,7 @@ mod tests {
 
     #[test]
     fn test_header_missing_colon() {
-        let raw: &str = "GET / HTTP/1.1\r\nInvalidHeader\r\n\r\n";
+        let raw: &str = "GET / HTTP/1.1\r\nHost: localhost\r\nInvalidHeader\r\n\r\n";
         let result: Result<Request, HttpError> = Request::new(raw);
 
         assert!(result.is_err());
@@ -167,7 +515,7 @@ mod tests {
 
     #[test]
     fn test_set_params() {
-        let raw: &str = "GET /store/123 HTTP/1.1\r\n\r\n";
+        let raw: &str = "GET /store/123 HTTP/1.1\r\nHost: localhost\r\n\r\n";
         let mut req: Request = Request::new(raw).unwrap();
 
         assert!(req.params.is_empty());
@@ -178,5 +526,335 @@ mod tests {
         assert_eq!(req.params.len(), 2);
         assert_eq!(req.params.get("store_id"), Some(&"123"));
         assert_eq!(req.params.get("filter"), Some(&"active"));
+        assert_eq!(req.param("store_id"), Some("123"));
+        assert_eq!(req.param("missing"), None);
+    }
+
+    #[test]
+    fn test_lf_only_matches_crlf() {
+        let crlf: &str = "POST /submit HTTP/1.1\r\nHost: localhost\r\nContent-Type: text/plain\r\n\r\n";
+        let lf: &str = "POST /submit HTTP/1.1\nHost: localhost\nContent-Type: text/plain\n\n";
+
+        let crlf_req: Request = Request::new(crlf).expect("Should parse CRLF request");
+        let lf_req: Request = Request::new(lf).expect("Should parse LF-only request");
+
+        assert_eq!(crlf_req.method, lf_req.method);
+        assert_eq!(crlf_req.path, lf_req.path);
+        assert_eq!(crlf_req.version, lf_req.version);
+        assert_eq!(crlf_req.headers, lf_req.headers);
+        assert_eq!(lf_req.headers.len(), 2);
+    }
+
+    #[test]
+    fn test_mixed_line_endings() {
+        let raw: &str = "GET / HTTP/1.1\nHost: localhost\r\nAccept: */*\n\r\nIgnored: after-boundary\r\n";
+        let req: Request = Request::new(raw).expect("Should parse mixed line endings");
+
+        assert_eq!(req.version, HttpVersion::Http11);
+        assert_eq!(req.headers.len(), 2);
+        assert_eq!(req.headers.get("accept").map(|v| v.as_ref()), Some("*/*"));
+        assert!(!req.headers.contains_key("ignored"));
+    }
+
+    #[test]
+    fn test_matched_path() {
+        let raw: &str = "GET /users/123 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let mut req: Request = Request::new(raw).unwrap();
+
+        assert_eq!(req.matched_path(), None);
+
+        req.set_matched_path("/users/:id");
+        assert_eq!(req.matched_path(), Some("/users/:id"));
+        assert_eq!(req.path, "/users/123");
+    }
+
+    #[test]
+    fn test_parse_error_offsets() {
+        assert_eq!(Request::parse("").unwrap_err(), HttpParseError::EmptyRequest);
+        assert_eq!(Request::parse("\r\n").unwrap_err(), HttpParseError::MissingMethod(0));
+        assert_eq!(Request::parse("GET\r\n").unwrap_err(), HttpParseError::MissingPath(3));
+        assert_eq!(Request::parse("GET /path\r\n").unwrap_err(), HttpParseError::MissingVersion(9));
+
+        assert_eq!(
+            Request::parse("  BREW /pot HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap_err(),
+            HttpParseError::UnknownMethod {
+                method: "BREW".into(),
+                offset: 2
+            }
+        );
+
+        assert_eq!(
+            Request::parse("GET / HTTP/1.1\r\nHost: localhost\r\nBroken\r\n\r\n").unwrap_err(),
+            HttpParseError::InvalidHeader {
+                header: "Broken".into(),
+                offset: 33
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error_converts_to_bad_request() {
+        let error: HttpError = Request::new("GET / HTTP/1.1\r\nHost: localhost\r\nBroken\r\n\r\n").unwrap_err();
+
+        assert_eq!(error.status, HttpStatus::BadRequest);
+        assert_eq!(error.message, "Invalid header format \"Broken\" at byte 33");
+    }
+
+    #[test]
+    fn test_http_1_1_requires_host() {
+        assert_eq!(
+            Request::parse("GET / HTTP/1.1\r\n\r\n").unwrap_err(),
+            HttpParseError::MissingHost
+        );
+        assert_eq!(
+            Request::new("GET / HTTP/1.1\r\n\r\n").unwrap_err().status,
+            HttpStatus::BadRequest
+        );
+        assert!(Request::parse("GET / HTTP/1.0\r\n\r\n").is_ok());
+    }
+
+    #[test]
+    fn test_parse_errors_escape_and_truncate_client_bytes() {
+        let raw: String = format!("GET / HTTP/1.1\r\nHost: localhost\r\n\"\x1b[2J{}\r\n\r\n", "a".repeat(100));
+        let error: HttpError = Request::new(&raw).unwrap_err();
+
+        assert_eq!(
+            error.message,
+            format!("Invalid header format \"\\\"\\u{{1b}}[2J{}...\" at byte 33", "a".repeat(59))
+        );
+    }
+
+    #[test]
+    fn test_parse_content_length_body() {
+        let raw: &str = "POST /users HTTP/1.1\r\nHost: localhost\r\nContent-Length: 12\r\n\r\n{\"id\": 1234}";
+        let req: Request = Request::new(raw).unwrap();
+
+        assert_eq!(req.body.as_ref(), b"{\"id\": 1234}");
+        assert!(matches!(req.body, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_parse_chunked_body() {
+        let raw: &str = "POST /upload HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n";
+        let req: Request = Request::new(raw).unwrap();
+
+        assert_eq!(req.body.as_ref(), b"Wikipedia");
+        assert!(matches!(req.body, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_binary_body_is_kept_as_bytes() {
+        let raw: &[u8] = b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\n\xff\xfe\x00\x80";
+        let req: Request = Request::new(raw).unwrap();
+        assert_eq!(req.body.as_ref(), b"\xff\xfe\x00\x80");
+
+        let raw: &[u8] = b"POST /\xff HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert_eq!(Request::parse(raw).unwrap_err(), HttpParseError::InvalidUtf8(6));
+    }
+
+    #[test]
+    fn test_parse_connect_authority_target() {
+        let req: Request = Request::new("CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n").unwrap();
+        assert_eq!(req.method, HttpMethod::CONNECT);
+        assert_eq!(req.path, "example.com:443");
+
+        for target in ["/tunnel", "example.com", "example.com:", ":443", "example.com/a:443"] {
+            let raw: String = format!("CONNECT {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+            let result: Result<Request, HttpError> = Request::new(&raw);
+            assert_eq!(result.unwrap_err().status, HttpStatus::BadRequest, "{target}");
+        }
+    }
+
+    #[test]
+    fn test_has_body_and_content_length() {
+        let sized: Request =
+            Request::parse_head("POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\n").unwrap();
+        assert!(sized.has_body());
+        assert_eq!(sized.content_length(), Some(5));
+
+        let chunked: Request =
+            Request::parse_head("POST /upload HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n")
+                .unwrap();
+        assert!(chunked.has_body());
+        assert_eq!(chunked.content_length(), None);
+
+        let empty: Request =
+            Request::new("POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n").unwrap();
+        assert!(!empty.has_body());
+        assert_eq!(empty.content_length(), Some(0));
+
+        let built: Request = Request::builder(HttpMethod::POST, "/upload").body(&b"data"[..]).build();
+        assert!(built.has_body());
+        assert_eq!(built.content_length(), None);
+    }
+
+    #[test]
+    fn test_malformed_chunked_body() {
+        let raw: &str =
+            "POST /upload HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\nXYZ\r\nWiki\r\n0\r\n\r\n";
+        let result: Result<Request, HttpError> = Request::new(raw);
+
+        assert_eq!(result.unwrap_err().status, HttpStatus::BadRequest);
+    }
+
+    #[test]
+    fn test_body_shorter_than_content_length() {
+        let raw: &str = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nshort";
+
+        assert_eq!(
+            Request::parse(raw).unwrap_err(),
+            HttpParseError::IncompleteBody {
+                expected: 10,
+                received: 5
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_http_1_0_version() {
+        let req: Request = Request::new("GET / HTTP/1.0\r\n\r\n").unwrap();
+
+        assert_eq!(req.version, HttpVersion::Http10);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let raw: &str = "PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+        assert_eq!(
+            Request::parse(raw).unwrap_err(),
+            HttpParseError::UnsupportedVersion {
+                version: "HTTP/2.0".into(),
+                offset: 6
+            }
+        );
+
+        assert_eq!(Request::new(raw).unwrap_err().status, HttpStatus::HttpVersionNotSupported);
+    }
+
+    #[test]
+    fn test_duplicate_content_length_is_rejected() {
+        let raw: &str = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\ncontent-length: 5\r\n\r\nhello";
+
+        assert_eq!(Request::parse(raw).unwrap_err(), HttpParseError::ConflictingContentLength(3, 5));
+        assert_eq!(Request::new(raw).unwrap_err().status, HttpStatus::BadRequest);
+    }
+
+    #[test]
+    fn test_record_timing_requires_server_timing() {
+        let mut req: Request = Request::new("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        req.record_timing("ignored", Duration::from_millis(1));
+
+        let server_timing: ServerTiming = ServerTiming::new();
+        req.set_server_timing(server_timing.clone());
+        req.record_timing("db", Duration::from_millis(2));
+
+        assert_eq!(server_timing.header_value(), "db;dur=2.000");
+    }
+
+    #[test]
+    fn test_builder_creates_synthetic_request() {
+        let req: Request = Request::builder(HttpMethod::POST, "/users")
+            .header("Content-Type", "application/json")
+            .body(&b"{}"[..])
+            .build();
+
+        assert_eq!(req.method, HttpMethod::POST);
+        assert_eq!(req.path, "/users");
+        assert_eq!(req.version, HttpVersion::Http11);
+        assert_eq!(req.headers.get("content-type").map(|v| v.as_ref()), Some("application/json"));
+        assert_eq!(req.body.as_ref(), b"{}");
+    }
+
+    #[test]
+    fn test_json_body_can_be_borrowed() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct NewUser<'a> {
+            name: &'a str,
+        }
+
+        let req: Request = Request::builder(HttpMethod::POST, "/users")
+            .body(&br#"{"name": "alice"}"#[..])
+            .build();
+        assert_eq!(req.json::<NewUser>().unwrap(), NewUser { name: "alice" });
+
+        let invalid: Request = Request::builder(HttpMethod::POST, "/users").body(&b"{"[..]).build();
+        assert_eq!(invalid.json::<NewUser>().unwrap_err().status, HttpStatus::BadRequest);
+    }
+
+    #[test]
+    fn test_params_as_struct() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct OrderParams<'a> {
+            user: &'a str,
+            order: u64,
+            archived: Option<bool>,
+        }
+
+        let mut req: Request = Request::new("GET /users/alice/orders/42 HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        req.set_params(vec![
+            ("user", "alice"),
+            ("order", "42"),
+            ("archived", "false"),
+            ("extra", "ignored"),
+        ]);
+
+        assert_eq!(
+            req.params_as::<OrderParams>().unwrap(),
+            OrderParams {
+                user: "alice",
+                order: 42,
+                archived: Some(false),
+            }
+        );
+    }
+
+    #[test]
+    fn test_params_as_rejects_missing_and_invalid_params() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct OrderParams {
+            order: u64,
+        }
+
+        let mut req: Request = Request::new("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        let missing: HttpError = req.params_as::<OrderParams>().unwrap_err();
+        assert_eq!(missing.status, HttpStatus::BadRequest);
+        assert!(missing.message.contains("missing field `order`"));
+
+        req.set_params(vec![("order", "abc")]);
+        let invalid: HttpError = req.params_as::<OrderParams>().unwrap_err();
+        assert_eq!(invalid.status, HttpStatus::BadRequest);
+        assert!(invalid.message.contains("invalid value \"abc\""));
+    }
+
+    #[test]
+    fn test_params_get_as_distinguishes_missing_from_invalid() {
+        use crate::ParamsExt;
+        use std::num::ParseIntError;
+
+        let mut req: Request = Request::new("GET /users/42/posts/abc HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        req.set_params(vec![("id", "42"), ("post", "abc")]);
+
+        assert_eq!(req.params.get_as::<u64>("id"), Some(Ok(42)));
+        assert!(matches!(req.params.get_as::<u64>("post"), Some(Err(ParseIntError { .. }))));
+        assert_eq!(req.params.get_as::<u64>("missing"), None);
+    }
+
+    #[test]
+    fn test_keep_alive_defaults_by_version() {
+        let http_11: Request = Request::new("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        assert!(http_11.keep_alive());
+
+        let http_11_close: Request =
+            Request::new("GET / HTTP/1.1\r\nHost: localhost\r\nConnection: Close\r\n\r\n").unwrap();
+        assert!(!http_11_close.keep_alive());
+
+        let http_10: Request = Request::new("GET / HTTP/1.0\r\n\r\n").unwrap();
+        assert!(!http_10.keep_alive());
+
+        let http_10_keep_alive: Request = Request::new("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n").unwrap();
+        assert!(http_10_keep_alive.keep_alive());
     }
 }
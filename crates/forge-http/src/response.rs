@@ -1,31 +1,135 @@
+use std::time::SystemTime;
 use std::{borrow::Cow, io::Write};
 
-use super::{HttpError, HttpStatus};
-use monoio::{io::AsyncWriteRentExt, net::TcpStream};
+use super::encoding;
+use super::etag;
+use super::http_date;
+use super::{ByteRange, HttpError, HttpStatus, HttpVersion};
+use monoio::io::{AsyncWriteRent, AsyncWriteRentExt};
 use serde::Serialize;
 
 const EXPECTED_BUFFER_SIZE: usize = 1024;
+type DeferredBody<'a> = Box<dyn FnOnce() -> Vec<u8> + 'a>;
+
+const NOT_MODIFIED_HEADERS: [&str; 7] = [
+    "cache-control",
+    "content-location",
+    "date",
+    "etag",
+    "expires",
+    "last-modified",
+    "vary",
+];
 
 pub struct Response<'a> {
     status: HttpStatus,
-    body: Option<Cow<'a, str>>,
+    version: HttpVersion,
+    body: Option<Cow<'a, [u8]>>,
+    gzip_body: Option<Cow<'a, [u8]>>,
+    deferred_body: Option<DeferredBody<'a>>,
     headers: Vec<(Cow<'a, str>, Cow<'a, str>)>,
 }
 
 impl<'a> Response<'a> {
+    pub const DEFAULT_MAX_WRITE_BUFFER: usize = 64 * 1024;
+
     pub fn new(status: HttpStatus) -> Self {
         Self {
             status,
+            version: HttpVersion::default(),
             body: None,
+            gzip_body: None,
+            deferred_body: None,
             headers: Vec::new(),
         }
     }
 
-    pub fn body<T>(mut self, body: T) -> Self
+    pub fn no_content() -> Self {
+        Self::new(HttpStatus::NoContent)
+    }
+
+    pub fn created<T>(location: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        Self::new(HttpStatus::Created).header("Location", location)
+    }
+
+    pub fn not_modified() -> Self {
+        Self::new(HttpStatus::NotModified)
+    }
+
+    /// An error with a JSON body shaped `{"error": {"status": 404, "message": "..."}}`, for APIs
+    /// that want every error in the same envelope. `HttpError` converts into a plain-text response
+    /// instead; use `HttpError::into_json_response` to get this shape from one.
+    pub fn error<T>(status: HttpStatus, message: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        Self::new(status).json(serde_json::json!({
+            "error": {
+                "status": u16::from(status),
+                "message": message.as_ref(),
+            }
+        }))
+    }
+
+    pub fn body<T>(self, body: T) -> Self
     where
         T: Into<Cow<'a, str>>,
     {
-        self.body.replace(body.into());
+        let bytes: Cow<'a, [u8]> = match body.into() {
+            Cow::Borrowed(body) => Cow::Borrowed(body.as_bytes()),
+            Cow::Owned(body) => Cow::Owned(body.into_bytes()),
+        };
+
+        self.bytes(bytes)
+    }
+
+    pub fn bytes<T>(mut self, bytes: T) -> Self
+    where
+        T: Into<Cow<'a, [u8]>>,
+    {
+        self.deferred_body = None;
+        self.body.replace(bytes.into());
+        self
+    }
+
+    /// Sets a body that is only built when the response is sent. If the response ends up without a
+    /// body (for example after `if_none_match` turns it into a 304), the closure never runs. The
+    /// body is still sent with a `Content-Length`, and `get_body` does not see it until then.
+    pub fn body_with<F, T>(mut self, body: F) -> Self
+    where
+        F: FnOnce() -> T + 'a,
+        T: Into<Cow<'a, str>>,
+    {
+        self.body = None;
+        self.deferred_body
+            .replace(Box::new(|| body().into().into_owned().into_bytes()));
+
+        self
+    }
+
+    pub fn precompressed<P, G>(mut self, plain: P, gzipped: G) -> Self
+    where
+        P: Into<Cow<'a, [u8]>>,
+        G: Into<Cow<'a, [u8]>>,
+    {
+        self.gzip_body.replace(gzipped.into());
+        self.vary("Accept-Encoding").bytes(plain)
+    }
+
+    pub fn accept_encoding(mut self, accept_encoding: &str) -> Self {
+        match self.gzip_body.take() {
+            Some(gzip_body) if encoding::accepts_gzip(accept_encoding) => {
+                self.header("Content-Encoding", "gzip").bytes(gzip_body)
+            }
+            _ => self,
+        }
+    }
+
+    pub fn version(mut self, version: HttpVersion) -> Self {
+        self.version = version;
         self
     }
 
@@ -38,6 +142,189 @@ impl<'a> Response<'a> {
         self
     }
 
+    pub fn headers<I, T, K>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (T, K)>,
+        T: Into<Cow<'a, str>>,
+        K: Into<Cow<'a, str>>,
+    {
+        self.headers.extend(
+            headers
+                .into_iter()
+                .map(|(key, value): (T, K)| (key.into(), value.into())),
+        );
+
+        self
+    }
+
+    pub fn default_headers<I, T, K>(self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (T, K)>,
+        T: Into<Cow<'a, str>>,
+        K: Into<Cow<'a, str>>,
+    {
+        headers.into_iter().fold(self, |response: Self, (key, value): (T, K)| {
+            let key: Cow<'a, str> = key.into();
+
+            match response.get_header(&key) {
+                Some(_) => response,
+                None => response.header(key, value),
+            }
+        })
+    }
+
+    /// Sets `Content-Type` when the response has a body but no content type of its own.
+    pub fn default_content_type<T>(self, content_type: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        let has_body: bool = self.body.is_some() || self.deferred_body.is_some();
+
+        match has_body && self.get_header("content-type").is_none() {
+            true => self.header("Content-Type", content_type),
+            false => self,
+        }
+    }
+
+    pub fn status(&self) -> HttpStatus {
+        self.status
+    }
+
+    pub fn get_body(&self) -> Option<&[u8]> {
+        self.body.as_deref()
+    }
+
+    pub fn get_header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _): &&(Cow<str>, Cow<str>)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v): &(Cow<str>, Cow<str>)| v.as_ref())
+    }
+
+    pub fn vary<T>(mut self, header: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        let header: Cow<'a, str> = header.into();
+
+        let Some((_, value)) = self
+            .headers
+            .iter_mut()
+            .find(|(key, _): &&mut (Cow<str>, Cow<str>)| key.eq_ignore_ascii_case("vary"))
+        else {
+            return self.header("Vary", header);
+        };
+
+        let already_listed: bool = value
+            .split(',')
+            .map(str::trim)
+            .any(|listed: &str| listed == "*" || listed.eq_ignore_ascii_case(&header));
+
+        if header == "*" {
+            *value = header;
+        } else if !already_listed {
+            *value = Cow::Owned(format!("{value}, {header}"));
+        }
+
+        self
+    }
+
+    pub fn etag<T>(self, tag: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        self.header("ETag", tag)
+    }
+
+    pub fn if_none_match(mut self, if_none_match: &str) -> Self {
+        let is_match: bool = self.status == HttpStatus::Ok
+            && self
+                .get_header("etag")
+                .is_some_and(|tag: &str| etag::weak_match(if_none_match, tag));
+
+        if is_match {
+            self.set_not_modified();
+        }
+
+        self
+    }
+
+    /// Sets `Last-Modified` to `time`, truncated to the second.
+    pub fn last_modified(self, time: SystemTime) -> Self {
+        self.header("Last-Modified", http_date::format(time))
+    }
+
+    /// Turns a `200 OK` into `304 Not Modified` when its `Last-Modified` is no later than
+    /// `if_modified_since`. Both are compared at second granularity, and a date that is not an
+    /// IMF-fixdate leaves the response unchanged. Requests that also send `If-None-Match` should
+    /// only be checked with `if_none_match`, since an entity tag is the stronger validator.
+    pub fn if_modified_since(mut self, if_modified_since: &str) -> Self {
+        let last_modified: Option<SystemTime> = self.get_header("last-modified").and_then(http_date::parse);
+
+        let is_unmodified: bool = self.status == HttpStatus::Ok
+            && last_modified
+                .zip(http_date::parse(if_modified_since))
+                .is_some_and(|(last_modified, since): (SystemTime, SystemTime)| last_modified <= since);
+
+        if is_unmodified {
+            self.set_not_modified();
+        }
+
+        self
+    }
+
+    fn set_not_modified(&mut self) {
+        self.status = HttpStatus::NotModified;
+        self.body = None;
+        self.deferred_body = None;
+        self.headers.retain(|(key, _): &(Cow<str>, Cow<str>)| {
+            NOT_MODIFIED_HEADERS
+                .iter()
+                .any(|header: &&str| key.eq_ignore_ascii_case(header))
+        });
+    }
+
+    pub fn accept_ranges(self) -> Self {
+        self.header("Accept-Ranges", "bytes")
+    }
+
+    /// Answers a single satisfiable range with `206 Partial Content`, and an unsatisfiable range or
+    /// a request for several ranges with `416 Range Not Satisfiable`. A header that cannot be read
+    /// is ignored and the whole body is sent.
+    pub fn range(mut self, range: &str) -> Self {
+        let accepts_ranges: bool = self
+            .get_header("accept-ranges")
+            .is_some_and(|value: &str| value.eq_ignore_ascii_case("bytes"));
+
+        if self.status != HttpStatus::Ok || !accepts_ranges {
+            return self;
+        }
+
+        self = self.resolve_body();
+
+        let body: Cow<[u8]> = self.body.take().unwrap_or_default();
+        let len: usize = body.len();
+
+        match ByteRange::parse(range, len) {
+            None => self.bytes(body),
+            Some(ByteRange::Unsatisfiable | ByteRange::Multiple) => {
+                self.status = HttpStatus::RangeNotSatisfiable;
+                self.header("Content-Range", format!("bytes */{len}"))
+            }
+            Some(ByteRange::Satisfiable(range)) => {
+                self.status = HttpStatus::PartialContent;
+                let content_range: String = format!("bytes {}-{}/{len}", range.start, range.end - 1);
+
+                let partial: Cow<[u8]> = match body {
+                    Cow::Borrowed(body) => Cow::Borrowed(&body[range]),
+                    Cow::Owned(body) => Cow::Owned(body[range].to_vec()),
+                };
+
+                self.header("Content-Range", content_range).bytes(partial)
+            }
+        }
+    }
+
     pub fn text<T>(self, text: T) -> Self
     where
         T: Into<Cow<'a, str>>,
@@ -53,44 +340,192 @@ impl<'a> Response<'a> {
             Ok(v) => self.header("Content-Type", "application/json").body(v),
             Err(e) => {
                 self.status = HttpStatus::InternalServerError;
-                self.body.replace(format!("JSON Serialization Failed: {e:?}").into());
-                self
+                self.body(format!("JSON Serialization Failed: {e:?}"))
             }
         }
     }
 
+    /// Sends `json` as the body as is, for JSON that is already serialized (from a cache, or built
+    /// by the database with `row_to_json`). It is not parsed or checked, so the caller is
+    /// responsible for it being valid JSON.
+    pub fn json_raw<T>(self, json: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        self.header("Content-Type", "application/json").body(json)
+    }
+
     fn write_head_to_buffer(&self, buffer: &mut Vec<u8>) -> Result<(), HttpError> {
-        write!(buffer, "HTTP/1.1 {} {}\r\n", u16::from(self.status), self.status)?;
+        write!(buffer, "{} {} {}\r\n", self.version, u16::from(self.status), self.status)?;
 
         for (key, value) in &self.headers {
             write!(buffer, "{key}: {value}\r\n")?;
         }
 
-        let content_length: usize = self.body.as_ref().map(|b: &Cow<str>| b.len()).unwrap_or(0);
+        if !self.allows_body() {
+            buffer.extend_from_slice(b"\r\n");
+            return Ok(());
+        }
+
+        let content_length: usize = self.body.as_ref().map(|b: &Cow<[u8]>| b.len()).unwrap_or(0);
+
+        debug_assert!(
+            self.get_header("content-length")
+                .is_none_or(|declared: &str| declared.trim().parse() == Ok(content_length)),
+            "Content-Length header disagrees with the {content_length}-byte body"
+        );
+
         write!(buffer, "Content-Length: {content_length}\r\n\r\n")
             .map_err(|_| HttpError::new(HttpStatus::InternalServerError, "Headers too long for buffer"))?;
 
         Ok(())
     }
 
-    pub async fn send(self, stream: &mut TcpStream) -> Result<(), HttpError> {
-        let content_length: usize = self.body.as_ref().map(|b: &Cow<str>| b.len()).unwrap_or(0);
-        let mut buffer: Vec<u8> = Vec::with_capacity(EXPECTED_BUFFER_SIZE + content_length);
+    /// Appends the status line and headers of a response whose body follows in pieces of unknown
+    /// total length: as a chunked body when `chunked`, otherwise unframed, ending when the
+    /// connection closes (for HTTP/1.0 clients). The body set on the response, if any, is ignored.
+    pub fn write_streaming_head(&self, buffer: &mut Vec<u8>, chunked: bool) -> Result<(), HttpError> {
+        write!(buffer, "{} {} {}\r\n", self.version, u16::from(self.status), self.status)?;
 
-        self.write_head_to_buffer(&mut buffer)?;
+        for (key, value) in &self.headers {
+            if !key.eq_ignore_ascii_case("content-length") && !key.eq_ignore_ascii_case("transfer-encoding") {
+                write!(buffer, "{key}: {value}\r\n")?;
+            }
+        }
 
-        if let Some(body) = &self.body {
-            buffer.extend_from_slice(body.as_bytes());
+        if chunked && self.allows_body() {
+            buffer.extend_from_slice(b"Transfer-Encoding: chunked\r\n");
         }
 
-        stream
-            .write_all(buffer)
-            .await
-            .0
-            .map_err(|_| HttpError::new(HttpStatus::InternalServerError, "Failed to write response"))?;
+        buffer.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+
+    /// Copies the status, headers and body into owned storage so the response can be kept (for
+    /// example in a cache) after the request it was built from is gone, and sent again later. A
+    /// body set with `body_with` is built first, since the closure can only run once.
+    pub fn into_owned(self) -> Response<'static> {
+        self.resolve_body().duplicate()
+    }
+
+    /// Returns an owned copy, or `None` if the body was set with `body_with` and has not been built
+    /// yet: that closure can only run once, so such a response cannot be copied without consuming
+    /// it. Caching layers should skip those responses or go through `into_owned` instead.
+    pub fn try_clone(&self) -> Option<Response<'static>> {
+        self.deferred_body.is_none().then(|| self.duplicate())
+    }
+
+    /// Copies everything except a deferred body, so it is meant for responses that went through
+    /// `into_owned` first.
+    pub(crate) fn duplicate(&self) -> Response<'static> {
+        let owned = |bytes: &Cow<[u8]>| -> Cow<'static, [u8]> { Cow::Owned(bytes.to_vec()) };
+
+        Response {
+            status: self.status,
+            version: self.version,
+            body: self.body.as_ref().map(owned),
+            gzip_body: self.gzip_body.as_ref().map(owned),
+            deferred_body: None,
+            headers: self
+                .headers
+                .iter()
+                .map(|(key, value): &(Cow<str>, Cow<str>)| (Cow::Owned(key.to_string()), Cow::Owned(value.to_string())))
+                .collect(),
+        }
+    }
+
+    fn resolve_body(mut self) -> Self {
+        match self.deferred_body.take() {
+            Some(deferred_body) if self.allows_body() => self.bytes(deferred_body()),
+            _ => self,
+        }
+    }
+
+    /// Whether the status may carry a body: not 1xx, 204 or 304.
+    pub fn allows_body(&self) -> bool {
+        let status: u16 = u16::from(self.status);
+        !(100..200).contains(&status) && !matches!(self.status, HttpStatus::NoContent | HttpStatus::NotModified)
+    }
+
+    /// Writes the response and returns how many bytes went out, head included.
+    pub async fn send<S>(self, stream: &mut S) -> Result<usize, HttpError>
+    where
+        S: AsyncWriteRent,
+    {
+        self.send_buffered(stream, Self::DEFAULT_MAX_WRITE_BUFFER).await
+    }
+
+    /// Sends the head and a body of up to `max_write_buffer` bytes in a single write. A larger body
+    /// is written after the head instead of being copied next to it: an owned body as is, and a
+    /// borrowed one through a buffer of `max_write_buffer` bytes. Either way it is framed by its
+    /// `Content-Length` and never chunked, since it is already in memory; a body produced piece by
+    /// piece belongs on a `stream_response` route, written through `ResponseWriter`.
+    pub async fn send_buffered<S>(self, stream: &mut S, max_write_buffer: usize) -> Result<usize, HttpError>
+    where
+        S: AsyncWriteRent,
+    {
+        let response: Self = self.resolve_body();
+        let allows_body: bool = response.allows_body();
+        let content_length: usize = response.body.as_ref().map(|b: &Cow<[u8]>| b.len()).unwrap_or(0);
+
+        if content_length <= max_write_buffer {
+            let mut buffer: Vec<u8> = Vec::with_capacity(EXPECTED_BUFFER_SIZE + content_length);
+            response.write_to(&mut buffer)?;
+            return Self::write_buffer(stream, buffer)
+                .await
+                .map(|buffer: Vec<u8>| buffer.len());
+        }
+
+        let mut buffer: Vec<u8> = Vec::with_capacity(EXPECTED_BUFFER_SIZE);
+        response.write_head_to_buffer(&mut buffer)?;
+        let head_len: usize = buffer.len();
+
+        let Some(body) = response.body.filter(|_: &Cow<[u8]>| allows_body) else {
+            return Self::write_buffer(stream, buffer).await.map(|_| head_len);
+        };
+
+        buffer = Self::write_buffer(stream, buffer).await?;
+        let mut written: usize = 0;
+
+        match body {
+            Cow::Owned(body) => written += Self::write_buffer(stream, body).await?.len(),
+            Cow::Borrowed(body) => {
+                for piece in body.chunks(max_write_buffer.max(1)) {
+                    buffer.clear();
+                    buffer.extend_from_slice(piece);
+                    buffer = Self::write_buffer(stream, buffer).await?;
+                    written += piece.len();
+                }
+            }
+        }
+
+        debug_assert_eq!(written, content_length, "Body length differs from the declared Content-Length");
+        Ok(head_len + written)
+    }
+
+    /// Appends the serialized response to `buffer` instead of writing it, so several responses can
+    /// go out in one write.
+    pub fn write_to(self, buffer: &mut Vec<u8>) -> Result<(), HttpError> {
+        let response: Self = self.resolve_body();
+        response.write_head_to_buffer(buffer)?;
+
+        if let Some(body) = response.body.as_ref().filter(|_: &&Cow<[u8]>| response.allows_body()) {
+            buffer.extend_from_slice(body);
+        }
 
         Ok(())
     }
+
+    async fn write_buffer<S>(stream: &mut S, buffer: Vec<u8>) -> Result<Vec<u8>, HttpError>
+    where
+        S: AsyncWriteRent,
+    {
+        let (result, buffer): (std::io::Result<usize>, Vec<u8>) = stream.write_all(buffer).await;
+
+        result
+            .map(|_| buffer)
+            .map_err(|_| HttpError::new(HttpStatus::InternalServerError, "Failed to write response"))
+    }
 }
 
 pub trait IntoResponse<'a> {
@@ -103,6 +538,23 @@ impl<'a> IntoResponse<'a> for Response<'a> {
     }
 }
 
+impl<'a> IntoResponse<'a> for serde_json::Value {
+    fn into_response(self) -> Response<'a> {
+        Response::new(HttpStatus::Ok).json(self)
+    }
+}
+
+pub struct Json<T>(pub T);
+
+impl<'a, T> IntoResponse<'a> for Json<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response<'a> {
+        Response::new(HttpStatus::Ok).json(self.0)
+    }
+}
+
 impl<'a> From<HttpError> for Response<'a> {
     fn from(e: HttpError) -> Self {
         Response::new(e.status).body(e.message)
@@ -112,6 +564,63 @@ impl<'a> From<HttpError> for Response<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use monoio::BufResult;
+    use monoio::buf::{IoBuf, IoVecBuf};
+    use std::collections::HashMap;
+    use std::future::{self, Future};
+    use std::io;
+    use std::pin::pin;
+    use std::str;
+    use std::task::{Context, Poll, Waker};
+
+    #[derive(Default)]
+    struct MemoryWriter {
+        written: Vec<u8>,
+        writes: usize,
+        fail: bool,
+    }
+
+    impl AsyncWriteRent for MemoryWriter {
+        fn write<T: IoBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+            if self.fail {
+                return future::ready((Err(io::ErrorKind::BrokenPipe.into()), buf));
+            }
+
+            let bytes: &[u8] = unsafe { std::slice::from_raw_parts(buf.read_ptr(), buf.bytes_init()) };
+            self.written.extend_from_slice(bytes);
+            self.writes += 1;
+            future::ready((Ok(bytes.len()), buf))
+        }
+
+        fn writev<T: IoVecBuf>(&mut self, buf_vec: T) -> impl Future<Output = BufResult<usize, T>> {
+            future::ready((Err(io::ErrorKind::Unsupported.into()), buf_vec))
+        }
+
+        fn flush(&mut self) -> impl Future<Output = io::Result<()>> {
+            future::ready(Ok(()))
+        }
+
+        fn shutdown(&mut self) -> impl Future<Output = io::Result<()>> {
+            future::ready(Ok(()))
+        }
+    }
+
+    fn send_to(response: Response, writer: &mut MemoryWriter) -> Result<usize, HttpError> {
+        send_buffered_to(response, writer, Response::DEFAULT_MAX_WRITE_BUFFER)
+    }
+
+    fn send_buffered_to(
+        response: Response,
+        writer: &mut MemoryWriter,
+        max_write_buffer: usize,
+    ) -> Result<usize, HttpError> {
+        let mut cx: Context = Context::from_waker(Waker::noop());
+
+        match pin!(response.send_buffered(writer, max_write_buffer)).poll(&mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => panic!("In-memory writes should complete immediately"),
+        }
+    }
 
     #[test]
     fn test_response_into_response() {
@@ -119,7 +628,7 @@ mod tests {
         let result: Response = response.into_response();
 
         assert_eq!(result.status, HttpStatus::Ok);
-        assert_eq!(result.body.unwrap(), "TEXT");
+        assert_eq!(result.body.unwrap(), "TEXT".as_bytes());
     }
 
     #[test]
@@ -128,7 +637,20 @@ mod tests {
         let response: Response = error.into();
 
         assert_eq!(response.status, HttpStatus::NotFound);
-        assert_eq!(response.body.unwrap(), "NOT_FOUND");
+        assert_eq!(response.body.unwrap(), "NOT_FOUND".as_bytes());
+    }
+
+    #[test]
+    fn test_error_response_uses_json_envelope() {
+        let response: Response = HttpError::new(HttpStatus::NotFound, "No such user").into_json_response();
+        let body: serde_json::Value = serde_json::from_slice(response.get_body().unwrap()).unwrap();
+
+        assert_eq!(response.status(), HttpStatus::NotFound);
+        assert_eq!(response.get_header("content-type"), Some("application/json"));
+        assert_eq!(
+            body,
+            serde_json::json!({ "error": { "status": 404, "message": "No such user" } })
+        );
     }
 
     #[test]
@@ -137,7 +659,16 @@ mod tests {
         let response: Response = Response::new(HttpStatus::Ok).json(&user);
 
         assert_eq!(response.status, HttpStatus::Ok);
-        assert_eq!(response.body.unwrap(), r#"{"age":18,"name":"John Doe"}"#);
+        assert_eq!(response.body.unwrap(), r#"{"age":18,"name":"John Doe"}"#.as_bytes());
+    }
+
+    #[test]
+    fn test_json_raw_is_sent_verbatim() {
+        let cached: String = r#"{"id": 7, "tags": ["a"]}"#.into();
+        let response: Response = Response::new(HttpStatus::Ok).json_raw(cached);
+
+        assert_eq!(response.get_header("content-type"), Some("application/json"));
+        assert_eq!(response.body.unwrap(), r#"{"id": 7, "tags": ["a"]}"#.as_bytes());
     }
 
     #[test]
@@ -152,10 +683,387 @@ mod tests {
 
         let success: Response = mock_success_handler();
         assert_eq!(success.status, HttpStatus::Ok);
-        assert_eq!(success.body.unwrap(), "SUCCESS");
+        assert_eq!(success.body.unwrap(), "SUCCESS".as_bytes());
 
         let error_response: Response = mock_error_handler_converted();
         assert_eq!(error_response.status, HttpStatus::Unauthorized);
-        assert_eq!(error_response.body.unwrap(), "UNAUTHORIZED");
+        assert_eq!(error_response.body.unwrap(), "UNAUTHORIZED".as_bytes());
+    }
+
+    #[test]
+    fn test_headers_extends_in_order() {
+        let shared: [(&str, &str); 2] = [("X-Frame-Options", "DENY"), ("X-Content-Type-Options", "nosniff")];
+
+        let response: Response = Response::new(HttpStatus::Ok)
+            .header("X-Request-Id", "abc")
+            .headers(shared)
+            .headers(vec![(String::from("Cache-Control"), String::from("no-store"))]);
+
+        let keys: Vec<&str> = response.headers.iter().map(|(k, _)| k.as_ref()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "X-Request-Id",
+                "X-Frame-Options",
+                "X-Content-Type-Options",
+                "Cache-Control"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_header_case_insensitive() {
+        let response: Response = Response::new(HttpStatus::Ok).text("TEXT");
+
+        assert_eq!(response.get_header("content-type"), Some("text/plain"));
+        assert_eq!(response.get_header("CONTENT-TYPE"), Some("text/plain"));
+        assert_eq!(response.get_header("x-missing"), None);
+    }
+
+    #[test]
+    fn test_status_line_echoes_version() {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        Response::new(HttpStatus::Ok)
+            .version(HttpVersion::Http10)
+            .write_head_to_buffer(&mut buffer)
+            .unwrap();
+
+        assert!(buffer.starts_with(b"HTTP/1.0 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_json_value_into_response() {
+        let response: Response = serde_json::json!({ "ok": true }).into_response();
+
+        assert_eq!(response.status, HttpStatus::Ok);
+        assert_eq!(response.get_header("content-type"), Some("application/json"));
+        assert_eq!(response.body.unwrap(), r#"{"ok":true}"#.as_bytes());
+    }
+
+    #[test]
+    fn test_json_wrapper_into_response() {
+        let user: HashMap<&str, u32> = HashMap::from([("id", 7)]);
+
+        let response: Response = Json(user).into_response();
+        assert_eq!(response.status, HttpStatus::Ok);
+        assert_eq!(response.body.unwrap(), r#"{"id":7}"#.as_bytes());
+
+        let invalid: HashMap<(u8, u8), u8> = HashMap::from([((1, 2), 3)]);
+        let response: Response = Json(invalid).into_response();
+        assert_eq!(response.status, HttpStatus::InternalServerError);
+        assert!(response.body.unwrap().starts_with(b"JSON Serialization Failed"));
+    }
+
+    #[test]
+    fn test_no_content_and_not_modified_omit_body() {
+        for response in [Response::no_content().text("ignored"), Response::not_modified()] {
+            let mut buffer: Vec<u8> = Vec::new();
+            response.write_head_to_buffer(&mut buffer).unwrap();
+
+            let head: &str = str::from_utf8(&buffer).unwrap();
+            assert!(!head.contains("Content-Length"));
+            assert!(head.ends_with("\r\n\r\n"));
+        }
+    }
+
+    #[test]
+    fn test_created_sets_location() {
+        let response: Response = Response::created("/users/7");
+
+        assert_eq!(response.status, HttpStatus::Created);
+        assert_eq!(response.get_header("Location"), Some("/users/7"));
+    }
+
+    #[test]
+    fn test_if_none_match_short_circuits_to_not_modified() {
+        let tag: String = etag::from_bytes(b"{\"id\":7}");
+
+        let response: Response = Response::new(HttpStatus::Ok)
+            .json(serde_json::json!({ "id": 7 }))
+            .etag(tag.clone())
+            .header("Cache-Control", "max-age=60")
+            .if_none_match(&format!("W/{tag}"));
+
+        assert_eq!(response.status, HttpStatus::NotModified);
+        assert!(response.body.is_none());
+        assert_eq!(response.get_header("ETag"), Some(tag.as_str()));
+        assert_eq!(response.get_header("Cache-Control"), Some("max-age=60"));
+        assert_eq!(response.get_header("Content-Type"), None);
+    }
+
+    #[test]
+    fn test_if_none_match_mismatch_keeps_response() {
+        let response: Response = Response::new(HttpStatus::Ok)
+            .text("body")
+            .etag("\"v2\"")
+            .if_none_match("\"v1\"");
+
+        assert_eq!(response.status, HttpStatus::Ok);
+        assert_eq!(response.body.unwrap(), "body".as_bytes());
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let modified: SystemTime = SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(784_111_777_500);
+
+        let unmodified: Response = Response::new(HttpStatus::Ok)
+            .text("body")
+            .last_modified(modified)
+            .if_modified_since("Sun, 06 Nov 1994 08:49:37 GMT");
+
+        assert_eq!(unmodified.status, HttpStatus::NotModified);
+        assert!(unmodified.body.is_none());
+        assert_eq!(unmodified.get_header("Last-Modified"), Some("Sun, 06 Nov 1994 08:49:37 GMT"));
+
+        let modified: Response = Response::new(HttpStatus::Ok)
+            .text("body")
+            .last_modified(modified)
+            .if_modified_since("Sun, 06 Nov 1994 08:49:36 GMT");
+
+        assert_eq!(modified.status, HttpStatus::Ok);
+        assert_eq!(modified.body.unwrap(), "body".as_bytes());
+
+        let invalid: Response = Response::new(HttpStatus::Ok)
+            .last_modified(SystemTime::UNIX_EPOCH)
+            .if_modified_since("yesterday");
+
+        assert_eq!(invalid.status, HttpStatus::Ok);
+    }
+
+    #[test]
+    fn test_range_returns_partial_content() {
+        let response: Response = Response::new(HttpStatus::Ok)
+            .text("Hello, Forge!")
+            .accept_ranges()
+            .range("bytes=7-");
+
+        assert_eq!(response.status, HttpStatus::PartialContent);
+        assert_eq!(response.get_header("Content-Range"), Some("bytes 7-12/13"));
+        assert_eq!(response.body.unwrap(), "Forge!".as_bytes());
+    }
+
+    #[test]
+    fn test_range_not_satisfiable() {
+        let response: Response = Response::new(HttpStatus::Ok)
+            .bytes(vec![0u8; 10])
+            .accept_ranges()
+            .range("bytes=10-20");
+
+        assert_eq!(response.status, HttpStatus::RangeNotSatisfiable);
+        assert_eq!(response.get_header("Content-Range"), Some("bytes */10"));
+        assert!(response.body.is_none());
+    }
+
+    #[test]
+    fn test_multiple_ranges_are_not_satisfiable() {
+        let response: Response = Response::new(HttpStatus::Ok)
+            .bytes(vec![0u8; 10])
+            .accept_ranges()
+            .range("bytes=0-1, 4-5");
+
+        assert_eq!(response.status, HttpStatus::RangeNotSatisfiable);
+        assert_eq!(response.get_header("Content-Range"), Some("bytes */10"));
+        assert!(response.body.is_none());
+    }
+
+    #[test]
+    fn test_range_ignored_without_accept_ranges() {
+        let response: Response = Response::new(HttpStatus::Ok).text("Hello").range("bytes=0-1");
+
+        assert_eq!(response.status, HttpStatus::Ok);
+        assert_eq!(response.body.unwrap(), "Hello".as_bytes());
+    }
+
+    #[test]
+    fn test_default_headers_do_not_override_handler_headers() {
+        let response: Response = Response::new(HttpStatus::Ok)
+            .header("x-powered-by", "handler")
+            .default_headers([("X-Powered-By", "Forge"), ("X-Deployment-Id", "42")]);
+
+        assert_eq!(response.get_header("X-Powered-By"), Some("handler"));
+        assert_eq!(response.get_header("X-Deployment-Id"), Some("42"));
+        assert_eq!(response.headers.len(), 2);
+    }
+
+    #[test]
+    fn test_default_content_type_only_labels_bare_bodies() {
+        let bare: Response = Response::new(HttpStatus::Ok)
+            .body("{}")
+            .default_content_type("application/json");
+        assert_eq!(bare.get_header("Content-Type"), Some("application/json"));
+
+        let text: Response = Response::new(HttpStatus::Ok)
+            .text("hi")
+            .default_content_type("application/json");
+        assert_eq!(text.get_header("Content-Type"), Some("text/plain"));
+
+        let empty: Response = Response::no_content().default_content_type("application/json");
+        assert_eq!(empty.get_header("Content-Type"), None);
+    }
+
+    #[test]
+    fn test_send_writes_to_in_memory_writer() {
+        let mut writer: MemoryWriter = MemoryWriter::default();
+        send_to(Response::new(HttpStatus::Ok).text("Hello"), &mut writer).unwrap();
+
+        assert_eq!(
+            str::from_utf8(&writer.written).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nHello"
+        );
+    }
+
+    #[test]
+    fn test_send_writes_large_bodies_after_the_head() {
+        let head: &str = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n";
+
+        let mut inline: MemoryWriter = MemoryWriter::default();
+        let sent: usize =
+            send_buffered_to(Response::new(HttpStatus::Ok).bytes(&b"0123456789"[..]), &mut inline, 10).unwrap();
+        assert_eq!(inline.writes, 1);
+        assert_eq!(sent, head.len() + 10);
+
+        let mut borrowed: MemoryWriter = MemoryWriter::default();
+        send_buffered_to(Response::new(HttpStatus::Ok).bytes(&b"0123456789"[..]), &mut borrowed, 4).unwrap();
+        assert_eq!(borrowed.writes, 4);
+        assert_eq!(borrowed.written, inline.written);
+
+        let mut owned: MemoryWriter = MemoryWriter::default();
+        let sent: usize =
+            send_buffered_to(Response::new(HttpStatus::Ok).bytes(b"0123456789".to_vec()), &mut owned, 4).unwrap();
+        assert_eq!(owned.writes, 2);
+        assert_eq!(sent, owned.written.len());
+        assert_eq!(str::from_utf8(&owned.written).unwrap(), format!("{head}0123456789"));
+    }
+
+    #[test]
+    fn test_send_maps_write_errors() {
+        let mut writer: MemoryWriter = MemoryWriter {
+            fail: true,
+            ..MemoryWriter::default()
+        };
+
+        let error: HttpError = send_to(Response::new(HttpStatus::Ok), &mut writer).unwrap_err();
+
+        assert_eq!(error.status, HttpStatus::InternalServerError);
+        assert_eq!(error.message, "Failed to write response");
+    }
+
+    #[test]
+    fn test_precompressed_negotiates_gzip() {
+        let gzip: Response = Response::new(HttpStatus::Ok)
+            .precompressed(&b"plain"[..], &b"gzipped"[..])
+            .accept_encoding("br, gzip");
+
+        assert_eq!(gzip.get_body(), Some(&b"gzipped"[..]));
+        assert_eq!(gzip.get_header("content-encoding"), Some("gzip"));
+        assert_eq!(gzip.get_header("vary"), Some("Accept-Encoding"));
+
+        let plain: Response = Response::new(HttpStatus::Ok)
+            .precompressed(&b"plain"[..], &b"gzipped"[..])
+            .accept_encoding("gzip;q=0");
+
+        assert_eq!(plain.get_body(), Some(&b"plain"[..]));
+        assert_eq!(plain.get_header("content-encoding"), None);
+        assert_eq!(plain.get_header("vary"), Some("Accept-Encoding"));
+    }
+
+    #[test]
+    fn test_precompressed_without_accept_encoding_sends_plain() {
+        let mut writer: MemoryWriter = MemoryWriter::default();
+        let response: Response = Response::new(HttpStatus::Ok).precompressed(&b"plain"[..], &b"gzipped"[..]);
+
+        send_to(response, &mut writer).unwrap();
+
+        let written: &str = str::from_utf8(&writer.written).unwrap();
+        assert!(written.ends_with("Content-Length: 5\r\n\r\nplain"));
+        assert!(!written.contains("Content-Encoding"));
+    }
+
+    #[test]
+    fn test_vary_merges_and_deduplicates() {
+        let response: Response = Response::new(HttpStatus::Ok)
+            .vary("Accept")
+            .precompressed(&b"plain"[..], &b"gzipped"[..])
+            .vary("accept-encoding")
+            .vary("Origin");
+
+        assert_eq!(response.get_header("vary"), Some("Accept, Accept-Encoding, Origin"));
+        assert_eq!(response.headers.iter().filter(|(key, _)| key == "Vary").count(), 1);
+
+        let wildcard: Response = Response::new(HttpStatus::Ok).vary("Accept").vary("*").vary("Origin");
+        assert_eq!(wildcard.get_header("vary"), Some("*"));
+    }
+
+    #[test]
+    fn test_body_with_is_built_on_send() {
+        let mut writer: MemoryWriter = MemoryWriter::default();
+        let response: Response = Response::new(HttpStatus::Ok).body_with(|| "Hello".repeat(2));
+
+        assert_eq!(response.get_body(), None);
+        send_to(response, &mut writer).unwrap();
+
+        assert!(
+            str::from_utf8(&writer.written)
+                .unwrap()
+                .ends_with("Content-Length: 10\r\n\r\nHelloHello")
+        );
+    }
+
+    #[test]
+    fn test_body_with_skipped_when_not_modified() {
+        let mut writer: MemoryWriter = MemoryWriter::default();
+
+        let response: Response = Response::new(HttpStatus::Ok)
+            .etag("\"v1\"")
+            .body_with(|| -> String { panic!("Deferred body should not be built for a 304") })
+            .if_none_match("\"v1\"");
+
+        assert_eq!(response.status, HttpStatus::NotModified);
+        send_to(response, &mut writer).unwrap();
+        assert!(writer.written.starts_with(b"HTTP/1.1 304 Not Modified\r\n"));
+    }
+
+    #[test]
+    fn test_body_with_supports_ranges_and_eager_override() {
+        let partial: Response = Response::new(HttpStatus::Ok)
+            .body_with(|| "Hello, Forge!")
+            .accept_ranges()
+            .range("bytes=0-4");
+
+        assert_eq!(partial.get_body(), Some(&b"Hello"[..]));
+
+        let eager: Response = Response::new(HttpStatus::Ok).body_with(|| "deferred").text("eager");
+        assert!(eager.deferred_body.is_none());
+        assert_eq!(eager.get_body(), Some(&b"eager"[..]));
+    }
+
+    #[test]
+    fn test_into_owned_outlives_borrowed_parts() {
+        let owned: Response<'static> = {
+            let body: String = String::from("cached body");
+            let header: String = String::from("max-age=60");
+
+            Response::new(HttpStatus::Ok)
+                .header("Cache-Control", header.as_str())
+                .text(body.as_str())
+                .into_owned()
+        };
+
+        assert_eq!(owned.get_header("cache-control"), Some("max-age=60"));
+        assert_eq!(owned.get_body(), Some(&b"cached body"[..]));
+
+        let replay: Response = owned.try_clone().unwrap();
+        assert_eq!(replay.status(), owned.status());
+        assert_eq!(replay.get_body(), owned.get_body());
+    }
+
+    #[test]
+    fn test_try_clone_skips_unbuilt_deferred_body() {
+        let deferred: Response = Response::new(HttpStatus::Ok).body_with(|| "late");
+        assert!(deferred.try_clone().is_none());
+
+        let owned: Response = deferred.into_owned();
+        assert_eq!(owned.try_clone().unwrap().get_body(), Some(&b"late"[..]));
     }
 }
@@ -0,0 +1,260 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Formatter};
+use std::future;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use super::{HttpError, HttpStatus, Response};
+
+#[derive(Default)]
+struct Shared {
+    head: Option<Response<'static>>,
+    status: Option<HttpStatus>,
+    chunks: VecDeque<Vec<u8>>,
+    finished: bool,
+    ended: bool,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Shared {
+    fn is_drained(&self) -> bool {
+        self.head.is_none() && self.chunks.is_empty() && (!self.finished || self.ended)
+    }
+}
+
+/// Writes a response in pieces, returned by `Request::response_writer` on routes registered with
+/// `stream_response = true`. `write_head` sends the status and headers right away, then each
+/// `write_body_chunk` goes out as it is written (as a chunk of a chunked body, or as is to HTTP/1.0
+/// clients, whose connection is then closed to end it), and `finish` ends the body.
+///
+/// Every write waits until the connection has sent it, so a handler never gets ahead of a slow
+/// client. Once the head is written, the `Response` the handler returns is dropped; a handler that
+/// never calls `write_head` is answered with its returned `Response` as usual. Returning without
+/// `finish` ends the body too, while a handler that panics or times out after the head went out
+/// has its connection closed, so the client can tell the body is incomplete.
+pub struct ResponseWriter {
+    shared: Rc<RefCell<Shared>>,
+}
+
+/// The connection's end of a `ResponseWriter`.
+pub struct ResponseSink {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl ResponseWriter {
+    pub fn channel() -> (ResponseSink, ResponseWriter) {
+        let shared: Rc<RefCell<Shared>> = Rc::default();
+
+        let sink: ResponseSink = ResponseSink {
+            shared: Rc::clone(&shared),
+        };
+
+        (sink, ResponseWriter { shared })
+    }
+
+    pub fn is_committed(&self) -> bool {
+        self.shared.borrow().status.is_some()
+    }
+
+    /// Sends the status line and `headers`. `Content-Length` and `Transfer-Encoding` are set by
+    /// the connection, and the listener's default and security headers are added as for any other
+    /// response. Fails if the head was already written.
+    pub async fn write_head<I, K, V>(&mut self, status: HttpStatus, headers: I) -> Result<(), HttpError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<Cow<'static, str>>,
+        V: Into<Cow<'static, str>>,
+    {
+        {
+            let mut shared = self.shared.borrow_mut();
+
+            if shared.status.is_some() {
+                return Err(Self::misuse("Response head was already written"));
+            }
+
+            shared.status = Some(status);
+            shared.head = Some(Response::new(status).headers(headers));
+        }
+
+        self.sent().await
+    }
+
+    pub async fn write_body_chunk<B>(&mut self, chunk: B) -> Result<(), HttpError>
+    where
+        B: Into<Vec<u8>>,
+    {
+        {
+            let mut shared = self.shared.borrow_mut();
+
+            if shared.status.is_none() {
+                return Err(Self::misuse("Response head must be written before the body"));
+            }
+
+            let chunk: Vec<u8> = chunk.into();
+
+            if !chunk.is_empty() {
+                shared.chunks.push_back(chunk);
+            }
+        }
+
+        self.sent().await
+    }
+
+    pub async fn finish(mut self) -> Result<(), HttpError> {
+        {
+            let mut shared = self.shared.borrow_mut();
+
+            if shared.status.is_none() {
+                return Err(Self::misuse("Response head must be written before the body"));
+            }
+
+            shared.finished = true;
+        }
+
+        self.sent().await
+    }
+
+    async fn sent(&mut self) -> Result<(), HttpError> {
+        future::poll_fn(|cx: &mut Context| {
+            let mut shared = self.shared.borrow_mut();
+
+            if shared.closed {
+                return Poll::Ready(Err(HttpError::new(
+                    HttpStatus::InternalServerError,
+                    "Connection closed while the response was being written",
+                )));
+            }
+
+            if shared.is_drained() {
+                return Poll::Ready(Ok(()));
+            }
+
+            shared.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn misuse(message: &str) -> HttpError {
+        HttpError::new(HttpStatus::InternalServerError, message)
+    }
+}
+
+impl Debug for ResponseWriter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let shared = self.shared.borrow();
+        f.debug_struct("ResponseWriter")
+            .field("status", &shared.status)
+            .field("finished", &shared.finished)
+            .finish()
+    }
+}
+
+impl ResponseSink {
+    /// The status passed to `write_head`, once it has been called.
+    pub fn status(&self) -> Option<HttpStatus> {
+        self.shared.borrow().status
+    }
+
+    /// Whether the writer is waiting on something the connection has not sent yet.
+    pub fn has_output(&self) -> bool {
+        !self.shared.borrow().is_drained()
+    }
+
+    pub fn take_head(&self) -> Option<Response<'static>> {
+        self.shared.borrow_mut().head.take()
+    }
+
+    pub fn pop_chunk(&self) -> Option<Vec<u8>> {
+        self.shared.borrow_mut().chunks.pop_front()
+    }
+
+    /// Whether the body has to be ended: after `finish`, or once the handler returned.
+    pub fn wants_end(&self) -> bool {
+        let shared = self.shared.borrow();
+        shared.finished && !shared.ended
+    }
+
+    /// Asks for the body to be ended, once the handler returned without calling `finish`.
+    pub fn finish(&self) {
+        self.shared.borrow_mut().finished = true;
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.shared.borrow().ended
+    }
+
+    /// Records that the end of the body was sent.
+    pub fn end(&self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.finished = true;
+        shared.ended = true;
+    }
+
+    /// Wakes the writer once everything it handed over has been sent.
+    pub fn sent(&self) {
+        let waker: Option<Waker> = self.shared.borrow_mut().waker.take();
+        waker.into_iter().for_each(Waker::wake);
+    }
+
+    /// Fails the writer's pending and future writes, after the connection could not send them.
+    pub fn close(&self) {
+        self.shared.borrow_mut().closed = true;
+        self.sent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::pin;
+
+    fn poll<F: Future>(future: F) -> Poll<F::Output> {
+        pin!(future).poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_writes_wait_until_sent() {
+        let (sink, mut writer): (ResponseSink, ResponseWriter) = ResponseWriter::channel();
+        assert!(!sink.has_output());
+
+        {
+            let mut cx: Context = Context::from_waker(Waker::noop());
+            let mut head = pin!(writer.write_head(HttpStatus::Ok, [("Content-Type", "text/plain")]));
+            assert!(head.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(sink.status(), Some(HttpStatus::Ok));
+            assert!(sink.take_head().is_some());
+            assert!(!sink.has_output());
+            assert!(matches!(head.poll(&mut cx), Poll::Ready(Ok(()))));
+        }
+
+        assert!(poll(writer.write_body_chunk("hello")).is_pending());
+        assert_eq!(sink.pop_chunk(), Some(b"hello".to_vec()));
+        assert_eq!(sink.pop_chunk(), None);
+
+        assert!(poll(writer.finish()).is_pending());
+        assert!(sink.wants_end());
+        sink.end();
+        assert!(!sink.has_output());
+    }
+
+    #[test]
+    fn test_misuse_and_closed_connection_fail_writes() {
+        let (sink, mut writer): (ResponseSink, ResponseWriter) = ResponseWriter::channel();
+
+        let Poll::Ready(Err(e)) = poll(writer.write_body_chunk("early")) else {
+            panic!("Body before head should fail");
+        };
+        assert_eq!(e.status, HttpStatus::InternalServerError);
+
+        assert!(poll(writer.write_head(HttpStatus::Ok, Vec::<(String, String)>::new())).is_pending());
+        sink.take_head();
+        assert!(poll(writer.write_head(HttpStatus::Ok, Vec::<(String, String)>::new())).is_ready());
+
+        sink.close();
+        assert!(matches!(poll(writer.write_body_chunk("late")), Poll::Ready(Err(_))));
+    }
+}
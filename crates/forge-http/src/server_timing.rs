@@ -0,0 +1,57 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+type Metric = (Cow<'static, str>, Duration);
+
+#[derive(Debug, Clone, Default)]
+pub struct ServerTiming {
+    metrics: Arc<Mutex<Vec<Metric>>>,
+}
+
+impl ServerTiming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record<T>(&self, name: T, duration: Duration)
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        self.metrics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((name.into(), duration));
+    }
+
+    pub fn header_value(&self) -> String {
+        let metrics: MutexGuard<Vec<Metric>> = self.metrics.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut value: String = String::new();
+
+        for (idx, (name, duration)) in metrics.iter().enumerate() {
+            if idx > 0 {
+                value.push_str(", ");
+            }
+
+            let _ = write!(value, "{name};dur={:.3}", duration.as_secs_f64() * 1000.0);
+        }
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_value_lists_metrics_in_order() {
+        let timing: ServerTiming = ServerTiming::new();
+        timing.record("parse", Duration::from_micros(250));
+        timing.clone().record(String::from("db"), Duration::from_millis(12));
+
+        assert_eq!(timing.header_value(), "parse;dur=0.250, db;dur=12.000");
+        assert_eq!(ServerTiming::new().header_value(), "");
+    }
+}
@@ -75,84 +75,147 @@ impl From<HttpStatus> for u16 {
     }
 }
 
-impl TryFrom<u16> for HttpStatus {
-    type Error = HttpError;
+impl HttpStatus {
+    const ALL: [HttpStatus; 62] = [
+        HttpStatus::Continue,
+        HttpStatus::SwitchingProtocols,
+        HttpStatus::Processing,
+        HttpStatus::EarlyHints,
+        HttpStatus::Ok,
+        HttpStatus::Created,
+        HttpStatus::Accepted,
+        HttpStatus::NonAuthoritativeInformation,
+        HttpStatus::NoContent,
+        HttpStatus::ResetContent,
+        HttpStatus::PartialContent,
+        HttpStatus::MultiStatus,
+        HttpStatus::AlreadyReported,
+        HttpStatus::ImUsed,
+        HttpStatus::MultipleChoices,
+        HttpStatus::MovedPermanently,
+        HttpStatus::Found,
+        HttpStatus::SeeOther,
+        HttpStatus::NotModified,
+        HttpStatus::UseProxy,
+        HttpStatus::TemporaryRedirect,
+        HttpStatus::PermanentRedirect,
+        HttpStatus::BadRequest,
+        HttpStatus::Unauthorized,
+        HttpStatus::PaymentRequired,
+        HttpStatus::Forbidden,
+        HttpStatus::NotFound,
+        HttpStatus::MethodNotAllowed,
+        HttpStatus::NotAcceptable,
+        HttpStatus::ProxyAuthenticationRequired,
+        HttpStatus::RequestTimeout,
+        HttpStatus::Conflict,
+        HttpStatus::Gone,
+        HttpStatus::LengthRequired,
+        HttpStatus::PreconditionFailed,
+        HttpStatus::PayloadTooLarge,
+        HttpStatus::UriTooLong,
+        HttpStatus::UnsupportedMediaType,
+        HttpStatus::RangeNotSatisfiable,
+        HttpStatus::ExpectationFailed,
+        HttpStatus::ImATeapot,
+        HttpStatus::MisdirectedRequest,
+        HttpStatus::UnprocessableEntity,
+        HttpStatus::Locked,
+        HttpStatus::FailedDependency,
+        HttpStatus::TooEarly,
+        HttpStatus::UpgradeRequired,
+        HttpStatus::PreconditionRequired,
+        HttpStatus::TooManyRequests,
+        HttpStatus::RequestHeaderFieldsTooLarge,
+        HttpStatus::UnavailableForLegalReasons,
+        HttpStatus::InternalServerError,
+        HttpStatus::NotImplemented,
+        HttpStatus::BadGateway,
+        HttpStatus::ServiceUnavailable,
+        HttpStatus::GatewayTimeout,
+        HttpStatus::HttpVersionNotSupported,
+        HttpStatus::VariantAlsoNegotiates,
+        HttpStatus::InsufficientStorage,
+        HttpStatus::LoopDetected,
+        HttpStatus::NotExtended,
+        HttpStatus::NetworkAuthenticationRequired,
+    ];
 
-    fn try_from(v: u16) -> Result<Self, Self::Error> {
-        match v {
-            100 => Ok(Self::Continue),
-            101 => Ok(Self::SwitchingProtocols),
-            102 => Ok(Self::Processing),
-            103 => Ok(Self::EarlyHints),
-            200 => Ok(Self::Ok),
-            201 => Ok(Self::Created),
-            202 => Ok(Self::Accepted),
-            203 => Ok(Self::NonAuthoritativeInformation),
-            204 => Ok(Self::NoContent),
-            205 => Ok(Self::ResetContent),
-            206 => Ok(Self::PartialContent),
-            207 => Ok(Self::MultiStatus),
-            208 => Ok(Self::AlreadyReported),
-            226 => Ok(Self::ImUsed),
-            300 => Ok(Self::MultipleChoices),
-            301 => Ok(Self::MovedPermanently),
-            302 => Ok(Self::Found),
-            303 => Ok(Self::SeeOther),
-            304 => Ok(Self::NotModified),
-            305 => Ok(Self::UseProxy),
-            307 => Ok(Self::TemporaryRedirect),
-            308 => Ok(Self::PermanentRedirect),
-            400 => Ok(Self::BadRequest),
-            401 => Ok(Self::Unauthorized),
-            402 => Ok(Self::PaymentRequired),
-            403 => Ok(Self::Forbidden),
-            404 => Ok(Self::NotFound),
-            405 => Ok(Self::MethodNotAllowed),
-            406 => Ok(Self::NotAcceptable),
-            407 => Ok(Self::ProxyAuthenticationRequired),
-            408 => Ok(Self::RequestTimeout),
-            409 => Ok(Self::Conflict),
-            410 => Ok(Self::Gone),
-            411 => Ok(Self::LengthRequired),
-            412 => Ok(Self::PreconditionFailed),
-            413 => Ok(Self::PayloadTooLarge),
-            414 => Ok(Self::UriTooLong),
-            415 => Ok(Self::UnsupportedMediaType),
-            416 => Ok(Self::RangeNotSatisfiable),
-            417 => Ok(Self::ExpectationFailed),
-            418 => Ok(Self::ImATeapot),
-            421 => Ok(Self::MisdirectedRequest),
-            422 => Ok(Self::UnprocessableEntity),
-            423 => Ok(Self::Locked),
-            424 => Ok(Self::FailedDependency),
-            425 => Ok(Self::TooEarly),
-            426 => Ok(Self::UpgradeRequired),
-            428 => Ok(Self::PreconditionRequired),
-            429 => Ok(Self::TooManyRequests),
-            431 => Ok(Self::RequestHeaderFieldsTooLarge),
-            451 => Ok(Self::UnavailableForLegalReasons),
-            500 => Ok(Self::InternalServerError),
-            501 => Ok(Self::NotImplemented),
-            502 => Ok(Self::BadGateway),
-            503 => Ok(Self::ServiceUnavailable),
-            504 => Ok(Self::GatewayTimeout),
-            505 => Ok(Self::HttpVersionNotSupported),
-            506 => Ok(Self::VariantAlsoNegotiates),
-            507 => Ok(Self::InsufficientStorage),
-            508 => Ok(Self::LoopDetected),
-            510 => Ok(Self::NotExtended),
-            511 => Ok(Self::NetworkAuthenticationRequired),
-            _ => Err(HttpError::new(
-                HttpStatus::InternalServerError,
-                format!("Unknown or non-standard HTTP status code: {v}"),
-            )),
+    /// Every status code Forge knows about, in ascending order.
+    pub fn all() -> impl Iterator<Item = HttpStatus> {
+        Self::ALL.into_iter()
+    }
+
+    pub const fn from_u16(code: u16) -> Option<Self> {
+        match code {
+            100 => Some(Self::Continue),
+            101 => Some(Self::SwitchingProtocols),
+            102 => Some(Self::Processing),
+            103 => Some(Self::EarlyHints),
+            200 => Some(Self::Ok),
+            201 => Some(Self::Created),
+            202 => Some(Self::Accepted),
+            203 => Some(Self::NonAuthoritativeInformation),
+            204 => Some(Self::NoContent),
+            205 => Some(Self::ResetContent),
+            206 => Some(Self::PartialContent),
+            207 => Some(Self::MultiStatus),
+            208 => Some(Self::AlreadyReported),
+            226 => Some(Self::ImUsed),
+            300 => Some(Self::MultipleChoices),
+            301 => Some(Self::MovedPermanently),
+            302 => Some(Self::Found),
+            303 => Some(Self::SeeOther),
+            304 => Some(Self::NotModified),
+            305 => Some(Self::UseProxy),
+            307 => Some(Self::TemporaryRedirect),
+            308 => Some(Self::PermanentRedirect),
+            400 => Some(Self::BadRequest),
+            401 => Some(Self::Unauthorized),
+            402 => Some(Self::PaymentRequired),
+            403 => Some(Self::Forbidden),
+            404 => Some(Self::NotFound),
+            405 => Some(Self::MethodNotAllowed),
+            406 => Some(Self::NotAcceptable),
+            407 => Some(Self::ProxyAuthenticationRequired),
+            408 => Some(Self::RequestTimeout),
+            409 => Some(Self::Conflict),
+            410 => Some(Self::Gone),
+            411 => Some(Self::LengthRequired),
+            412 => Some(Self::PreconditionFailed),
+            413 => Some(Self::PayloadTooLarge),
+            414 => Some(Self::UriTooLong),
+            415 => Some(Self::UnsupportedMediaType),
+            416 => Some(Self::RangeNotSatisfiable),
+            417 => Some(Self::ExpectationFailed),
+            418 => Some(Self::ImATeapot),
+            421 => Some(Self::MisdirectedRequest),
+            422 => Some(Self::UnprocessableEntity),
+            423 => Some(Self::Locked),
+            424 => Some(Self::FailedDependency),
+            425 => Some(Self::TooEarly),
+            426 => Some(Self::UpgradeRequired),
+            428 => Some(Self::PreconditionRequired),
+            429 => Some(Self::TooManyRequests),
+            431 => Some(Self::RequestHeaderFieldsTooLarge),
+            451 => Some(Self::UnavailableForLegalReasons),
+            500 => Some(Self::InternalServerError),
+            501 => Some(Self::NotImplemented),
+            502 => Some(Self::BadGateway),
+            503 => Some(Self::ServiceUnavailable),
+            504 => Some(Self::GatewayTimeout),
+            505 => Some(Self::HttpVersionNotSupported),
+            506 => Some(Self::VariantAlsoNegotiates),
+            507 => Some(Self::InsufficientStorage),
+            508 => Some(Self::LoopDetected),
+            510 => Some(Self::NotExtended),
+            511 => Some(Self::NetworkAuthenticationRequired),
+            _ => None,
         }
     }
-}
 
-impl fmt::Display for HttpStatus {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let msg: &'static str = match self {
+    pub fn canonical_reason(&self) -> &'static str {
+        match self {
             HttpStatus::Continue => "Continue",
             HttpStatus::SwitchingProtocols => "Switching Protocols",
             HttpStatus::Processing => "Processing",
@@ -215,8 +278,50 @@ impl fmt::Display for HttpStatus {
             HttpStatus::LoopDetected => "Loop Detected",
             HttpStatus::NotExtended => "Not Extended",
             HttpStatus::NetworkAuthenticationRequired => "Network Authentication Required",
-        };
+        }
+    }
+}
+
+impl TryFrom<u16> for HttpStatus {
+    type Error = HttpError;
+
+    fn try_from(v: u16) -> Result<Self, Self::Error> {
+        Self::from_u16(v).ok_or_else(|| {
+            HttpError::new(
+                HttpStatus::InternalServerError,
+                format!("Unknown or non-standard HTTP status code: {v}"),
+            )
+        })
+    }
+}
+
+impl fmt::Display for HttpStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.canonical_reason())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_round_trips_through_from_u16() {
+        let statuses: Vec<HttpStatus> = HttpStatus::all().collect();
+
+        assert!(statuses.is_sorted_by_key(|status: &HttpStatus| u16::from(*status)));
+        assert!(
+            statuses
+                .iter()
+                .all(|status: &HttpStatus| HttpStatus::from_u16(u16::from(*status)) == Some(*status))
+        );
+        assert_eq!(HttpStatus::from_u16(299), None);
+        assert!(HttpStatus::try_from(299).is_err());
+    }
 
-        write!(f, "{msg}")
+    #[test]
+    fn test_canonical_reason_matches_display() {
+        assert_eq!(HttpStatus::NotFound.canonical_reason(), "Not Found");
+        assert_eq!(HttpStatus::ImATeapot.to_string(), "I'm a teapot");
     }
 }
@@ -0,0 +1,38 @@
+use core::fmt;
+use std::str;
+
+use super::HttpError;
+use super::HttpStatus;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum HttpVersion {
+    Http10,
+    #[default]
+    Http11,
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg: &'static str = match self {
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+        };
+
+        write!(f, "{msg}")
+    }
+}
+
+impl str::FromStr for HttpVersion {
+    type Err = HttpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HTTP/1.0" => Ok(HttpVersion::Http10),
+            "HTTP/1.1" => Ok(HttpVersion::Http11),
+            _ => Err(HttpError::new(
+                HttpStatus::HttpVersionNotSupported,
+                format!("Unsupported HTTP version: \"{s}\""),
+            )),
+        }
+    }
+}
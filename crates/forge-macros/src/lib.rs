@@ -2,21 +2,41 @@ use proc_macro::TokenStream;
 use proc_macro_crate::{FoundCrate, crate_name};
 use quote::{format_ident, quote};
 use syn::{
-    Error, FnArg, Ident, ItemFn, LitStr, Result, Token, Type,
+    Error, FnArg, Ident, ItemFn, LitBool, LitInt, LitStr, Result, Token, Type, bracketed,
     parse::{Parse, ParseStream},
     parse_macro_input, parse_quote,
+    punctuated::Punctuated,
     spanned::Spanned,
 };
 
 struct RouteArgs {
     path: LitStr,
+    aliases: Vec<LitStr>,
     method: LitStr,
+    host: Option<LitStr>,
+    name: Option<LitStr>,
+    description: Option<LitStr>,
+    tags: Vec<LitStr>,
+    max_body_size: Option<LitInt>,
+    stream_body: Option<LitBool>,
+    stream_response: Option<LitBool>,
+    guard: Option<LitStr>,
+    guard_status: Option<LitInt>,
 }
 
 impl Parse for RouteArgs {
     fn parse(input: ParseStream) -> Result<Self> {
         let mut method: Option<LitStr> = None;
-        let mut path: Option<LitStr> = None;
+        let mut paths: Vec<LitStr> = Vec::new();
+        let mut host: Option<LitStr> = None;
+        let mut name: Option<LitStr> = None;
+        let mut description: Option<LitStr> = None;
+        let mut tags: Vec<LitStr> = Vec::new();
+        let mut max_body_size: Option<LitInt> = None;
+        let mut stream_body: Option<LitBool> = None;
+        let mut stream_response: Option<LitBool> = None;
+        let mut guard: Option<LitStr> = None;
+        let mut guard_status: Option<LitInt> = None;
 
         while !input.is_empty() {
             let key: Ident = input.parse()?;
@@ -24,10 +44,40 @@ impl Parse for RouteArgs {
 
             if key == "method" {
                 method = Some(input.parse()?);
+            } else if key == "path" && input.peek(syn::token::Bracket) {
+                let content: syn::parse::ParseBuffer;
+                bracketed!(content in input);
+                paths.extend(Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?);
             } else if key == "path" {
-                path = Some(input.parse()?);
+                paths.push(input.parse()?);
+            } else if key == "host" {
+                host = Some(input.parse()?);
+            } else if key == "name" {
+                name = Some(input.parse()?);
+            } else if key == "description" {
+                description = Some(input.parse()?);
+            } else if key == "max_body_size" {
+                max_body_size = Some(input.parse()?);
+            } else if key == "stream_body" {
+                stream_body = Some(input.parse()?);
+            } else if key == "stream_response" {
+                stream_response = Some(input.parse()?);
+            } else if key == "guard" {
+                guard = Some(input.parse()?);
+            } else if key == "guard_status" {
+                guard_status = Some(input.parse()?);
+            } else if key == "tags" {
+                let content: syn::parse::ParseBuffer;
+                bracketed!(content in input);
+                tags = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?
+                    .into_iter()
+                    .collect();
             } else {
-                return Err(Error::new(key.span(), "Expected `method` or `path`"));
+                return Err(Error::new(
+                    key.span(),
+                    "Expected `method`, `path`, `host`, `name`, `description`, `tags`, `max_body_size`, `stream_body`, \
+                     `stream_response`, `guard` or `guard_status`",
+                ));
             }
 
             if input.peek(Token![,]) {
@@ -35,9 +85,50 @@ impl Parse for RouteArgs {
             }
         }
 
+        if let (None, Some(guard_status)) = (&guard, &guard_status) {
+            return Err(Error::new(guard_status.span(), "`guard_status` requires a `guard`"));
+        }
+
         let method: LitStr = method.ok_or_else(|| Error::new(input.span(), "Missing `method=\"...\"`"))?;
-        let path: LitStr = path.ok_or_else(|| Error::new(input.span(), "Missing `path=\"...\"`"))?;
-        Ok(Self { method, path })
+        let mut paths: std::vec::IntoIter<LitStr> = paths.into_iter();
+        let path: LitStr = paths
+            .next()
+            .ok_or_else(|| Error::new(input.span(), "Missing `path=\"...\"`"))?;
+
+        Ok(Self {
+            method,
+            path,
+            aliases: paths.collect(),
+            host,
+            name,
+            description,
+            tags,
+            max_body_size,
+            stream_body,
+            stream_response,
+            guard,
+            guard_status,
+        })
+    }
+}
+
+struct MethodRouteArgs {
+    path: LitStr,
+    rest: quote::__private::TokenStream,
+}
+
+impl Parse for MethodRouteArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let path: LitStr = input.parse()?;
+
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+
+        Ok(Self {
+            path,
+            rest: input.parse()?,
+        })
     }
 }
 
@@ -76,12 +167,30 @@ fn is_request_type(ty: &Type) -> bool {
     matches!(last_path_ident(ty), Some(ident) if ident == "Request")
 }
 
-fn extract_arc_inner_ty(ty: &Type) -> Option<Type> {
-    let Type::Path(tp) = ty else { return None };
+fn is_bytes_type(ty: &Type) -> bool {
+    matches!(last_path_ident(ty), Some(ident) if ident == "Bytes")
+}
+
+/// The state type of an `Arc<T>`, `Rc<T>` or `&T` argument, with the kind of state it requires.
+fn extract_state_ty(ty: &Type) -> Option<(Type, StateArg)> {
+    let tp: &syn::TypePath = match ty {
+        Type::Reference(reference)
+            if reference.mutability.is_none()
+                && !is_request_type(&reference.elem)
+                && !is_bytes_type(&reference.elem) =>
+        {
+            return Some(((*reference.elem).clone(), StateArg::Any));
+        }
+        Type::Path(tp) => tp,
+        _ => return None,
+    };
+
     let seg: &syn::PathSegment = tp.path.segments.last()?;
-    if seg.ident != "Arc" {
-        return None;
-    }
+    let kind: StateArg = match seg.ident.to_string().as_str() {
+        "Arc" => StateArg::Shared,
+        "Rc" => StateArg::Local,
+        _ => return None,
+    };
 
     let syn::PathArguments::AngleBracketed(ab) = &seg.arguments else {
         return None;
@@ -92,39 +201,58 @@ fn extract_arc_inner_ty(ty: &Type) -> Option<Type> {
     }
 
     match ab.args.first()? {
-        syn::GenericArgument::Type(inner) => Some(inner.clone()),
+        syn::GenericArgument::Type(inner) => Some((inner.clone(), kind)),
         _ => None,
     }
 }
 
 #[derive(Clone, Copy, Debug)]
-enum ReqPos {
-    First,
-    Second,
+enum StateArg {
+    Any,
+    Shared,
+    Local,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum HandlerArg {
+    Request,
+    State(StateArg),
+    Bytes,
 }
 
 #[derive(Clone)]
 struct InputsShape {
     has_req: bool,
     has_state: bool,
+    has_bytes: bool,
     state_ty: Option<Type>,
-    req_pos: Option<ReqPos>,
+    args: Vec<HandlerArg>,
+}
+
+impl InputsShape {
+    fn state_arg(&self) -> Option<StateArg> {
+        self.args.iter().find_map(|arg: &HandlerArg| match arg {
+            HandlerArg::State(kind) => Some(*kind),
+            _ => None,
+        })
+    }
 }
 
 fn parse_inputs(inputs: &syn::punctuated::Punctuated<FnArg, Token![,]>) -> Result<InputsShape> {
-    if inputs.len() > 2 {
+    if inputs.len() > 3 {
         return Err(Error::new(
             inputs.span(),
-            "#[route] Handler must take (), (Request), (Arc<T>), or (Request, Arc<T>)",
+            "#[route] Handler must take any of Request, Bytes and the state (Arc<T>, Rc<T> or &T), each at most once",
         ));
     }
 
     let mut has_req: bool = false;
     let mut has_state: bool = false;
+    let mut has_bytes: bool = false;
     let mut state_ty: Option<Type> = None;
-    let mut req_pos: Option<ReqPos> = None;
+    let mut args: Vec<HandlerArg> = Vec::new();
 
-    for (idx, input) in inputs.iter().enumerate() {
+    for input in inputs {
         let typed: &syn::PatType = match input {
             FnArg::Typed(t) => t,
             FnArg::Receiver(r) => {
@@ -138,28 +266,43 @@ fn parse_inputs(inputs: &syn::punctuated::Punctuated<FnArg, Token![,]>) -> Resul
             }
 
             has_req = true;
-            req_pos = Some(if idx == 0 { ReqPos::First } else { ReqPos::Second });
+            args.push(HandlerArg::Request);
+            continue;
+        }
+
+        if is_bytes_type(&typed.ty) {
+            if has_bytes {
+                return Err(Error::new(typed.span(), "Duplicate Bytes argument"));
+            }
+
+            has_bytes = true;
+            args.push(HandlerArg::Bytes);
             continue;
         }
 
-        if let Some(inner) = extract_arc_inner_ty(&typed.ty) {
+        if let Some((inner, kind)) = extract_state_ty(&typed.ty) {
             if has_state {
-                return Err(Error::new(typed.span(), "Duplicate Arc<T> (state) argument"));
+                return Err(Error::new(typed.span(), "Duplicate state argument"));
             }
 
             has_state = true;
             state_ty = Some(inner);
+            args.push(HandlerArg::State(kind));
             continue;
         }
 
-        return Err(Error::new(typed.span(), "Argument must be Request<'_> or Arc<T>"));
+        return Err(Error::new(
+            typed.span(),
+            "Argument must be Request<'_>, Bytes<'_>, Arc<T>, Rc<T> or &T",
+        ));
     }
 
     Ok(InputsShape {
         has_req,
         has_state,
+        has_bytes,
         state_ty,
-        req_pos,
+        args,
     })
 }
 
@@ -177,6 +320,11 @@ struct ExpandModel {
     router_path: syn::Path,
     method_lit: LitStr,
     path_lit: LitStr,
+    aliases: Vec<LitStr>,
+    host: quote::__private::TokenStream,
+    meta: quote::__private::TokenStream,
+    max_body_size: quote::__private::TokenStream,
+    guard: Option<(syn::Path, u16)>,
     shape: InputsShape,
     kind: HandlerKind,
 }
@@ -187,12 +335,39 @@ fn build_model(args: RouteArgs, mut func: ItemFn) -> Result<ExpandModel> {
     }
 
     let (http_path, router_path) = resolve_paths();
+    let host: quote::__private::TokenStream = optional_lit(&args.host);
+    let max_body_size: quote::__private::TokenStream = match &args.max_body_size {
+        Some(max_body_size) => quote! { ::core::option::Option::Some(#max_body_size) },
+        None => quote! { ::core::option::Option::None },
+    };
+
+    let guard: Option<(syn::Path, u16)> = match &args.guard {
+        Some(guard) => Some((guard.parse()?, guard_status(args.guard_status.as_ref())?)),
+        None => None,
+    };
 
     let public_name: Ident = func.sig.ident.clone();
     let inner_name: Ident = format_ident!("__forge_route_impl_{public_name}");
     func.sig.ident = inner_name.clone();
 
     let shape: InputsShape = parse_inputs(&func.sig.inputs)?;
+    let meta: quote::__private::TokenStream = route_meta(&router_path, &args, &max_body_size, shape.state_arg());
+
+    if shape.has_bytes && args.stream_body.as_ref().is_some_and(LitBool::value) {
+        return Err(Error::new(
+            func.sig.inputs.span(),
+            "`Bytes` cannot be used with `stream_body`, read the body with `Request::body_stream` instead",
+        ));
+    }
+
+    if args.stream_body.as_ref().is_some_and(LitBool::value)
+        && args.stream_response.as_ref().is_some_and(LitBool::value)
+    {
+        return Err(Error::new(
+            func.sig.span(),
+            "`stream_body` and `stream_response` cannot be used on the same route",
+        ));
+    }
 
     let kind: HandlerKind = match (shape.has_req, shape.has_state) {
         (false, false) | (true, false) => HandlerKind::Generic,
@@ -215,54 +390,151 @@ fn build_model(args: RouteArgs, mut func: ItemFn) -> Result<ExpandModel> {
         router_path,
         method_lit: args.method,
         path_lit: args.path,
+        aliases: args.aliases,
+        host,
+        meta,
+        max_body_size,
+        guard,
         shape,
         kind,
     })
 }
 
+fn guard_status(guard_status: Option<&LitInt>) -> Result<u16> {
+    let Some(guard_status) = guard_status else {
+        return Ok(403);
+    };
+
+    match guard_status.base10_parse::<u16>()? {
+        status @ 400..=599 => Ok(status),
+        _ => Err(Error::new(
+            guard_status.span(),
+            "`guard_status` must be a 4xx or 5xx status code",
+        )),
+    }
+}
+
+fn optional_lit(lit: &Option<LitStr>) -> quote::__private::TokenStream {
+    match lit {
+        Some(lit) => quote! { ::core::option::Option::Some(#lit) },
+        None => quote! { ::core::option::Option::None },
+    }
+}
+
+fn route_meta(
+    router_path: &syn::Path,
+    args: &RouteArgs,
+    max_body_size: &quote::__private::TokenStream,
+    state_arg: Option<StateArg>,
+) -> quote::__private::TokenStream {
+    let requires_state: bool = state_arg.is_some();
+    let state_kind: quote::__private::TokenStream = match state_arg {
+        Some(StateArg::Shared) => quote! { ::core::option::Option::Some(#router_path::StateKind::Shared) },
+        Some(StateArg::Local) => quote! { ::core::option::Option::Some(#router_path::StateKind::Local) },
+        Some(StateArg::Any) | None => quote! { ::core::option::Option::None },
+    };
+
+    let name: quote::__private::TokenStream = optional_lit(&args.name);
+    let description: quote::__private::TokenStream = optional_lit(&args.description);
+    let tags: &Vec<LitStr> = &args.tags;
+    let stream_body: bool = args.stream_body.as_ref().is_some_and(LitBool::value);
+    let stream_response: bool = args.stream_response.as_ref().is_some_and(LitBool::value);
+
+    quote! {
+        #router_path::RouteMeta {
+            name: #name,
+            description: #description,
+            tags: &[#(#tags),*],
+            stream_body: #stream_body,
+            stream_response: #stream_response,
+            requires_state: #requires_state,
+            state_kind: #state_kind,
+            max_body_size: #max_body_size,
+        }
+    }
+}
+
 fn boxed_body(m: &ExpandModel) -> quote::__private::TokenStream {
     let http_path: &syn::Path = &m.http_path;
+    let router_path: &syn::Path = &m.router_path;
     let inner_name: &Ident = &m.inner_name;
     let shape: &InputsShape = &m.shape;
 
+    let require_kind = |variant: Ident, message: &str| {
+        quote! {
+            let #router_path::StateHandle::#variant(state) = state else {
+                return #http_path::Response::new(#http_path::HttpStatus::InternalServerError).text(#message);
+            };
+        }
+    };
+
+    let require_state_kind: Option<quote::__private::TokenStream> = match shape.state_arg() {
+        Some(StateArg::Shared) => Some(require_kind(
+            format_ident!("Shared"),
+            "This route takes shared state (`Arc<T>`), but the state is built per worker",
+        )),
+        Some(StateArg::Local) => Some(require_kind(
+            format_ident!("Local"),
+            "This route takes per-worker state (`Rc<T>`), but the state is shared",
+        )),
+        Some(StateArg::Any) | None => None,
+    };
+
     let require_state: quote::__private::TokenStream = quote! {
         let Some(state) = state else {
             return #http_path::Response::new(#http_path::HttpStatus::InternalServerError)
                 .text("Application state is required for this route, but no state was configured");
         };
+        #require_state_kind
     };
 
-    match (shape.has_req, shape.has_state) {
-        (false, false) => quote! {
-            let _ = (req, state);
-            #inner_name().await
-        },
-
-        (true, false) => quote! {
-            let _ = state;
-            #inner_name(req).await
-        },
+    let max_body_size: &quote::__private::TokenStream = &m.max_body_size;
+    let mut prelude: Vec<quote::__private::TokenStream> = Vec::new();
+
+    if let Some((guard, status)) = &m.guard {
+        prelude.push(quote! {
+            if !#guard(&req, state.as_deref()) {
+                // Evaluated at compile time, so a code `HttpStatus` does not know fails the build.
+                let status: #http_path::HttpStatus = const {
+                    match #http_path::HttpStatus::from_u16(#status) {
+                        ::core::option::Option::Some(status) => status,
+                        ::core::option::Option::None => panic!("`guard_status` is not a status code known to `HttpStatus`"),
+                    }
+                };
+                return #http_path::Response::from(#http_path::HttpError::new(status, status.canonical_reason()));
+            }
+        });
+    }
 
-        (false, true) => quote! {
-            let _ = req;
-            #require_state
-            #inner_name(state).await
-        },
+    prelude.push(match shape.has_state {
+        true => require_state,
+        false => quote! { let _ = state; },
+    });
+
+    if shape.has_bytes {
+        prelude.push(quote! {
+            let mut req: #http_path::Request = req;
+            let bytes: #http_path::Bytes = match #http_path::Bytes::from_request(&mut req, #max_body_size) {
+                ::core::result::Result::Ok(bytes) => bytes,
+                ::core::result::Result::Err(e) => return #http_path::Response::from(e),
+            };
+        });
+    }
 
-        (true, true) => {
-            let req_first: bool = matches!(shape.req_pos, Some(ReqPos::First));
+    if !shape.has_req {
+        prelude.push(quote! { let _ = req; });
+    }
 
-            let args: quote::__private::TokenStream = if req_first {
-                quote! { req, state }
-            } else {
-                quote! { state, req }
-            };
+    let args = shape.args.iter().map(|arg: &HandlerArg| match arg {
+        HandlerArg::Request => quote! { req },
+        HandlerArg::State(StateArg::Any) => quote! { &*state },
+        HandlerArg::State(StateArg::Shared | StateArg::Local) => quote! { state },
+        HandlerArg::Bytes => quote! { bytes },
+    });
 
-            quote! {
-                #require_state
-                #inner_name(#args).await
-            }
-        }
+    quote! {
+        #(#prelude)*
+        #http_path::IntoResponse::into_response(#inner_name(#(#args),*).await)
     }
 }
 
@@ -273,23 +545,26 @@ fn expand_generic(m: &ExpandModel, body: quote::__private::TokenStream) -> quote
     let router_path: &syn::Path = &m.router_path;
     let method_lit: &LitStr = &m.method_lit;
     let path_lit: &LitStr = &m.path_lit;
+    let aliases: &Vec<LitStr> = &m.aliases;
+    let host: &quote::__private::TokenStream = &m.host;
+    let meta: &quote::__private::TokenStream = &m.meta;
 
     quote! {
         #func
         pub fn #public_name<T>() -> #router_path::Routable<T>
         where
-            T: Send + Sync + 'static,
+            T: 'static,
         {
             fn make<T>() -> #router_path::handler::BoxedHandler<T>
             where
-                T: Send + Sync + 'static,
+                T: 'static,
             {
                 fn boxed<'a, T>(
                     req: #http_path::Request<'a>,
-                    state: ::core::option::Option<::std::sync::Arc<T>>,
+                    state: ::core::option::Option<#router_path::StateHandle<T>>,
                 ) -> #router_path::handler::LocalBoxFuture<'a, #http_path::Response<'a>>
                 where
-                    T: Send + Sync + 'static,
+                    T: 'static,
                 {
                     ::std::boxed::Box::pin(async move {
                         #body
@@ -303,6 +578,9 @@ fn expand_generic(m: &ExpandModel, body: quote::__private::TokenStream) -> quote
                 method: <#http_path::HttpMethod as ::core::str::FromStr>::from_str(#method_lit)
                     .expect("Invalid HTTP method in #[route]"),
                 path: #path_lit,
+                aliases: &[#(#aliases),*],
+                host: #host,
+                meta: #meta,
                 make: make::<T>,
             }
         }
@@ -320,6 +598,9 @@ fn expand_stateful(
     let router_path: &syn::Path = &m.router_path;
     let method_lit: &LitStr = &m.method_lit;
     let path_lit: &LitStr = &m.path_lit;
+    let aliases: &Vec<LitStr> = &m.aliases;
+    let host: &quote::__private::TokenStream = &m.host;
+    let meta: &quote::__private::TokenStream = &m.meta;
 
     quote! {
         #func
@@ -327,7 +608,7 @@ fn expand_stateful(
             fn make() -> #router_path::handler::BoxedHandler<#state_ty> {
                 fn boxed<'a>(
                     req: #http_path::Request<'a>,
-                    state: ::core::option::Option<::std::sync::Arc<#state_ty>>,
+                    state: ::core::option::Option<#router_path::StateHandle<#state_ty>>,
                 ) -> #router_path::handler::LocalBoxFuture<'a, #http_path::Response<'a>> {
                     ::std::boxed::Box::pin(async move {
                         #body
@@ -341,6 +622,9 @@ fn expand_stateful(
                 method: <#http_path::HttpMethod as ::core::str::FromStr>::from_str(#method_lit)
                     .expect("Invalid HTTP method in #[route]"),
                 path: #path_lit,
+                aliases: &[#(#aliases),*],
+                host: #host,
+                meta: #meta,
                 make,
             }
         }
@@ -358,6 +642,15 @@ fn expand(m: ExpandModel) -> TokenStream {
     out.into()
 }
 
+/// A handler takes the application state as `Arc<T>` when it is shared through
+/// `Listener::with_state`, as `Rc<T>` when it is built per worker through
+/// `Listener::with_state_factory`, or as `&T` to work with either.
+///
+/// `guard = "path::to::fn"` runs a check before the handler and its extractors. The guard is called
+/// as `fn(&Request, Option<&T>) -> bool`, so on handlers that do not take the state it has to be
+/// generic over `T`. When it returns `false` the handler is skipped and the client gets
+/// `guard_status` (`403 Forbidden` unless set) with the status's reason phrase as the body. A
+/// `guard_status` that is not one of the 4xx or 5xx codes in `HttpStatus` fails to compile.
 #[proc_macro_attribute]
 pub fn route(attr: TokenStream, item: TokenStream) -> TokenStream {
     let args: RouteArgs = parse_macro_input!(attr as RouteArgs);
@@ -370,9 +663,9 @@ pub fn route(attr: TokenStream, item: TokenStream) -> TokenStream {
 }
 
 fn method_route(method: &str, attr: TokenStream, item: TokenStream) -> TokenStream {
-    let path_lit: LitStr = parse_macro_input!(attr as LitStr);
-    let method_lit: LitStr = LitStr::new(method, path_lit.span());
-    let args: TokenStream = quote! { method = #method_lit, path = #path_lit }.into();
+    let MethodRouteArgs { path, rest } = parse_macro_input!(attr as MethodRouteArgs);
+    let method_lit: LitStr = LitStr::new(method, path.span());
+    let args: TokenStream = quote! { method = #method_lit, path = #path, #rest }.into();
     route(args, item)
 }
 
@@ -6,4 +6,20 @@ use thiserror::Error;
 pub enum RouterError {
     #[error("{0}: duplicate route")]
     DuplicateRoute(String),
+
+    #[error("{0}: duplicate fallback")]
+    DuplicateFallback(String),
+
+    #[error("{path}: parameter \":{incoming}\" conflicts with \":{existing}\" registered at the same position")]
+    ParamConflict {
+        path: String,
+        existing: String,
+        incoming: String,
+    },
+
+    #[error("invalid route pattern {0}")]
+    InvalidPattern(String),
+
+    #[error("{0}: wildcard segments are not supported, register a fallback to match the rest of a path")]
+    WildcardUnsupported(String),
 }
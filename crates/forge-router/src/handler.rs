@@ -1,5 +1,6 @@
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{future::Future, pin::Pin};
 
+use super::StateHandle;
 use forge_http::{Request, Response};
 
 pub type LocalBoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
@@ -12,23 +13,23 @@ pub trait IntoHandler<T>: Send + Sync + 'static {
 pub struct HandlerFn<T>(pub T);
 
 pub trait Handler<T>: Send + Sync + 'static {
-    fn call<'a>(&'a self, req: Request<'a>, state: Option<Arc<T>>) -> LocalBoxFuture<'a, Response<'a>>;
+    fn call<'a>(&'a self, req: Request<'a>, state: Option<StateHandle<T>>) -> LocalBoxFuture<'a, Response<'a>>;
 }
 
 impl<T, K> Handler<K> for HandlerFn<T>
 where
-    K: Send + Sync + 'static,
-    T: for<'r> Fn(Request<'r>, Option<Arc<K>>) -> LocalBoxFuture<'r, Response<'r>> + Send + Sync + 'static,
+    K: 'static,
+    T: for<'r> Fn(Request<'r>, Option<StateHandle<K>>) -> LocalBoxFuture<'r, Response<'r>> + Send + Sync + 'static,
 {
-    fn call<'a>(&'a self, req: Request<'a>, state: Option<Arc<K>>) -> LocalBoxFuture<'a, Response<'a>> {
+    fn call<'a>(&'a self, req: Request<'a>, state: Option<StateHandle<K>>) -> LocalBoxFuture<'a, Response<'a>> {
         (self.0)(req, state)
     }
 }
 
 impl<T, K> IntoHandler<K> for T
 where
-    K: Send + Sync + 'static,
-    T: for<'a> Fn(Request<'a>, Option<Arc<K>>) -> LocalBoxFuture<'a, Response<'a>> + Send + Sync + 'static,
+    K: 'static,
+    T: for<'a> Fn(Request<'a>, Option<StateHandle<K>>) -> LocalBoxFuture<'a, Response<'a>> + Send + Sync + 'static,
 {
     fn into_handler(self) -> BoxedHandler<K> {
         Box::new(HandlerFn(self))
@@ -37,7 +38,7 @@ where
 
 impl<T> IntoHandler<T> for BoxedHandler<T>
 where
-    T: Send + Sync + 'static,
+    T: 'static,
 {
     fn into_handler(self) -> BoxedHandler<T> {
         self
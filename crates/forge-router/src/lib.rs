@@ -1,10 +1,13 @@
 pub mod error;
 pub mod handler;
+pub mod openapi;
 pub mod router;
+pub mod state;
 
 pub use error::RouterError;
 pub use handler::{BoxedHandler, Handler, IntoHandler};
-pub use router::{Routable, Router};
+pub use router::{Routable, Route, RouteMeta, Router};
+pub use state::{StateHandle, StateKind};
 
 pub use forge_http::HttpMethod;
 pub use forge_http::IntoResponse;
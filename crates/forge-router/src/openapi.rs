@@ -0,0 +1,251 @@
+use std::sync::{Arc, OnceLock};
+
+use super::handler::{Handler, LocalBoxFuture};
+use super::{Route, RouteMeta, Router, RouterError, StateHandle};
+use forge_http::{HttpMethod, HttpStatus, Request, Response};
+use forge_utils::Segment;
+use serde_json::{Map, Value, json};
+
+const OPENAPI_VERSION: &str = "3.0.3";
+
+/// A document registered with `register_openapi`, filled in once the router is finalized.
+pub(crate) struct OpenApiSlot {
+    title: String,
+    version: String,
+    document: Arc<OnceLock<String>>,
+}
+
+impl<T> Router<T>
+where
+    T: 'static,
+{
+    pub fn openapi(&self, title: &str, version: &str) -> Value {
+        let mut paths: Map<String, Value> = Map::new();
+
+        for (route, _) in self
+            .route_entries()
+            .filter(|(_, is_fallback): &(&Route<T>, bool)| !is_fallback)
+        {
+            let (path, parameters): (String, Vec<Value>) = Self::openapi_path(route.path);
+
+            let operation: Value = Self::openapi_operation(&route.meta, parameters);
+            let method: String = route.method.to_string().to_ascii_lowercase();
+
+            if let Value::Object(item) = paths.entry(path).or_insert_with(|| json!({})) {
+                item.insert(method, operation);
+            }
+        }
+
+        json!({
+            "openapi": OPENAPI_VERSION,
+            "info": { "title": title, "version": version },
+            "paths": paths,
+        })
+    }
+
+    /// Serves `openapi` at `path`. The document is generated when the router is finalized, which
+    /// `Listener::run` and `RouterHandle::replace` do, so it covers every route registered by then,
+    /// including ones added after this call and the health check routes. Fallbacks are left out, and
+    /// until then the route answers `503 Service Unavailable`.
+    pub fn register_openapi(&mut self, path: &'static str, title: &str, version: &str) -> Result<(), RouterError> {
+        let document: Arc<OnceLock<String>> = Arc::default();
+
+        self.add_route(Route {
+            path,
+            method: HttpMethod::GET,
+            host: None,
+            meta: RouteMeta::default(),
+            handler: Box::new(OpenApiDocument {
+                document: document.clone(),
+            }),
+        })?;
+
+        self.openapi_slots.push(OpenApiSlot {
+            title: title.into(),
+            version: version.into(),
+            document,
+        });
+
+        Ok(())
+    }
+
+    pub(crate) fn render_openapi(&self) {
+        for slot in &self.openapi_slots {
+            slot.document
+                .get_or_init(|| self.openapi(&slot.title, &slot.version).to_string());
+        }
+    }
+
+    fn openapi_path(path: &str) -> (String, Vec<Value>) {
+        let mut openapi_path: String = String::new();
+        let mut parameters: Vec<Value> = Vec::new();
+
+        for segment in Self::parse_to_segment(path) {
+            match segment {
+                Segment::Exact(name) => {
+                    openapi_path.push('/');
+                    openapi_path.push_str(name);
+                }
+                Segment::Param(name) => {
+                    openapi_path.push_str(&format!("/{{{name}}}"));
+                    parameters.push(json!({
+                        "name": name,
+                        "in": "path",
+                        "required": true,
+                        "schema": { "type": "string" },
+                    }));
+                }
+            }
+        }
+
+        if openapi_path.is_empty() {
+            openapi_path.push('/');
+        }
+
+        (openapi_path, parameters)
+    }
+
+    fn openapi_operation(meta: &RouteMeta, parameters: Vec<Value>) -> Value {
+        let mut operation: Map<String, Value> = Map::new();
+
+        if let Some(name) = meta.name {
+            operation.insert("operationId".into(), name.into());
+        }
+
+        if let Some(description) = meta.description {
+            operation.insert("description".into(), description.into());
+        }
+
+        if !meta.tags.is_empty() {
+            operation.insert("tags".into(), meta.tags.into());
+        }
+
+        if !parameters.is_empty() {
+            operation.insert("parameters".into(), parameters.into());
+        }
+
+        operation.insert("responses".into(), json!({ "default": { "description": "Response" } }));
+        operation.into()
+    }
+}
+
+struct OpenApiDocument {
+    document: Arc<OnceLock<String>>,
+}
+
+impl<T> Handler<T> for OpenApiDocument
+where
+    T: 'static,
+{
+    fn call<'a>(&'a self, _: Request<'a>, _: Option<StateHandle<T>>) -> LocalBoxFuture<'a, Response<'a>> {
+        Box::pin(async {
+            match self.document.get() {
+                Some(document) => Response::new(HttpStatus::Ok)
+                    .header("Content-Type", "application/json")
+                    .body(document.as_str()),
+                None => Response::new(HttpStatus::ServiceUnavailable)
+                    .text("The OpenAPI document is generated once the router is finalized"),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use forge_http::Response;
+    use forge_macros::{get, post};
+    use forge_utils::PathMatch;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    struct State;
+
+    #[test]
+    fn test_openapi_document() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/users/:id", name = "get_user", description = "Fetch a user", tags = ["users"])]
+        async fn get_user() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[post("/users")]
+        async fn create_user() -> Response<'static> {
+            Response::new(HttpStatus::Created)
+        }
+
+        #[get("/")]
+        async fn index() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        router.register(get_user);
+        router.register(create_user);
+        router.register(index);
+
+        let document: Value = router.openapi("Forge", "1.0.0");
+
+        assert_eq!(document["openapi"], "3.0.3");
+        assert_eq!(document["info"], json!({ "title": "Forge", "version": "1.0.0" }));
+        assert!(document["paths"]["/"]["get"].is_object());
+        assert!(document["paths"]["/users"]["post"]["parameters"].is_null());
+
+        assert_eq!(
+            document["paths"]["/users/{id}"]["get"],
+            json!({
+                "operationId": "get_user",
+                "description": "Fetch a user",
+                "tags": ["users"],
+                "parameters": [{ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } }],
+                "responses": { "default": { "description": "Response" } },
+            })
+        );
+    }
+
+    #[test]
+    fn test_registered_document_covers_routes_added_before_finalize() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/users")]
+        async fn list_users() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/static")]
+        async fn assets() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        router.register_openapi("/openapi.json", "Forge", "1.0.0").unwrap();
+        router.register(list_users);
+        router.register_fallback(assets);
+
+        let serve = |router: &Router<State>| -> (HttpStatus, Option<Value>) {
+            let route: PathMatch<Route<State>> = router.get_route("/openapi.json", &HttpMethod::GET).unwrap();
+            let request: Request = Request::builder(HttpMethod::GET, "/openapi.json").build();
+            let mut cx: Context = Context::from_waker(Waker::noop());
+
+            match pin!(route.value.handler.call(request, None)).poll(&mut cx) {
+                Poll::Ready(response) => (
+                    response.status(),
+                    response
+                        .get_body()
+                        .and_then(|body: &[u8]| serde_json::from_slice(body).ok()),
+                ),
+                Poll::Pending => panic!("Handler should complete without suspending"),
+            }
+        };
+
+        assert_eq!(serve(&router).0, HttpStatus::ServiceUnavailable);
+
+        router.finalize();
+        let (status, document): (HttpStatus, Option<Value>) = serve(&router);
+        let document: Value = document.unwrap();
+
+        assert_eq!(status, HttpStatus::Ok);
+        assert!(document["paths"]["/users"]["get"].is_object());
+        assert!(document["paths"]["/openapi.json"]["get"].is_object());
+        assert!(document["paths"]["/static"].is_null());
+    }
+}
@@ -1,34 +1,72 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 use super::BoxedHandler;
 use super::RouterError;
-use forge_http::HttpMethod;
+use super::openapi::OpenApiSlot;
+use super::{StateHandle, StateKind};
+
+use forge_http::{HttpError, HttpMethod, HttpStatus, Request, Response};
 use forge_utils::{PathMatch, PathTree, Segment};
 
 type Path = &'static str;
-type Routes<T> = HashMap<HttpMethod, PathTree<BoxedHandler<T>>>;
+type Routes<T> = HashMap<HttpMethod, PathTree<Route<T>>>;
 
 const ROUTER_RULES: (char, char) = ('/', ':');
+const WILDCARD_PREFIX: char = '*';
+
+/// `stream_body` routes receive their request body through `Request::body_stream` as it arrives
+/// instead of after the whole body has been read. `stream_response` routes can write their response
+/// in pieces through `Request::response_writer`. `requires_state` is set for handlers that take
+/// the application state, and `state_kind` for those that take it as an `Arc<T>` or an `Rc<T>`,
+/// which only work with that kind of state. `max_body_size` replaces the listener's body size
+/// limit for the route.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RouteMeta {
+    pub name: Option<&'static str>,
+    pub description: Option<&'static str>,
+    pub tags: &'static [&'static str],
+    pub stream_body: bool,
+    pub stream_response: bool,
+    pub requires_state: bool,
+    pub state_kind: Option<StateKind>,
+    pub max_body_size: Option<usize>,
+}
 
+/// `aliases` are extra paths served by the same handler, registered under the same method and
+/// host as `path`.
 pub struct Routable<T> {
     pub path: &'static str,
+    pub aliases: &'static [&'static str],
     pub method: HttpMethod,
+    pub host: Option<&'static str>,
+    pub meta: RouteMeta,
     pub make: fn() -> BoxedHandler<T>,
 }
 
 pub struct Route<T> {
     pub path: Path,
     pub method: HttpMethod,
+    pub host: Option<&'static str>,
+    pub meta: RouteMeta,
     pub handler: BoxedHandler<T>,
 }
 
+/// Routes registered with a `host` only match requests whose `Host` header names that host (the
+/// port is ignored and the comparison is case-insensitive). Routes without a host match any host
+/// and act as the fallback, so a request is first looked up among the routes of its host and then
+/// among the hostless ones.
 pub struct Router<T> {
     routes: Routes<T>,
+    host_routes: HashMap<String, Routes<T>>,
+    streaming: bool,
+    body_limits: bool,
+    pub(crate) openapi_slots: Vec<OpenApiSlot>,
 }
 
 impl<T> Default for Router<T>
 where
-    T: Send + Sync + 'static,
+    T: 'static,
 {
     fn default() -> Self {
         Self::new()
@@ -37,49 +75,236 @@ where
 
 impl<T> Router<T>
 where
-    T: Send + Sync + 'static,
+    T: 'static,
 {
     pub fn new() -> Self {
-        Self { routes: HashMap::new() }
+        Self {
+            routes: HashMap::new(),
+            host_routes: HashMap::new(),
+            streaming: false,
+            body_limits: false,
+            openapi_slots: Vec::new(),
+        }
     }
 
     pub fn register<F>(&mut self, routable: F)
     where
         F: FnOnce() -> Routable<T>,
     {
-        let routable: Routable<T> = routable();
+        for route in Self::expand_routable(routable()) {
+            self.add_route(route)
+                .unwrap_or_else(|e: RouterError| panic!("failed to register route {e}"));
+        }
+    }
 
-        self.add_route(Route {
-            path: routable.path,
-            method: routable.method,
-            handler: (routable.make)(),
-        })
-        .unwrap_or_else(|e: RouterError| panic!("failed to register route {e}"));
+    pub fn register_fallback<F>(&mut self, routable: F)
+    where
+        F: FnOnce() -> Routable<T>,
+    {
+        for route in Self::expand_routable(routable()) {
+            self.add_fallback(route)
+                .unwrap_or_else(|e: RouterError| panic!("failed to register fallback {e}"));
+        }
+    }
+
+    fn expand_routable(routable: Routable<T>) -> impl Iterator<Item = Route<T>> {
+        std::iter::once(routable.path)
+            .chain(routable.aliases.iter().copied())
+            .map(move |path: Path| Route {
+                path,
+                method: routable.method,
+                host: routable.host,
+                meta: routable.meta,
+                handler: (routable.make)(),
+            })
+    }
+
+    pub fn get_route<'a, 'b>(&'a self, path: &'b str, method: &HttpMethod) -> Option<PathMatch<'a, 'b, Route<T>>> {
+        Self::find_route(&self.routes, path, method)
     }
 
-    pub fn get_route<'a, 'b>(
+    pub fn get_host_route<'a, 'b>(
         &'a self,
+        host: Option<&str>,
         path: &'b str,
         method: &HttpMethod,
-    ) -> Option<PathMatch<'a, 'b, BoxedHandler<T>>> {
-        let path_tree: &PathTree<BoxedHandler<T>> = self.routes.get(method)?;
-        path_tree.find(Self::sanitize_path(path))
+    ) -> Option<PathMatch<'a, 'b, Route<T>>> {
+        host.filter(|_: &&str| !self.host_routes.is_empty())
+            .and_then(|host: &str| self.host_routes.get(Self::normalize_host(host).as_ref()))
+            .and_then(|routes: &Routes<T>| Self::find_route(routes, path, method))
+            .or_else(|| self.get_route(path, method))
+    }
+
+    pub async fn dispatch<'a>(&'a self, mut request: Request<'a>, state: Option<StateHandle<T>>) -> Response<'a> {
+        let host: Option<&str> = request.headers.get("host").map(|host: &Cow<str>| host.as_ref());
+
+        let Some(route) = self.get_host_route(host, request.path, &request.method) else {
+            return self.no_route_error(&request.method).into();
+        };
+
+        request.set_params(route.params);
+        request.set_path_tail(route.tail);
+        request.set_matched_path(route.value.path);
+        route.value.handler.call(request, state).await
     }
 
-    fn add_route(&mut self, route: Route<T>) -> Result<(), RouterError> {
-        let path_tree: &mut PathTree<BoxedHandler<T>> = self.routes.entry(route.method).or_default();
+    /// The error for a request no route matched. `CONNECT` requests get `501 Not Implemented` unless
+    /// some route handles the method; a fallback registered at `/` receives every `CONNECT`, with
+    /// its `host:port` target as the path.
+    pub fn no_route_error(&self, method: &HttpMethod) -> HttpError {
+        let handles_method: bool = std::iter::once(&self.routes)
+            .chain(self.host_routes.values())
+            .any(|routes: &Routes<T>| routes.contains_key(method));
+
+        if *method == HttpMethod::CONNECT && !handles_method {
+            return HttpError::new(HttpStatus::NotImplemented, "CONNECT tunnels are not supported");
+        }
 
-        if path_tree
-            .insert(Self::parse_to_segment(route.path), route.handler)
+        HttpError::new(HttpStatus::NotFound, "The requested resource could not be found")
+    }
+
+    pub fn finalize(&mut self) {
+        std::iter::once(&mut self.routes)
+            .chain(self.host_routes.values_mut())
+            .flat_map(|routes: &mut Routes<T>| routes.values_mut())
+            .for_each(PathTree::compile);
+
+        self.render_openapi();
+    }
+
+    pub fn has_streaming_routes(&self) -> bool {
+        self.streaming
+    }
+
+    pub fn has_route_body_limits(&self) -> bool {
+        self.body_limits
+    }
+
+    /// Every registered route, fallbacks included.
+    pub fn routes(&self) -> impl Iterator<Item = &Route<T>> {
+        self.route_entries().map(|(route, _): (&Route<T>, bool)| route)
+    }
+
+    /// Every registered route with whether it was registered as a fallback.
+    pub(crate) fn route_entries(&self) -> impl Iterator<Item = (&Route<T>, bool)> {
+        std::iter::once(&self.routes)
+            .chain(self.host_routes.values())
+            .flat_map(|routes: &Routes<T>| routes.values())
+            .flat_map(|path_tree: &PathTree<Route<T>>| path_tree.entries())
+    }
+
+    pub fn add_route(&mut self, route: Route<T>) -> Result<(), RouterError> {
+        let route_name: String = Self::fmt_route(&route);
+        let path: Path = route.path;
+        self.check_pattern(&route, &route_name)?;
+        self.streaming |= route.meta.stream_body;
+        self.body_limits |= route.meta.max_body_size.is_some();
+
+        if self
+            .path_tree(&route)
+            .insert(Self::parse_to_segment(path), route)
             .is_some()
         {
-            return Err(RouterError::DuplicateRoute(Self::fmt_route(&route.method, route.path)));
+            return Err(RouterError::DuplicateRoute(route_name));
         };
 
         Ok(())
     }
 
-    fn parse_to_segment<'a>(path: &'a str) -> impl Iterator<Item = Segment<'a>> {
+    pub fn add_fallback(&mut self, route: Route<T>) -> Result<(), RouterError> {
+        let route_name: String = Self::fmt_route(&route);
+        let path: Path = route.path;
+        self.check_pattern(&route, &route_name)?;
+        self.streaming |= route.meta.stream_body;
+        self.body_limits |= route.meta.max_body_size.is_some();
+
+        if self
+            .path_tree(&route)
+            .insert_fallback(Self::parse_to_segment(path), route)
+            .is_some()
+        {
+            return Err(RouterError::DuplicateFallback(route_name));
+        };
+
+        Ok(())
+    }
+
+    fn check_pattern(&mut self, route: &Route<T>, route_name: &str) -> Result<(), RouterError> {
+        let segments: Vec<&str> = Self::sanitize_path(route.path).collect();
+
+        for (idx, segment) in segments.iter().enumerate() {
+            if segment.starts_with(WILDCARD_PREFIX) {
+                return Err(RouterError::WildcardUnsupported(route_name.into()));
+            }
+
+            if let Some(name) = segment.strip_prefix(ROUTER_RULES.1) {
+                if name.is_empty() {
+                    return Err(RouterError::InvalidPattern(format!(
+                        "{route_name}: parameter at segment {} has no name",
+                        idx + 1
+                    )));
+                }
+
+                if name.contains([ROUTER_RULES.1, WILDCARD_PREFIX]) {
+                    return Err(RouterError::InvalidPattern(format!(
+                        "{route_name}: parameter name \"{name}\" may not contain ':' or '*'"
+                    )));
+                }
+            }
+        }
+
+        match self
+            .path_tree(route)
+            .conflicting_param(Self::parse_to_segment(route.path))
+        {
+            Some((existing, incoming)) => Err(RouterError::ParamConflict {
+                path: route_name.into(),
+                existing: existing.into(),
+                incoming: incoming.into(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn path_tree(&mut self, route: &Route<T>) -> &mut PathTree<Route<T>> {
+        let routes: &mut Routes<T> = match route.host {
+            Some(host) => self
+                .host_routes
+                .entry(Self::normalize_host(host).into_owned())
+                .or_default(),
+            None => &mut self.routes,
+        };
+
+        routes.entry(route.method).or_default()
+    }
+
+    fn find_route<'a, 'b>(
+        routes: &'a Routes<T>,
+        path: &'b str,
+        method: &HttpMethod,
+    ) -> Option<PathMatch<'a, 'b, Route<T>>> {
+        let path_tree: &PathTree<Route<T>> = routes.get(method)?;
+        path_tree.find(Self::sanitize_path(path))
+    }
+
+    fn normalize_host(host: &str) -> Cow<'_, str> {
+        let host: &str = host.trim();
+
+        let host: &str = match host.strip_prefix('[') {
+            Some(ipv6) => ipv6.split(']').next().unwrap_or(ipv6),
+            None => host.split(':').next().unwrap_or(host),
+        };
+
+        let host: &str = host.strip_suffix('.').unwrap_or(host);
+
+        if host.bytes().any(|byte: u8| byte.is_ascii_uppercase()) {
+            Cow::Owned(host.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(host)
+        }
+    }
+
+    pub(crate) fn parse_to_segment<'a>(path: &'a str) -> impl Iterator<Item = Segment<'a>> {
         Self::sanitize_path(path).map(|path: &str| {
             if path.starts_with(ROUTER_RULES.1) {
                 Segment::Param(&path[1..])
@@ -95,19 +320,28 @@ where
             .filter(|s: &&str| !s.is_empty())
     }
 
-    fn fmt_route(method: &HttpMethod, path: &str) -> String {
-        format!("[{method}] - \"{path}\"")
+    fn fmt_route(route: &Route<T>) -> String {
+        match route.host {
+            Some(host) => format!("[{}] - \"{}\" on host \"{host}\"", route.method, route.path),
+            None => format!("[{}] - \"{}\"", route.method, route.path),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use forge_http::{HttpMethod, HttpStatus, Response};
-    use forge_macros::get;
+    use forge_http::Bytes;
+    use forge_http::{HttpMethod, HttpStatus, RequestBuilder, Response};
+    use forge_macros::{get, post, route};
+    use std::cell::Cell;
+    use std::pin::pin;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Waker};
 
     struct State;
-    type Match<'a, 'b> = PathMatch<'a, 'b, BoxedHandler<State>>;
+    type Match<'a, 'b> = PathMatch<'a, 'b, super::Route<State>>;
     type Route<'a, 'b> = Option<Match<'a, 'b>>;
 
     #[test]
@@ -178,6 +412,7 @@ mod tests {
         let match_data: Match = result.unwrap();
         assert_eq!(match_data.params.len(), 1);
         assert_eq!(match_data.params[0], ("id", "123"));
+        assert_eq!(match_data.value.path, "/users/:id");
     }
 
     #[test]
@@ -196,6 +431,7 @@ mod tests {
 
         let match_data: Match = result.unwrap();
         assert_eq!(match_data.params.len(), 2);
+        assert_eq!(match_data.value.path, "/store/:store_id/customer/:customer_id");
 
         assert!(match_data.params.contains(&("store_id", "99")));
         assert!(match_data.params.contains(&("customer_id", "500")));
@@ -301,4 +537,443 @@ mod tests {
         assert!(param_match.is_some());
         assert_eq!(param_match.unwrap().params[0], ("id", "123"));
     }
+
+    #[test]
+    fn test_deepest_fallback_wins() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/")]
+        async fn spa_fallback() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/api")]
+        async fn api_fallback() -> Response<'static> {
+            Response::new(HttpStatus::NotFound)
+        }
+
+        #[get("/api/users")]
+        async fn users_handler() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        router.register(users_handler);
+        router.register_fallback(spa_fallback);
+        router.register_fallback(api_fallback);
+
+        let exact: Match = router.get_route("/api/users", &HttpMethod::GET).unwrap();
+        assert!(!exact.is_fallback);
+
+        let api_miss: Match = router.get_route("/api/unknown/deep", &HttpMethod::GET).unwrap();
+        assert!(api_miss.is_fallback);
+        assert!(std::ptr::eq(
+            api_miss.value,
+            router.get_route("/api", &HttpMethod::GET).unwrap().value
+        ));
+
+        let spa_miss: Match = router.get_route("/dashboard/settings", &HttpMethod::GET).unwrap();
+        assert!(spa_miss.is_fallback);
+        assert!(!std::ptr::eq(spa_miss.value, api_miss.value));
+
+        let other_method: Route = router.get_route("/api/unknown", &HttpMethod::POST);
+        assert!(other_method.is_none());
+
+        assert_eq!(router.routes().count(), 3);
+    }
+
+    #[test]
+    fn test_fallback_keeps_ancestor_params() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/tenants/:tenant")]
+        async fn tenant_fallback() -> Response<'static> {
+            Response::new(HttpStatus::NotFound)
+        }
+
+        router.register_fallback(tenant_fallback);
+
+        let result: Match = router.get_route("/tenants/acme/missing/42", &HttpMethod::GET).unwrap();
+        assert!(result.is_fallback);
+        assert_eq!(result.params, vec![("tenant", "acme")]);
+        assert_eq!(result.tail(), "missing/42");
+
+        let outside: Route = router.get_route("/other", &HttpMethod::GET);
+        assert!(outside.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to register fallback [GET] - \"/api\": duplicate fallback")]
+    fn test_duplicate_fallback_panics() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/api")]
+        async fn api_fallback() -> Response<'static> {
+            Response::new(HttpStatus::NotFound)
+        }
+
+        router.register_fallback(api_fallback);
+        router.register_fallback(api_fallback);
+    }
+
+    #[test]
+    fn test_routes_expose_metadata() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/users/:id", name = "get_user", description = "Fetch a user by id", tags = ["users", "public"])]
+        async fn get_user() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/ping")]
+        async fn ping_handler() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        router.register(get_user);
+        router.register(ping_handler);
+
+        let mut routes: Vec<&super::Route<State>> = router.routes().collect();
+        routes.sort_by_key(|route: &&super::Route<State>| route.path);
+
+        assert_eq!(routes.len(), 2);
+        assert_eq!(routes[0].path, "/ping");
+        assert_eq!(routes[0].meta.name, None);
+        assert!(routes[0].meta.tags.is_empty());
+
+        assert_eq!(routes[1].meta.name, Some("get_user"));
+        assert_eq!(routes[1].meta.description, Some("Fetch a user by id"));
+        assert_eq!(routes[1].meta.tags, ["users", "public"]);
+    }
+
+    fn dispatch<'a>(router: &'a Router<State>, request: Request<'a>) -> Response<'a> {
+        dispatch_with_state(router, request, None)
+    }
+
+    fn dispatch_with_state<'a, T>(
+        router: &'a Router<T>,
+        request: Request<'a>,
+        state: Option<StateHandle<T>>,
+    ) -> Response<'a>
+    where
+        T: 'static,
+    {
+        let mut cx: Context = Context::from_waker(Waker::noop());
+
+        match pin!(router.dispatch(request, state)).poll(&mut cx) {
+            Poll::Ready(response) => response,
+            Poll::Pending => panic!("Handler should complete without suspending"),
+        }
+    }
+
+    #[test]
+    fn test_dispatch_runs_matched_handler() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/users/:id")]
+        async fn get_user(req: Request<'_>) -> Response<'static> {
+            let id: String = req.param("id").unwrap_or_default().to_owned();
+            let matched: String = req.matched_path().unwrap_or_default().to_owned();
+            Response::new(HttpStatus::Ok).header("X-Matched-Path", matched).text(id)
+        }
+
+        router.register(get_user);
+
+        let response: Response = dispatch(&router, Request::builder(HttpMethod::GET, "/users/42").build());
+        assert_eq!(response.status(), HttpStatus::Ok);
+        assert_eq!(response.get_body(), Some(&b"42"[..]));
+        assert_eq!(response.get_header("X-Matched-Path"), Some("/users/:id"));
+
+        let missing: Response = dispatch(&router, Request::builder(HttpMethod::POST, "/users/42").build());
+        assert_eq!(missing.status(), HttpStatus::NotFound);
+    }
+
+    #[test]
+    fn test_host_routes_take_precedence_over_hostless() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/", host = "api.example.com")]
+        async fn api_index() -> Response<'static> {
+            Response::new(HttpStatus::Ok).text("api")
+        }
+
+        #[get("/")]
+        async fn default_index() -> Response<'static> {
+            Response::new(HttpStatus::Ok).text("default")
+        }
+
+        #[get("/status", host = "api.example.com")]
+        async fn api_status() -> Response<'static> {
+            Response::new(HttpStatus::Ok).text("status")
+        }
+
+        router.register(api_index);
+        router.register(default_index);
+        router.register(api_status);
+
+        let body = |host: Option<&'static str>, path: &'static str| -> Option<Vec<u8>> {
+            let mut request: RequestBuilder = Request::builder(HttpMethod::GET, path);
+
+            if let Some(host) = host {
+                request = request.header("host", host);
+            }
+
+            dispatch(&router, request.build()).get_body().map(<[u8]>::to_vec)
+        };
+
+        assert_eq!(body(Some("api.example.com"), "/"), Some(b"api".to_vec()));
+        assert_eq!(body(Some("API.Example.com:8080"), "/"), Some(b"api".to_vec()));
+        assert_eq!(body(Some("www.example.com"), "/"), Some(b"default".to_vec()));
+        assert_eq!(body(None, "/"), Some(b"default".to_vec()));
+        assert_eq!(body(Some("api.example.com"), "/status"), Some(b"status".to_vec()));
+        assert_eq!(
+            body(Some("www.example.com"), "/status"),
+            Some(b"The requested resource could not be found".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_dispatch_exposes_params_by_name() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/stores/:store/orders/:order")]
+        async fn get_order(req: Request<'_>) -> Response<'static> {
+            let mut names: Vec<&str> = req.params.keys().copied().collect();
+            names.sort_unstable();
+
+            let body: String = format!(
+                "{}:{}:{}:{}",
+                req.params.len(),
+                names.join(","),
+                req.param("store").unwrap_or_default(),
+                req.param("order").unwrap_or_default(),
+            );
+
+            Response::new(HttpStatus::Ok).text(body)
+        }
+
+        router.register(get_order);
+
+        let response: Response = dispatch(&router, Request::builder(HttpMethod::GET, "/stores/7/orders/42").build());
+        assert_eq!(response.get_body(), Some(&b"2:order,store:7:42"[..]));
+    }
+
+    #[test]
+    fn test_route_with_multiple_paths() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/", path = "/index.html")]
+        async fn index(req: Request<'_>) -> Response<'static> {
+            Response::new(HttpStatus::Ok).text(req.matched_path().unwrap_or_default().to_string())
+        }
+
+        #[route(method = "GET", path = ["/home", "/start"])]
+        async fn home() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        router.register(index);
+        router.register(home);
+
+        for (path, matched) in [("/", "/"), ("/index.html", "/index.html")] {
+            let response: Response = dispatch(&router, Request::builder(HttpMethod::GET, path).build());
+            assert_eq!(response.get_body(), Some(matched.as_bytes()));
+        }
+
+        assert!(router.get_route("/home", &HttpMethod::GET).is_some());
+        assert!(router.get_route("/start", &HttpMethod::GET).is_some());
+        assert_eq!(router.routes().count(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to register route [GET] - \"/index.html\": duplicate route")]
+    fn test_multiple_paths_collide_like_duplicates() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/index.html")]
+        async fn page() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/", path = "/index.html")]
+        async fn index() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        router.register(page);
+        router.register(index);
+    }
+
+    #[test]
+    fn test_bytes_extractor_moves_exact_body() {
+        let mut router: Router<State> = Router::new();
+
+        #[post("/webhooks", max_body_size = 8)]
+        async fn webhook(req: Request<'_>, body: Bytes<'_>) -> Response<'static> {
+            assert!(req.bytes().is_empty());
+            Response::new(HttpStatus::Ok).bytes(body.to_vec())
+        }
+
+        router.register(webhook);
+
+        let payload: [u8; 6] = [0x00, 0xFF, 0x0D, 0x0A, 0x7F, 0x80];
+        let request: Request = Request::builder(HttpMethod::POST, "/webhooks")
+            .body(&payload[..])
+            .build();
+        let response: Response = dispatch(&router, request);
+
+        assert_eq!(response.status(), HttpStatus::Ok);
+        assert_eq!(response.get_body(), Some(&payload[..]));
+
+        let oversized: Request = Request::builder(HttpMethod::POST, "/webhooks")
+            .body(&[0u8; 9][..])
+            .build();
+        assert_eq!(dispatch(&router, oversized).status(), HttpStatus::PayloadTooLarge);
+    }
+
+    #[test]
+    fn test_handlers_take_the_state_they_declare() {
+        let mut router: Router<Cell<usize>> = Router::new();
+
+        #[get("/local")]
+        async fn local(visits: Rc<Cell<usize>>) -> Response<'static> {
+            visits.set(visits.get() + 1);
+            Response::new(HttpStatus::Ok).text(visits.get().to_string())
+        }
+
+        #[get("/either")]
+        async fn either(visits: &Cell<usize>) -> Response<'static> {
+            Response::new(HttpStatus::Ok).text(visits.get().to_string())
+        }
+
+        #[get("/shared")]
+        async fn shared(_visits: Arc<Cell<usize>>) -> Response<'static> {
+            panic!("Shared handler should not run on per-worker state");
+        }
+
+        router.register(local);
+        router.register(either);
+        router.register(shared);
+
+        let kinds: [Option<StateKind>; 3] = ["/local", "/either", "/shared"]
+            .map(|path: &str| router.get_route(path, &HttpMethod::GET).unwrap().value.meta.state_kind);
+        assert_eq!(kinds, [Some(StateKind::Local), None, Some(StateKind::Shared)]);
+
+        let state: StateHandle<Cell<usize>> = StateHandle::Local(Rc::new(Cell::new(0)));
+        let get = |path: &'static str| Request::builder(HttpMethod::GET, path).build();
+
+        let local: Response = dispatch_with_state(&router, get("/local"), Some(state.clone()));
+        assert_eq!(local.get_body(), Some(&b"1"[..]));
+
+        let either: Response = dispatch_with_state(&router, get("/either"), Some(state.clone()));
+        assert_eq!(either.get_body(), Some(&b"1"[..]));
+
+        let shared: Response = dispatch_with_state(&router, get("/shared"), Some(state));
+        assert_eq!(shared.status(), HttpStatus::InternalServerError);
+    }
+
+    fn is_open<T>(req: &Request, _: Option<&T>) -> bool {
+        !req.headers.contains_key("x-maintenance")
+    }
+
+    #[test]
+    fn test_guard_runs_before_handler() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/reports", guard = "is_open")]
+        async fn reports() -> Response<'static> {
+            Response::new(HttpStatus::Ok).text("reports")
+        }
+
+        #[post("/imports", guard = "is_open", guard_status = 503)]
+        async fn imports(_body: Bytes<'_>) -> Response<'static> {
+            panic!("Guarded handler should not run");
+        }
+
+        router.register(reports);
+        router.register(imports);
+
+        let open: Response = dispatch(&router, Request::builder(HttpMethod::GET, "/reports").build());
+        assert_eq!(open.status(), HttpStatus::Ok);
+        assert_eq!(open.get_body(), Some(&b"reports"[..]));
+
+        let closed: Request = Request::builder(HttpMethod::GET, "/reports")
+            .header("X-Maintenance", "1")
+            .build();
+        assert_eq!(dispatch(&router, closed).status(), HttpStatus::Forbidden);
+
+        let closed: Request = Request::builder(HttpMethod::POST, "/imports")
+            .header("X-Maintenance", "1")
+            .build();
+        let response: Response = dispatch(&router, closed);
+        assert_eq!(response.status(), HttpStatus::ServiceUnavailable);
+        assert_eq!(response.get_body(), Some(&b"Service Unavailable"[..]));
+    }
+
+    #[test]
+    fn test_conflicting_param_names_are_rejected() {
+        let mut router: Router<State> = Router::new();
+
+        #[get("/users/:id")]
+        async fn user() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/users/:user_id/posts")]
+        async fn user_posts() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/users/:id/comments")]
+        async fn user_comments() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        router.register(user);
+        router.register(user_comments);
+
+        let route: super::Route<State> = Router::expand_routable(user_posts()).next().unwrap();
+        let error: RouterError = router.add_route(route).unwrap_err();
+
+        assert!(matches!(
+            &error,
+            RouterError::ParamConflict { existing, incoming, .. } if existing == "id" && incoming == "user_id"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "[GET] - \"/users/:user_id/posts\": parameter \":user_id\" conflicts with \":id\" registered at the same position"
+        );
+    }
+
+    #[test]
+    fn test_malformed_patterns_are_rejected() {
+        #[get("/files/:")]
+        async fn unnamed() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/files/:a:b")]
+        async fn nested() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/files/*rest/raw")]
+        async fn wildcard() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        #[get("/files/*rest")]
+        async fn trailing_wildcard() -> Response<'static> {
+            Response::new(HttpStatus::Ok)
+        }
+
+        let add = |routable: Routable<State>| -> Result<(), RouterError> {
+            let route: super::Route<State> = Router::expand_routable(routable).next().unwrap();
+            Router::new().add_route(route)
+        };
+
+        assert!(matches!(add(unnamed()), Err(RouterError::InvalidPattern(_))));
+        assert!(matches!(add(nested()), Err(RouterError::InvalidPattern(_))));
+        assert!(matches!(add(wildcard()), Err(RouterError::WildcardUnsupported(_))));
+        assert!(matches!(add(trailing_wildcard()), Err(RouterError::WildcardUnsupported(_))));
+    }
 }
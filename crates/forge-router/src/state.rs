@@ -0,0 +1,59 @@
+use std::fmt::{self, Display, Formatter};
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// The application state a handler is called with. `Shared` is the single value every worker
+/// thread is given, and `Local` a value built on the worker handling the request, which never
+/// leaves that thread and so may hold `Rc`, `Cell` or `RefCell` instead of locks.
+///
+/// Handlers that take `&T` accept either. Those that take `Arc<T>` only accept `Shared` state and
+/// those that take `Rc<T>` only `Local` state; `RouteMeta::state_kind` records which one.
+pub enum StateHandle<T> {
+    Shared(Arc<T>),
+    Local(Rc<T>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateKind {
+    Shared,
+    Local,
+}
+
+impl<T> StateHandle<T> {
+    pub fn kind(&self) -> StateKind {
+        match self {
+            Self::Shared(_) => StateKind::Shared,
+            Self::Local(_) => StateKind::Local,
+        }
+    }
+}
+
+impl<T> Clone for StateHandle<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Shared(state) => Self::Shared(Arc::clone(state)),
+            Self::Local(state) => Self::Local(Rc::clone(state)),
+        }
+    }
+}
+
+impl<T> Deref for StateHandle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match self {
+            Self::Shared(state) => state,
+            Self::Local(state) => state,
+        }
+    }
+}
+
+impl Display for StateKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Shared => "shared `Arc<T>`",
+            Self::Local => "per-worker `Rc<T>`",
+        })
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use forge_http::{HttpStatus, Response};
+use forge_macros::get;
+use forge_router::Router;
+use forge_server::{Listener, ListenerOptions};
+
+const ROUNDS: usize = 200;
+const PIPELINE_DEPTH: usize = 32;
+const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+#[get("/")]
+async fn hello() -> Response<'static> {
+    Response::new(HttpStatus::Ok).text("hello")
+}
+
+fn start(port: u16, pipeline_batching: bool) {
+    let mut router: Router<()> = Router::new();
+    router.register(hello);
+
+    let options: ListenerOptions = ListenerOptions {
+        port,
+        host: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        dual_stack: false,
+        server_timing: false,
+        read_timeout: Duration::from_secs(5),
+        header_timeout: Duration::from_secs(5),
+        max_uri_length: 8 * 1024,
+        pipeline_batching,
+        trusted_proxies: Default::default(),
+        threads: Some(1),
+    };
+
+    thread::spawn(move || Listener::new(router, options).run().expect("listener failed"));
+}
+
+fn connect(port: u16) -> TcpStream {
+    let addr: SocketAddr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+
+    for _ in 0..50 {
+        if let Ok(stream) = TcpStream::connect(addr) {
+            return stream;
+        }
+
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    panic!("listener on port {port} did not start");
+}
+
+// Every round writes `PIPELINE_DEPTH` requests at once and waits for all of their responses.
+fn measure(port: u16) -> Duration {
+    let mut stream: TcpStream = connect(port);
+    stream.set_nodelay(true).unwrap();
+
+    let batch: Vec<u8> = REQUEST.repeat(PIPELINE_DEPTH);
+    let mut buffer: Vec<u8> = vec![0; 64 * 1024];
+    let start: Instant = Instant::now();
+
+    for _ in 0..ROUNDS {
+        stream.write_all(&batch).unwrap();
+        let mut responses: usize = 0;
+
+        while responses < PIPELINE_DEPTH {
+            let read: usize = stream.read(&mut buffer).unwrap();
+            assert!(read > 0, "connection closed mid-pipeline");
+            responses += buffer[..read]
+                .windows(5)
+                .filter(|window: &&[u8]| *window == b"hello")
+                .count();
+        }
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    start(38_301, false);
+    start(38_302, true);
+
+    for (label, port) in [("unbatched", 38_301), ("batched", 38_302)] {
+        let elapsed: Duration = measure(port);
+        let requests: f64 = (ROUNDS * PIPELINE_DEPTH) as f64;
+
+        println!(
+            "{label:>9}: {:>8.2} ms, {:>10.0} req/s",
+            elapsed.as_secs_f64() * 1000.0,
+            requests / elapsed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::mem;
+
+use forge_http::framing::{ChunkedDecoder, Decoded};
+use forge_http::{BodyFraming, BodySender, HttpError, HttpParseError, HttpStatus};
+
+enum Remaining {
+    Length(usize),
+    Chunked(ChunkedDecoder),
+    Done,
+    Failed,
+}
+
+/// Moves body bytes read from the connection into a `stream_body` handler's `BodyStream`, one
+/// chunk per time the handler asks. Bytes read past the end of the body stay in `pending` so they
+/// can be kept as the start of the next request.
+pub(crate) struct BodyPump {
+    remaining: Remaining,
+    pending: Vec<u8>,
+    received: usize,
+    sender: BodySender,
+}
+
+impl BodyPump {
+    pub(crate) fn new(framing: BodyFraming, pending: Vec<u8>, sender: BodySender) -> Self {
+        let remaining: Remaining = match framing {
+            BodyFraming::Empty | BodyFraming::Length(0) => Remaining::Done,
+            BodyFraming::Length(length) => Remaining::Length(length),
+            BodyFraming::Chunked => Remaining::Chunked(ChunkedDecoder::new()),
+        };
+
+        if matches!(remaining, Remaining::Done) {
+            sender.finish();
+        }
+
+        Self {
+            remaining,
+            received: pending.len(),
+            pending,
+            sender,
+        }
+    }
+
+    pub(crate) fn extend(&mut self, bytes: &[u8]) {
+        self.received += bytes.len();
+        self.pending.extend_from_slice(bytes);
+    }
+
+    /// Every byte handed to the pump, including any read past the end of the body.
+    pub(crate) fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Whether the handler is waiting on a chunk that the bytes read so far cannot provide.
+    pub(crate) fn needs_read(&self) -> bool {
+        matches!(self.remaining, Remaining::Length(_) | Remaining::Chunked(_)) && self.sender.wants_data()
+    }
+
+    /// Hands the handler its next chunk if it is waiting for one and enough bytes have been read.
+    /// Returns whether the stream changed, in which case the handler should be polled again.
+    pub(crate) fn feed(&mut self) -> bool {
+        if !self.sender.wants_data() {
+            return false;
+        }
+
+        match self.decode() {
+            Ok(Decoded::Data(chunk)) => self.sender.send(chunk),
+            Ok(Decoded::Done) => self.sender.finish(),
+            Ok(Decoded::NeedMore) => return false,
+            Err(e) => {
+                self.remaining = Remaining::Failed;
+                self.sender
+                    .fail(HttpError::new(HttpStatus::BadRequest, format!("Invalid request body: {e}")));
+            }
+        }
+
+        true
+    }
+
+    /// Reads past whatever the handler left unread, using only bytes that are already buffered.
+    /// Returns the bytes that follow the body when it ends within them, or `None` when the rest
+    /// of the body has not arrived yet (or was malformed) and the connection cannot be reused.
+    pub(crate) fn into_leftover(mut self) -> Option<Vec<u8>> {
+        loop {
+            match self.decode() {
+                Ok(Decoded::Data(_)) => continue,
+                Ok(Decoded::Done) => return Some(mem::take(&mut self.pending)),
+                Ok(Decoded::NeedMore) | Err(_) => return None,
+            }
+        }
+    }
+
+    fn decode(&mut self) -> Result<Decoded, HttpParseError> {
+        match &mut self.remaining {
+            Remaining::Done => Ok(Decoded::Done),
+            Remaining::Failed => Ok(Decoded::NeedMore),
+            Remaining::Length(_) if self.pending.is_empty() => Ok(Decoded::NeedMore),
+            Remaining::Length(length) => {
+                let len: usize = (*length).min(self.pending.len());
+                let chunk: Vec<u8> = self.pending.drain(..len).collect();
+                *length -= len;
+
+                if *length == 0 {
+                    self.remaining = Remaining::Done;
+                }
+
+                Ok(Decoded::Data(chunk))
+            }
+            Remaining::Chunked(decoder) => {
+                let decoded: Decoded = decoder.decode(&mut self.pending)?;
+
+                if decoded == Decoded::Done {
+                    self.remaining = Remaining::Done;
+                }
+
+                Ok(decoded)
+            }
+        }
+    }
+}
@@ -1,47 +1,802 @@
+use std::any::Any;
+use std::borrow::Cow;
+use std::future::{self, Future};
 use std::io::Error;
 use std::io::ErrorKind;
-use std::str::{self, Utf8Error};
+use std::mem;
+use std::net::SocketAddr;
+use std::pin::pin;
+use std::str;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-use super::ListenerError;
-use forge_http::{HttpError, HttpStatus, Request, Response};
-use forge_router::{BoxedHandler, Router};
-use forge_utils::PathMatch;
-use monoio::{io::AsyncReadRent, net::TcpStream};
+use super::body_pump::BodyPump;
+use super::listener::{BUFFER_SIZE, MAX_HEAD_SIZE};
+use super::metrics::{MetricsHook, TrafficCounters};
+use super::read_buffer::ReadBuffer;
+use super::{ListenerError, RequestMetrics, RouterHandle, SecurityHeaders};
+use forge_http::{
+    BodyFraming, BodySender, BodyStream, HttpError, HttpMethod, HttpStatus, HttpVersion, Request, Response,
+    ResponseSink, ResponseWriter, ServerTiming, TrustedProxies, framing,
+};
+use forge_router::{Route, RouteMeta, Router, StateHandle};
+use forge_utils::{CatchUnwind, PathMatch, panic_message};
+use monoio::BufResult;
+use monoio::io::{AsyncWriteRent, AsyncWriteRentExt, CancelableAsyncReadRent, Canceller};
+use monoio::net::TcpStream;
+use monoio::time::{self, Elapsed};
 
-pub struct Connection<T> {
-    pub stream: TcpStream,
-    pub state: Option<Arc<T>>,
+type TimedRead = Result<BufResult<usize, Vec<u8>>, Elapsed>;
+type HandlerOutput<'r> = Option<Result<Response<'r>, Box<dyn Any + Send>>>;
+
+/// While a handler runs, the connection keeps a read pending on the stream so a client that
+/// disconnects mid-request is noticed. The handler future is then dropped instead of running to
+/// completion, which releases whatever it holds (such as a database pool permit) right away. Bytes
+/// the client sends in the meantime are kept as the start of its next request. A client that only
+/// shuts down its sending side still gets its response, and the connection is closed after it.
+///
+/// Routes registered with `stream_body = true` are dispatched as soon as their head has arrived.
+/// The body is then read only while the handler waits on its `BodyStream`, so no disconnect probe
+/// runs for them; if the handler returns before the whole body was read, the connection is closed
+/// instead of reading and discarding the rest.
+///
+/// Routes registered with `stream_response = true` get a `ResponseWriter`, and what the handler
+/// writes to it is sent while the handler is still running. No disconnect probe runs for them
+/// either; a client that went away is noticed on the next write.
+///
+/// With `pipeline_batching`, a response is held back while the next pipelined request is already
+/// buffered, and the held responses go out in one write once no complete request is left to handle
+/// (or `max_write_buffer` bytes have piled up).
+///
+/// When `router_handle` is set, `router` is reloaded from it before each request that follows the
+/// first one on a keep-alive connection.
+pub struct Connection<T, S = TcpStream> {
+    pub secure: bool,
+    pub stream: S,
+    pub peer_addr: SocketAddr,
+    pub trusted_proxies: Arc<TrustedProxies>,
+    pub state: Option<StateHandle<T>>,
     pub router: Arc<Router<T>>,
+    pub router_handle: Option<RouterHandle<T>>,
+    pub security_headers: Option<Arc<SecurityHeaders>>,
+    pub default_headers: Option<Arc<[(String, String)]>>,
+    pub default_content_type: Option<Arc<str>>,
+    pub(crate) metrics: Option<Arc<MetricsHook>>,
+    pub(crate) traffic: Option<Arc<TrafficCounters>>,
+    pub server_timing: bool,
+    pub read_timeout: Duration,
+    pub header_timeout: Duration,
+    pub max_uri_length: usize,
+    pub request_timeout: Option<Duration>,
+    pub max_body_size: Option<usize>,
+    pub max_write_buffer: usize,
+    pub pipeline_batching: bool,
+    pub access_log: bool,
+    pub(crate) buffered: ReadBuffer,
+    pub(crate) pending_writes: Vec<u8>,
+    pub(crate) probe_buffer: Vec<u8>,
 }
 
-impl<T> Connection<T>
+impl<T, S> Connection<T, S>
 where
-    T: Send + Sync + 'static,
+    T: 'static,
+    S: CancelableAsyncReadRent + AsyncWriteRent,
 {
-    pub async fn process_request(&mut self, buffer: Vec<u8>) -> Result<Vec<u8>, ListenerError> {
-        let (bytes_read, buffer): (usize, Vec<u8>) = self.read_request_bytes(buffer).await?;
-        let raw_bytes: &[u8] = &buffer[..bytes_read];
+    pub async fn process_request(&mut self, buffer: Vec<u8>) -> Result<(Vec<u8>, bool), ListenerError> {
+        let (buffer, read_start, streaming): (Vec<u8>, Instant, bool) = self.read_request_bytes(buffer).await?;
+        let raw_bytes: &[u8] = &buffer;
+        let parse_start: Instant = Instant::now();
+
+        let mut request: Request = match streaming {
+            true => Request::parse_head(raw_bytes).map_err(HttpError::from)?,
+            false => Request::new(raw_bytes)?,
+        };
 
-        let raw_request: &str = str::from_utf8(raw_bytes)
-            .map_err(|e: Utf8Error| HttpError::new(HttpStatus::BadRequest, format!("Invalid UTF-8 sequence: {e:?}")))?;
+        request.set_origin(self.peer_addr.ip(), self.secure, &self.trusted_proxies);
+        let mut body_pump: Option<BodyPump> = None;
 
-        let mut request: Request = Request::new(raw_request)?;
+        if streaming {
+            // The buffer holds only the head here, which `parse_head` found to be valid UTF-8.
+            let raw_head: &str = str::from_utf8(raw_bytes).unwrap_or_default();
+            let framing: BodyFraming =
+                BodyFraming::from_headers(framing::header_fields(raw_head)).map_err(HttpError::from)?;
+            let (sender, body_stream): (BodySender, BodyStream) = BodyStream::channel();
+            request.set_body_stream(body_stream);
+            body_pump = Some(BodyPump::new(framing, self.buffered.take_all(), sender));
+        }
 
-        let route: PathMatch<BoxedHandler<T>> = self
+        let route_start: Instant = Instant::now();
+
+        if request.path.len() > self.max_uri_length {
+            return Err(HttpError::new(
+                HttpStatus::UriTooLong,
+                format!("Request target exceeds {} bytes", self.max_uri_length),
+            )
+            .into());
+        }
+
+        let route: PathMatch<Route<T>> = self
             .router
-            .get_route(request.path, &request.method)
-            .ok_or_else(|| HttpError::new(HttpStatus::NotFound, "The requested resource could not be found"))?;
+            .get_host_route(
+                request.headers.get("host").map(|host: &Cow<str>| host.as_ref()),
+                request.path,
+                &request.method,
+            )
+            .ok_or_else(|| self.router.no_route_error(&request.method))?;
 
+        let route_end: Instant = Instant::now();
         request.set_params(route.params);
-        let response: Response = route.value.call(request, self.state.clone()).await;
-        response.send(&mut self.stream).await?;
+        request.set_path_tail(route.tail);
+        request.set_matched_path(route.value.path);
+
+        let server_timing: Option<ServerTiming> = self.server_timing.then(|| {
+            let server_timing: ServerTiming = ServerTiming::new();
+            server_timing.record("parse", route_start - parse_start);
+            request.set_server_timing(server_timing.clone());
+            server_timing
+        });
+
+        let version: HttpVersion = request.version;
+        let mut keep_alive: bool = request.keep_alive();
+        let accept_encoding: Option<Cow<str>> = request.headers.get("accept-encoding").cloned();
+        let conditional: bool = matches!(request.method, HttpMethod::GET | HttpMethod::HEAD);
+        let conditional_header = |key: &str| request.headers.get(key).filter(|_| conditional).cloned();
+        let if_none_match: Option<Cow<str>> = conditional_header("if-none-match");
+        let if_modified_since: Option<Cow<str>> = conditional_header("if-modified-since");
+        let range: Option<Cow<str>> = conditional_header("range");
+
+        let method: HttpMethod = request.method;
+        let path: &str = request.path;
+        let request_timeout: Option<Duration> = self.request_timeout;
+        let response_sink: Option<ResponseSink> = route.value.meta.stream_response.then(|| {
+            let (sink, writer): (ResponseSink, ResponseWriter) = ResponseWriter::channel();
+            request.set_response_writer(writer);
+            sink
+        });
+
+        let handler_start: Instant = Instant::now();
+        let handler = CatchUnwind::new(route.value.handler.call(request, self.state.clone()));
+
+        let handler = async move {
+            match request_timeout {
+                Some(request_timeout) => time::timeout(request_timeout, handler).await.ok(),
+                None => Some(handler.await),
+            }
+        };
+
+        // A streamed body has no length to frame it by, so HTTP/1.0 clients read it until the
+        // connection closes.
+        let chunked: bool = version == HttpVersion::Http11;
+        let mut send_body: bool = !matches!(method, HttpMethod::HEAD);
+        let mut bytes_out: usize = 0;
+        let mut stream_keep_alive: bool = keep_alive && chunked;
+
+        let mut prepare_head = |head: Response<'static>| {
+            Self::connection_headers(
+                head.version(version),
+                self.default_headers.as_deref(),
+                self.security_headers.as_deref(),
+                self.secure,
+                &mut stream_keep_alive,
+                version,
+            )
+        };
+
+        let output: HandlerOutput = match (&mut body_pump, &response_sink) {
+            (Some(body_pump), _) => Self::drive_body(&mut self.stream, self.read_timeout, body_pump, handler).await?,
+            (None, Some(sink)) => {
+                Self::write_pending(&mut self.stream, &mut self.pending_writes).await?;
+                Self::drive_response(
+                    &mut self.stream,
+                    sink,
+                    chunked,
+                    &mut send_body,
+                    &mut bytes_out,
+                    &mut prepare_head,
+                    handler,
+                )
+                .await?
+            }
+            (None, None) => {
+                let (output, peer_open): (HandlerOutput, bool) =
+                    Self::until_disconnect(&mut self.stream, &mut self.buffered, &mut self.probe_buffer, handler)
+                        .await?;
+
+                keep_alive &= peer_open;
+                output
+            }
+        };
+
+        let mut bytes_in: usize = buffer.len();
+
+        if let Some(body_pump) = body_pump {
+            bytes_in += body_pump.received();
+
+            match body_pump.into_leftover() {
+                Some(leftover) => {
+                    bytes_in -= leftover.len();
+                    self.buffered.extend(&leftover);
+                }
+                None => keep_alive = false,
+            }
+        }
+
+        let handler_duration: Duration = handler_start.elapsed();
+        let write_start: Instant = Instant::now();
+        let streamed: Option<(&ResponseSink, HttpStatus)> = response_sink
+            .as_ref()
+            .and_then(|sink: &ResponseSink| Some((sink, sink.status()?)));
+
+        let status: HttpStatus = match streamed {
+            Some((sink, status)) => {
+                // The response the handler returned is dropped, since the head already went out.
+                let completed: bool = output.is_some_and(|output: Result<Response, Box<dyn Any + Send>>| {
+                    output
+                        .inspect_err(|payload: &Box<dyn Any + Send>| {
+                            let message: &str = panic_message(payload);
+                            eprintln!("Handler for [{method}] - \"{}\" panicked: {message}", route.value.path);
+                        })
+                        .is_ok()
+                });
+
+                if completed {
+                    sink.finish();
+                }
+
+                Self::write_streamed(
+                    &mut self.stream,
+                    sink,
+                    chunked,
+                    &mut send_body,
+                    &mut bytes_out,
+                    &mut prepare_head,
+                )
+                .await?;
+
+                // The head already went out, so a handler that panicked or timed out can only have
+                // its connection closed to show the body is incomplete.
+                keep_alive = stream_keep_alive && completed;
+                status
+            }
+            None => {
+                let mut response: Response = output
+                    .map(|output: Result<Response, Box<dyn Any + Send>>| {
+                        output.unwrap_or_else(|payload: Box<dyn Any + Send>| {
+                            let message: &str = panic_message(&payload);
+                            eprintln!("Handler for [{method}] - \"{}\" panicked: {message}", route.value.path);
+                            HttpError::new(HttpStatus::InternalServerError, "Internal Server Error").into()
+                        })
+                    })
+                    .unwrap_or_else(|| {
+                        let message: String =
+                            format!("Request handler did not finish within {:?}", handler_start.elapsed());
+                        HttpError::new(HttpStatus::ServiceUnavailable, message).into()
+                    })
+                    .version(version);
+
+                if let Some(server_timing) = server_timing {
+                    server_timing.record("handler", handler_duration);
+                    response = response.header("Server-Timing", server_timing.header_value());
+                }
+
+                if let Some(content_type) = self.default_content_type.as_deref() {
+                    response = response.default_content_type(content_type);
+                }
+
+                if let Some(accept_encoding) = accept_encoding {
+                    response = response.accept_encoding(&accept_encoding);
+                }
+
+                // If-Modified-Since is ignored when If-None-Match is present (RFC 9110, section 13.2.2).
+                if let Some(if_none_match) = if_none_match {
+                    response = response.if_none_match(&if_none_match);
+                } else if let Some(if_modified_since) = if_modified_since {
+                    response = response.if_modified_since(&if_modified_since);
+                }
+
+                if let Some(range) = range {
+                    response = response.range(&range);
+                }
+
+                response = Self::connection_headers(
+                    response,
+                    self.default_headers.as_deref(),
+                    self.security_headers.as_deref(),
+                    self.secure,
+                    &mut keep_alive,
+                    version,
+                );
+
+                let status: HttpStatus = response.status();
+                let batch: bool = self.pipeline_batching
+                    && keep_alive
+                    && self.pending_writes.len() < self.max_write_buffer
+                    && framing::find_head_end(self.buffered.unread()).is_some();
+
+                if batch || !self.pending_writes.is_empty() {
+                    let held: usize = self.pending_writes.len();
+                    response.write_to(&mut self.pending_writes)?;
+                    bytes_out = self.pending_writes.len() - held;
+                } else {
+                    bytes_out = response.send_buffered(&mut self.stream, self.max_write_buffer).await?;
+                }
+
+                if !batch {
+                    Self::write_pending(&mut self.stream, &mut self.pending_writes).await?;
+                }
+
+                status
+            }
+        };
+
+        if self.access_log {
+            println!(
+                "[{method}] - \"{path}\" {} in {:?} ({bytes_in} bytes in, {bytes_out} bytes out)",
+                u16::from(status),
+                read_start.elapsed()
+            );
+        }
+
+        if let Some(traffic) = &self.traffic {
+            traffic.record(bytes_in, bytes_out);
+        }
+
+        if let Some(metrics) = &self.metrics {
+            metrics(&RequestMetrics {
+                method,
+                route: route.value.path,
+                status,
+                read: parse_start - read_start,
+                parse: route_start - parse_start,
+                route_match: route_end - route_start,
+                handler: handler_duration,
+                write: write_start.elapsed(),
+                bytes_in,
+                bytes_out,
+            });
+        }
+
+        Ok((buffer, keep_alive))
+    }
+
+    /// Adds the listener's default and security headers and the `Connection` header, clearing
+    /// `keep_alive` when the response asks for the connection to be closed.
+    fn connection_headers<'r>(
+        mut response: Response<'r>,
+        default_headers: Option<&'r [(String, String)]>,
+        security_headers: Option<&SecurityHeaders>,
+        secure: bool,
+        keep_alive: &mut bool,
+        version: HttpVersion,
+    ) -> Response<'r> {
+        if let Some(default_headers) = default_headers {
+            response = response.default_headers(
+                default_headers
+                    .iter()
+                    .map(|(key, value): &(String, String)| (key.as_str(), value.as_str())),
+            );
+        }
+
+        if let Some(security_headers) = security_headers {
+            response = security_headers.apply(response, secure);
+        }
+
+        *keep_alive &= !response
+            .get_header("connection")
+            .is_some_and(|value: &str| value.eq_ignore_ascii_case("close"));
+
+        match (*keep_alive, version) {
+            (false, _) => response.default_headers([("Connection", "close")]),
+            (true, HttpVersion::Http10) => response.default_headers([("Connection", "keep-alive")]),
+            (true, HttpVersion::Http11) => response,
+        }
+    }
+
+    /// Answers a request that failed before reaching a handler, after any responses still held
+    /// back, and leaves the connection to be closed.
+    pub(crate) async fn send_error(&mut self, e: HttpError) {
+        self.flush_pending().await.ok();
+
+        let response: Response =
+            Response::from(e).default_content_type(self.default_content_type.as_deref().unwrap_or("text/plain"));
+        let mut keep_alive: bool = false;
+        let response: Response = Self::connection_headers(
+            response,
+            self.default_headers.as_deref(),
+            self.security_headers.as_deref(),
+            self.secure,
+            &mut keep_alive,
+            HttpVersion::Http11,
+        );
+
+        response.send(&mut self.stream).await.ok();
+    }
+
+    /// Writes out the responses held back by `pipeline_batching`.
+    pub(crate) async fn flush_pending(&mut self) -> Result<(), HttpError> {
+        Self::write_pending(&mut self.stream, &mut self.pending_writes).await
+    }
+
+    async fn write_pending(stream: &mut S, pending_writes: &mut Vec<u8>) -> Result<(), HttpError> {
+        if pending_writes.is_empty() {
+            return Ok(());
+        }
+
+        let (result, mut pending): (std::io::Result<usize>, Vec<u8>) =
+            stream.write_all(std::mem::take(pending_writes)).await;
+        pending.clear();
+        *pending_writes = pending;
+
+        result
+            .map(drop)
+            .map_err(|_| HttpError::new(HttpStatus::InternalServerError, "Failed to write response"))
+    }
+
+    /// Returns a buffer holding exactly the next request, or only its head when the body is
+    /// streamed, and leaves any bytes read past it in `buffered`.
+    async fn read_request_bytes(&mut self, buffer: Vec<u8>) -> Result<(Vec<u8>, Instant, bool), ListenerError> {
+        let header_deadline: Instant = Instant::now() + self.header_timeout;
+        let mut scratch: Vec<u8> = buffer;
+
+        if self.buffered.is_empty() {
+            scratch = self.fill_buffer(scratch, Some(header_deadline)).await?;
+        }
+
+        let read_start: Instant = Instant::now();
+
+        // Kept across reads so every byte is scanned once: how far the head was searched for its
+        // end, the head's end and framing once found (no framing when it is not valid UTF-8, which
+        // the parser then rejects), and where the first incomplete chunk of a chunked body starts.
+        let mut scanned: usize = 0;
+        let mut head: Option<(usize, Option<BodyFraming>)> = None;
+        let mut next_chunk: usize = 0;
+        let mut max_body_size: Option<usize> = self.max_body_size;
+
+        loop {
+            // Only strips bytes before anything was scanned, so `scanned` stays valid.
+            self.buffered.skip_empty_lines();
+            let unread: &[u8] = self.buffered.unread();
+
+            if head.is_none() {
+                let from: usize = scanned.saturating_sub(3);
+
+                if let Some(head_end) = framing::find_head_end(&unread[from..]).map(|end: usize| from + end) {
+                    let raw_head: &[u8] = &unread[..head_end];
+                    let meta: Option<RouteMeta> = self.head_route_meta(raw_head);
+                    max_body_size = meta.and_then(|meta: RouteMeta| meta.max_body_size).or(max_body_size);
+                    Self::check_declared_body_size(raw_head, max_body_size)?;
+
+                    if meta.is_some_and(|meta: RouteMeta| meta.stream_body) {
+                        return Ok((self.buffered.take(head_end, scratch), read_start, true));
+                    }
 
-        Ok(buffer)
+                    let framing: Option<BodyFraming> = match str::from_utf8(raw_head) {
+                        Ok(raw_head) => {
+                            Some(BodyFraming::from_headers(framing::header_fields(raw_head)).map_err(HttpError::from)?)
+                        }
+                        Err(_) => None,
+                    };
+
+                    head = Some((head_end, framing));
+                    next_chunk = head_end;
+                }
+
+                scanned = unread.len();
+            }
+
+            let message_len: Option<usize> = match head {
+                None => None,
+                Some((_, None)) => Some(unread.len()),
+                Some((head_end, Some(BodyFraming::Empty))) => Some(head_end),
+                Some((head_end, Some(BodyFraming::Length(length)))) => {
+                    head_end.checked_add(length).filter(|&end: &usize| end <= unread.len())
+                }
+                Some((_, Some(BodyFraming::Chunked))) => {
+                    framing::chunked_body_end(unread, &mut next_chunk).map_err(HttpError::from)?
+                }
+            };
+
+            if let Some(message_len) = message_len {
+                return Ok((self.buffered.take(message_len, scratch), read_start, false));
+            }
+
+            if let (Some((head_end, _)), Some(max_body_size)) = (head, max_body_size)
+                && unread.len() - head_end > max_body_size
+            {
+                return Err(Self::body_too_large(max_body_size).into());
+            }
+
+            let reading_head: bool = head.is_none();
+
+            if reading_head && unread.len() > MAX_HEAD_SIZE {
+                return Err(HttpError::new(
+                    HttpStatus::RequestHeaderFieldsTooLarge,
+                    format!("Request line and headers exceed {MAX_HEAD_SIZE} bytes"),
+                )
+                .into());
+            }
+
+            let deadline: Option<Instant> = reading_head.then_some(header_deadline);
+            scratch = self.fill_buffer(scratch, deadline).await?;
+        }
+    }
+
+    async fn fill_buffer(&mut self, mut scratch: Vec<u8>, deadline: Option<Instant>) -> Result<Vec<u8>, ListenerError> {
+        scratch.clear();
+        scratch.resize(BUFFER_SIZE, 0);
+
+        let (bytes, scratch): (usize, Vec<u8>) = self.read_bytes(scratch, deadline).await?;
+        self.buffered.extend(&scratch[..bytes]);
+        Ok(scratch)
+    }
+
+    fn check_declared_body_size(head: &[u8], max_body_size: Option<usize>) -> Result<(), HttpError> {
+        let Some(max_body_size) = max_body_size else {
+            return Ok(());
+        };
+
+        let framing: Option<BodyFraming> = str::from_utf8(head)
+            .ok()
+            .and_then(|head: &str| BodyFraming::from_headers(framing::header_fields(head)).ok());
+
+        match framing {
+            Some(BodyFraming::Length(length)) if length > max_body_size => Err(Self::body_too_large(max_body_size)),
+            _ => Ok(()),
+        }
+    }
+
+    fn body_too_large(max_body_size: usize) -> HttpError {
+        HttpError::new(
+            HttpStatus::PayloadTooLarge,
+            format!("Request body exceeds {max_body_size} bytes"),
+        )
+    }
+
+    /// Looks up the route of a request from its head alone, and only when some route streams its
+    /// body or sets its own body size limit, since both change how the body is read.
+    fn head_route_meta(&self, head: &[u8]) -> Option<RouteMeta> {
+        if !self.router.has_streaming_routes() && !self.router.has_route_body_limits() {
+            return None;
+        }
+
+        let request: Request = str::from_utf8(head)
+            .ok()
+            .and_then(|head: &str| Request::parse_head(head).ok())?;
+
+        self.router
+            .get_host_route(
+                request.headers.get("host").map(|host: &Cow<str>| host.as_ref()),
+                request.path,
+                &request.method,
+            )
+            .map(|route: PathMatch<Route<T>>| route.value.meta)
+    }
+
+    /// Runs a `stream_body` handler, reading from the stream only while the handler waits on a
+    /// chunk that the bytes read so far cannot provide.
+    async fn drive_body<F>(
+        stream: &mut S,
+        read_timeout: Duration,
+        body_pump: &mut BodyPump,
+        future: F,
+    ) -> Result<F::Output, ListenerError>
+    where
+        F: Future,
+    {
+        let mut future = pin!(future);
+
+        loop {
+            let finished: Option<F::Output> = future::poll_fn(|cx: &mut Context| {
+                loop {
+                    if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                        return Poll::Ready(Some(output));
+                    }
+
+                    if !body_pump.feed() {
+                        break;
+                    }
+                }
+
+                match body_pump.needs_read() {
+                    true => Poll::Ready(None),
+                    false => Poll::Pending,
+                }
+            })
+            .await;
+
+            if let Some(output) = finished {
+                return Ok(output);
+            }
+
+            let canceller: Canceller = Canceller::new();
+            let mut read = pin!(time::timeout(
+                read_timeout,
+                stream.cancelable_read(vec![0; BUFFER_SIZE], canceller.handle())
+            ));
+
+            let raced: Result<F::Output, TimedRead> = future::poll_fn(|cx: &mut Context| {
+                if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                    return Poll::Ready(Ok(output));
+                }
+
+                read.as_mut().poll(cx).map(Err)
+            })
+            .await;
+
+            match raced {
+                Ok(output) => {
+                    canceller.cancel();
+
+                    if let Ok((Ok(bytes), read_buffer)) = read.await {
+                        body_pump.extend(&read_buffer[..bytes]);
+                    }
+
+                    return Ok(output);
+                }
+                Err(Ok((Ok(bytes), read_buffer))) if bytes > 0 => body_pump.extend(&read_buffer[..bytes]),
+                Err(Ok(_)) => return Err(ListenerError::ConnectionClosed),
+                Err(Err(_)) => return Err(ListenerError::ReadTimeout(read_timeout)),
+            }
+        }
+    }
+
+    /// Runs a `stream_response` handler, sending what it hands its `ResponseWriter` as soon as it
+    /// does. Whatever it handed over in the poll it returned from is left for the caller to send.
+    async fn drive_response<'h, F, H>(
+        stream: &mut S,
+        sink: &ResponseSink,
+        chunked: bool,
+        send_body: &mut bool,
+        bytes_out: &mut usize,
+        prepare_head: &mut H,
+        future: F,
+    ) -> Result<F::Output, ListenerError>
+    where
+        F: Future,
+        H: FnMut(Response<'static>) -> Response<'h>,
+    {
+        let mut future = pin!(future);
+
+        loop {
+            let finished: Option<F::Output> = future::poll_fn(|cx: &mut Context| {
+                if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                    return Poll::Ready(Some(output));
+                }
+
+                match sink.has_output() {
+                    true => Poll::Ready(None),
+                    false => Poll::Pending,
+                }
+            })
+            .await;
+
+            if let Some(output) = finished {
+                return Ok(output);
+            }
+
+            Self::write_streamed(stream, sink, chunked, send_body, bytes_out, prepare_head).await?;
+        }
     }
 
-    async fn read_request_bytes(&mut self, buffer: Vec<u8>) -> Result<(usize, Vec<u8>), ListenerError> {
-        let (read_result, buffer): (Result<usize, Error>, Vec<u8>) = self.stream.read(buffer).await;
+    /// Sends the head, body chunks and end of body waiting in `sink`, then wakes the writer. The
+    /// body is dropped for `HEAD` requests and statuses that have none, which clears `send_body`.
+    /// What was written is added to `bytes_out`.
+    async fn write_streamed<'h, H>(
+        stream: &mut S,
+        sink: &ResponseSink,
+        chunked: bool,
+        send_body: &mut bool,
+        bytes_out: &mut usize,
+        prepare_head: &mut H,
+    ) -> Result<(), ListenerError>
+    where
+        H: FnMut(Response<'static>) -> Response<'h>,
+    {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        if let Some(head) = sink.take_head() {
+            let head: Response = prepare_head(head);
+            *send_body &= head.allows_body();
+            head.write_streaming_head(&mut buffer, chunked)?;
+        }
+
+        while let Some(chunk) = sink.pop_chunk() {
+            match (*send_body, chunked) {
+                (false, _) => {}
+                (true, true) => framing::encode_chunk(&chunk, &mut buffer),
+                (true, false) => buffer.extend_from_slice(&chunk),
+            }
+        }
+
+        if sink.wants_end() {
+            if *send_body && chunked {
+                buffer.extend_from_slice(framing::LAST_CHUNK);
+            }
+
+            sink.end();
+        }
+
+        let len: usize = buffer.len();
+
+        if len > 0 && stream.write_all(buffer).await.0.is_err() {
+            sink.close();
+            return Err(ListenerError::ConnectionClosed);
+        }
+
+        *bytes_out += len;
+
+        sink.sent();
+        Ok(())
+    }
+
+    /// Runs `future` while a read stays pending on the stream, dropping it if the read fails. The
+    /// returned flag is `false` once the client has shut down its sending side, since it can still
+    /// read the response but will send no further request.
+    async fn until_disconnect<F>(
+        stream: &mut S,
+        buffered: &mut ReadBuffer,
+        probe_buffer: &mut Vec<u8>,
+        future: F,
+    ) -> Result<(F::Output, bool), ListenerError>
+    where
+        F: Future,
+    {
+        if probe_buffer.is_empty() {
+            *probe_buffer = vec![0; BUFFER_SIZE];
+        }
+
+        let canceller: Canceller = Canceller::new();
+        let mut probe = pin!(stream.cancelable_read(mem::take(probe_buffer), canceller.handle()));
+        let mut future = pin!(future);
+
+        let first: Result<F::Output, BufResult<usize, Vec<u8>>> = future::poll_fn(|cx: &mut Context| {
+            if let Poll::Ready(output) = future.as_mut().poll(cx) {
+                return Poll::Ready(Ok(output));
+            }
+
+            probe.as_mut().poll(cx).map(Err)
+        })
+        .await;
+
+        let (output, (read_result, buffer), peer_open): (F::Output, BufResult<usize, Vec<u8>>, bool) = match first {
+            Ok(output) => {
+                canceller.cancel();
+                (output, probe.await, true)
+            }
+            Err((Err(_), buffer)) => {
+                *probe_buffer = buffer;
+                return Err(ListenerError::ConnectionClosed);
+            }
+            // A clean EOF is a client that half-closed after sending its request, so it still gets
+            // the response.
+            Err(probed) => {
+                let peer_open: bool = !matches!(probed.0, Ok(0));
+                (future.await, probed, peer_open)
+            }
+        };
+
+        if let Ok(bytes) = read_result {
+            buffered.extend(&buffer[..bytes]);
+        }
+
+        *probe_buffer = buffer;
+        Ok((output, peer_open))
+    }
+
+    async fn read_bytes(
+        &mut self,
+        buffer: Vec<u8>,
+        deadline: Option<Instant>,
+    ) -> Result<(usize, Vec<u8>), ListenerError> {
+        let timeout: Duration = deadline.map_or(self.read_timeout, |deadline: Instant| {
+            self.read_timeout
+                .min(deadline.saturating_duration_since(Instant::now()))
+        });
+
+        let (read_result, buffer): (Result<usize, Error>, Vec<u8>) = time::timeout(timeout, self.stream.read(buffer))
+            .await
+            .map_err(|_: Elapsed| ListenerError::ReadTimeout(timeout))?;
 
         let bytes: usize = read_result.map_err(|e: Error| match e.kind() {
             ErrorKind::ConnectionReset | ErrorKind::BrokenPipe => ListenerError::ConnectionClosed,
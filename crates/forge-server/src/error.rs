@@ -1,6 +1,7 @@
-use std::{io, net::SocketAddr};
+use std::{io, net::SocketAddr, time::Duration};
 
-use forge_http::HttpError;
+use forge_http::{HttpError, HttpMethod};
+use forge_router::{RouterError, StateKind};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -8,9 +9,15 @@ pub enum ListenerError {
     #[error(transparent)]
     Http(#[from] HttpError),
 
+    #[error("failed to register route {0}")]
+    Router(#[from] RouterError),
+
     #[error("connection closed by peer")]
     ConnectionClosed,
 
+    #[error("timed out after {0:?} waiting for request data")]
+    ReadTimeout(Duration),
+
     #[error("failed to start runtime on worker #{0}: {1:?}")]
     Runtime(usize, io::Error),
 
@@ -19,4 +26,25 @@ pub enum ListenerError {
 
     #[error("worker #{0} panicked: {1}")]
     ThreadPanic(usize, String),
+
+    #[error("route [{0}] - \"{1}\" requires state, but the listener has none (see `with_state`)")]
+    MissingState(HttpMethod, &'static str),
+
+    #[error("route [{0}] - \"{1}\" takes {2} state, which the listener's state cannot provide")]
+    StateKindMismatch(HttpMethod, &'static str, StateKind),
+
+    #[error("invalid listener options: \"{0}\" must be greater than 0")]
+    InvalidOptions(&'static str),
+}
+
+#[derive(Debug, Error)]
+pub enum FrameError {
+    #[error("frame of {len} bytes exceeds the {max} byte limit")]
+    TooLarge { len: usize, max: usize },
+
+    #[error("connection closed after {received} of {expected} frame bytes")]
+    Truncated { expected: usize, received: usize },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
 }
@@ -0,0 +1,177 @@
+use std::io::Error;
+
+use super::FrameError;
+use monoio::io::{AsyncReadRent, AsyncWriteRent, AsyncWriteRentExt};
+
+const LENGTH_PREFIX: usize = 4;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Reads one message framed by a 4-byte big-endian length prefix. Returns `None` when the peer
+/// closes the connection between two frames, and an error when it closes in the middle of one or
+/// announces a frame larger than `max_len` (which is then left unread).
+///
+/// Nothing past the end of the frame is read, so frames can be read one after another from the
+/// same stream without a buffer in between.
+pub async fn read_frame<S>(stream: &mut S, max_len: usize) -> Result<Option<Vec<u8>>, FrameError>
+where
+    S: AsyncReadRent,
+{
+    let Some(prefix) = read_exact(stream, LENGTH_PREFIX, true).await? else {
+        return Ok(None);
+    };
+
+    let len: usize = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+
+    if len > max_len {
+        return Err(FrameError::TooLarge { len, max: max_len });
+    }
+
+    Ok(Some(read_exact(stream, len, false).await?.unwrap_or_default()))
+}
+
+/// Writes `message` behind its 4-byte big-endian length, in a single write.
+pub async fn write_frame<S>(stream: &mut S, message: &[u8]) -> Result<(), FrameError>
+where
+    S: AsyncWriteRent,
+{
+    let len: u32 = u32::try_from(message.len()).map_err(|_| FrameError::TooLarge {
+        len: message.len(),
+        max: u32::MAX as usize,
+    })?;
+
+    let mut frame: Vec<u8> = Vec::with_capacity(LENGTH_PREFIX + message.len());
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(message);
+
+    let (result, _): (Result<usize, Error>, Vec<u8>) = stream.write_all(frame).await;
+    result?;
+    Ok(())
+}
+
+/// Reads exactly `len` bytes, never asking the stream for more than are still missing. With
+/// `allow_eof`, a stream that ends before the first byte yields `None` instead of an error.
+async fn read_exact<S>(stream: &mut S, len: usize, allow_eof: bool) -> Result<Option<Vec<u8>>, FrameError>
+where
+    S: AsyncReadRent,
+{
+    let mut message: Vec<u8> = Vec::with_capacity(len);
+
+    while message.len() < len {
+        // A boxed slice offers the stream exactly its length, while a `Vec` offers its capacity.
+        let scratch: Box<[u8]> = vec![0; (len - message.len()).min(READ_CHUNK_SIZE)].into_boxed_slice();
+        let (result, read): (Result<usize, Error>, Box<[u8]>) = stream.read(scratch).await;
+        let bytes: usize = result?;
+
+        if bytes == 0 {
+            return match allow_eof && message.is_empty() {
+                true => Ok(None),
+                false => Err(FrameError::Truncated {
+                    expected: len,
+                    received: message.len(),
+                }),
+            };
+        }
+
+        message.extend_from_slice(&read[..bytes]);
+    }
+
+    Ok(Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monoio::BufResult;
+    use monoio::buf::{IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+    use std::collections::VecDeque;
+    use std::future::{self, Future};
+    use std::io;
+
+    #[derive(Default)]
+    struct MemoryStream {
+        reads: VecDeque<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl AsyncReadRent for MemoryStream {
+        fn read<T: IoBufMut>(&mut self, mut buf: T) -> impl Future<Output = BufResult<usize, T>> {
+            let mut chunk: Vec<u8> = self.reads.pop_front().unwrap_or_default();
+            let len: usize = chunk.len().min(buf.bytes_total());
+
+            unsafe {
+                std::ptr::copy_nonoverlapping(chunk.as_ptr(), buf.write_ptr(), len);
+                buf.set_init(len);
+            }
+
+            if len < chunk.len() {
+                self.reads.push_front(chunk.split_off(len));
+            }
+
+            future::ready((Ok(len), buf))
+        }
+
+        fn readv<T: IoVecBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+            future::ready((Err(io::ErrorKind::Unsupported.into()), buf))
+        }
+    }
+
+    impl AsyncWriteRent for MemoryStream {
+        fn write<T: IoBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+            let bytes: &[u8] = unsafe { std::slice::from_raw_parts(buf.read_ptr(), buf.bytes_init()) };
+            self.written.extend_from_slice(bytes);
+            future::ready((Ok(bytes.len()), buf))
+        }
+
+        fn writev<T: IoVecBuf>(&mut self, buf_vec: T) -> impl Future<Output = BufResult<usize, T>> {
+            future::ready((Err(io::ErrorKind::Unsupported.into()), buf_vec))
+        }
+
+        fn flush(&mut self) -> impl Future<Output = io::Result<()>> {
+            future::ready(Ok(()))
+        }
+
+        fn shutdown(&mut self) -> impl Future<Output = io::Result<()>> {
+            future::ready(Ok(()))
+        }
+    }
+
+    #[monoio::test]
+    async fn test_frames_round_trip_across_split_reads() {
+        let mut stream: MemoryStream = MemoryStream::default();
+        write_frame(&mut stream, b"hello").await.unwrap();
+        write_frame(&mut stream, b"").await.unwrap();
+        assert_eq!(stream.written, b"\0\0\0\x05hello\0\0\0\0");
+
+        let written: Vec<u8> = std::mem::take(&mut stream.written);
+        stream.reads = [&written[..2], &written[2..7], &written[7..]]
+            .map(<[u8]>::to_vec)
+            .into();
+
+        assert_eq!(read_frame(&mut stream, 16).await.unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(read_frame(&mut stream, 16).await.unwrap(), Some(Vec::new()));
+        assert_eq!(read_frame(&mut stream, 16).await.unwrap(), None);
+    }
+
+    #[monoio::test]
+    async fn test_oversized_and_truncated_frames_fail() {
+        let mut stream: MemoryStream = MemoryStream {
+            reads: [b"\0\0\x01\0".to_vec()].into(),
+            written: Vec::new(),
+        };
+
+        assert!(matches!(
+            read_frame(&mut stream, 16).await,
+            Err(FrameError::TooLarge { len: 256, max: 16 })
+        ));
+
+        stream.reads = [b"\0\0\0\x05hel".to_vec()].into();
+
+        assert!(matches!(
+            read_frame(&mut stream, 16).await,
+            Err(FrameError::Truncated {
+                expected: 5,
+                received: 3
+            })
+        ));
+    }
+}
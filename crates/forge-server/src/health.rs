@@ -0,0 +1,176 @@
+use std::{fmt::Display, future::Future, sync::Arc};
+
+use forge_http::{HttpMethod, HttpStatus, Request, Response};
+use forge_router::{Handler, Route, RouteMeta, Router, RouterError, StateHandle, handler::LocalBoxFuture};
+
+type ReadinessCheck<T> = dyn Fn(StateHandle<T>) -> LocalBoxFuture<'static, Result<(), String>> + Send + Sync;
+
+const DEFAULT_LIVENESS_PATH: &str = "/healthz";
+const DEFAULT_READINESS_PATH: &str = "/readyz";
+
+pub struct HealthChecks<T> {
+    liveness_path: &'static str,
+    readiness_path: &'static str,
+    readiness: Option<Arc<ReadinessCheck<T>>>,
+}
+
+impl<T> Default for HealthChecks<T>
+where
+    T: 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> HealthChecks<T>
+where
+    T: 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            readiness: None,
+            liveness_path: DEFAULT_LIVENESS_PATH,
+            readiness_path: DEFAULT_READINESS_PATH,
+        }
+    }
+
+    pub fn liveness_path(mut self, path: &'static str) -> Self {
+        self.liveness_path = path;
+        self
+    }
+
+    pub fn readiness_path(mut self, path: &'static str) -> Self {
+        self.readiness_path = path;
+        self
+    }
+
+    pub fn readiness<F, K, E>(mut self, check: F) -> Self
+    where
+        F: Fn(StateHandle<T>) -> K + Send + Sync + 'static,
+        K: Future<Output = Result<(), E>> + 'static,
+        E: Display,
+    {
+        self.readiness = Some(Arc::new(move |state: StateHandle<T>| {
+            let future: K = check(state);
+            Box::pin(async move { future.await.map_err(|e: E| e.to_string()) })
+        }));
+
+        self
+    }
+
+    pub fn register(self, router: &mut Router<T>) -> Result<(), RouterError> {
+        router.add_route(Route {
+            path: self.liveness_path,
+            method: HttpMethod::GET,
+            host: None,
+            meta: RouteMeta::default(),
+            handler: Box::new(Liveness),
+        })?;
+
+        router.add_route(Route {
+            path: self.readiness_path,
+            method: HttpMethod::GET,
+            host: None,
+            meta: RouteMeta::default(),
+            handler: Box::new(Readiness { check: self.readiness }),
+        })
+    }
+}
+
+struct Liveness;
+
+impl<T> Handler<T> for Liveness
+where
+    T: 'static,
+{
+    fn call<'a>(&'a self, _: Request<'a>, _: Option<StateHandle<T>>) -> LocalBoxFuture<'a, Response<'a>> {
+        Box::pin(async { Response::new(HttpStatus::Ok).text("OK") })
+    }
+}
+
+struct Readiness<T> {
+    check: Option<Arc<ReadinessCheck<T>>>,
+}
+
+impl<T> Handler<T> for Readiness<T>
+where
+    T: 'static,
+{
+    fn call<'a>(&'a self, _: Request<'a>, state: Option<StateHandle<T>>) -> LocalBoxFuture<'a, Response<'a>> {
+        Box::pin(async move {
+            let Some(check) = &self.check else {
+                return Response::new(HttpStatus::Ok).text("OK");
+            };
+
+            let Some(state) = state else {
+                return Response::new(HttpStatus::ServiceUnavailable)
+                    .text("Application state is required for the readiness check, but no state was configured");
+            };
+
+            match check(state).await {
+                Ok(()) => Response::new(HttpStatus::Ok).text("OK"),
+                Err(e) => Response::new(HttpStatus::ServiceUnavailable).text(e),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{self, Ready};
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::{Context, Poll, Waker};
+
+    fn get<'a>(router: &'a Router<AtomicBool>, path: &'a str, state: Option<StateHandle<AtomicBool>>) -> Response<'a> {
+        let mut cx: Context = Context::from_waker(Waker::noop());
+
+        match pin!(router.dispatch(Request::builder(HttpMethod::GET, path).build(), state)).poll(&mut cx) {
+            Poll::Ready(response) => response,
+            Poll::Pending => panic!("Health check should complete without suspending"),
+        }
+    }
+
+    fn is_ready(state: StateHandle<AtomicBool>) -> Ready<Result<(), String>> {
+        future::ready(match state.load(Ordering::SeqCst) {
+            true => Ok(()),
+            false => Err(String::from("database is unreachable")),
+        })
+    }
+
+    #[test]
+    fn test_liveness_and_readiness_answer_ok_without_a_check() {
+        let mut router: Router<AtomicBool> = Router::new();
+        HealthChecks::new()
+            .liveness_path("/live")
+            .register(&mut router)
+            .unwrap();
+
+        assert_eq!(get(&router, "/live", None).status(), HttpStatus::Ok);
+        assert_eq!(get(&router, "/healthz", None).status(), HttpStatus::NotFound);
+
+        let ready: Response = get(&router, DEFAULT_READINESS_PATH, None);
+        assert_eq!(ready.status(), HttpStatus::Ok);
+        assert_eq!(ready.get_body(), Some(&b"OK"[..]));
+    }
+
+    #[test]
+    fn test_readiness_follows_its_check() {
+        let mut router: Router<AtomicBool> = Router::new();
+        HealthChecks::new().readiness(is_ready).register(&mut router).unwrap();
+
+        let state: StateHandle<AtomicBool> = StateHandle::Shared(Arc::new(AtomicBool::new(true)));
+        assert_eq!(get(&router, "/readyz", Some(state.clone())).status(), HttpStatus::Ok);
+
+        state.store(false, Ordering::SeqCst);
+        let unready: Response = get(&router, "/readyz", Some(state.clone()));
+        assert_eq!(unready.status(), HttpStatus::ServiceUnavailable);
+        assert_eq!(unready.get_body(), Some(&b"database is unreachable"[..]));
+
+        // Liveness does not depend on the readiness check.
+        assert_eq!(get(&router, "/healthz", Some(state)).status(), HttpStatus::Ok);
+        assert_eq!(get(&router, "/readyz", None).status(), HttpStatus::ServiceUnavailable);
+    }
+}
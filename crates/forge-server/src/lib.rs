@@ -1,7 +1,22 @@
+mod body_pump;
 pub mod connection;
 pub mod error;
+pub mod frame;
+pub mod health;
 pub mod listener;
+pub mod metrics;
+pub mod raw_listener;
+mod read_buffer;
+pub mod router_handle;
+pub mod security_headers;
+mod state_source;
 
 pub use connection::Connection;
-pub use error::ListenerError;
-pub use listener::{Listener, ListenerOptions};
+pub use error::{FrameError, ListenerError};
+pub use frame::{read_frame, write_frame};
+pub use health::HealthChecks;
+pub use listener::{DEFAULT_MAX_BODY_SIZE, Listener, ListenerOptions};
+pub use metrics::{RequestMetrics, TrafficCounters};
+pub use raw_listener::{RawListener, RawListenerOptions};
+pub use router_handle::RouterHandle;
+pub use security_headers::SecurityHeaders;
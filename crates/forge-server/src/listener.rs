@@ -1,130 +1,1272 @@
 use std::io::Error;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, SocketAddr};
 use std::num::NonZero;
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
-use super::{Connection, ListenerError};
-use forge_http::Response;
-use forge_router::Router;
-use monoio::net::{TcpListener, TcpStream};
+use super::metrics::{MetricsHook, TrafficCounters};
+use super::read_buffer::ReadBuffer;
+use super::state_source::StateSource;
+use super::{Connection, HealthChecks, ListenerError, RequestMetrics, RouterHandle, SecurityHeaders};
+use forge_http::{Response, TrustedProxies};
+use forge_router::{Route, Router, StateHandle, StateKind, handler::LocalBoxFuture};
+use forge_utils::panic_message;
+use monoio::io::{AsyncWriteRent, CancelableAsyncReadRent};
+use monoio::net::TcpListener;
 use monoio::time::TimeDriver;
 use monoio::{FusionDriver, FusionRuntime, IoUringDriver, LegacyDriver, RuntimeBuilder};
+use socket2::{Domain, Protocol, Socket, Type};
 
 const DEFAULT_RING_ENTRIES: u32 = 4096;
-const BUFFER_SIZE: usize = 4096;
+const LISTEN_BACKLOG: i32 = 1024;
+pub(crate) const BUFFER_SIZE: usize = 4096;
+pub(crate) const MAX_HEAD_SIZE: usize = 16 * 1024;
 
+/// The request body limit a `Listener` starts with.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Where and how a `Listener` accepts connections. `Listener::run` checks it with `validate`.
 pub struct ListenerOptions {
     pub port: u16,
-    pub host: Ipv4Addr,
+    /// Either an IPv4 or an IPv6 address.
+    pub host: IpAddr,
+    /// Sets `IPV6_V6ONLY` on IPv6 hosts: when enabled, a listener on `::` also accepts IPv4 clients
+    /// as IPv4-mapped addresses (`::ffff:a.b.c.d`). It is set explicitly so the result does not
+    /// depend on platform defaults such as Linux's `net.ipv6.bindv6only`, and is ignored for IPv4.
+    pub dual_stack: bool,
+    /// Adds a `Server-Timing` header with parse and handler durations and anything recorded through
+    /// `Request::record_timing`. It exposes server-side timing to every client, so keep it disabled
+    /// outside of development and trusted environments.
+    pub server_timing: bool,
+    /// Bounds every individual read from a client, which also closes keep-alive connections idle for
+    /// longer than this.
+    pub read_timeout: Duration,
+    /// Bounds the time between the start of a request and the end of its headers, so a client that
+    /// trickles bytes to hold a connection open (slowloris) is dropped.
+    pub header_timeout: Duration,
+    pub max_uri_length: usize,
+    /// Holds a keep-alive response back while the client has already sent its next request, so
+    /// responses to pipelined requests go out together in a single write.
+    pub pipeline_batching: bool,
+    /// The load balancers and reverse proxies in front of the server. Requests from them have
+    /// `Request::real_ip`, `scheme` and `host` taken from their `Forwarded` or `X-Forwarded-*`
+    /// headers; those headers are ignored on requests from any other peer.
+    pub trusted_proxies: TrustedProxies,
     pub threads: Option<usize>,
 }
 
+impl ListenerOptions {
+    /// Rejects a zero `threads`, `max_uri_length`, `read_timeout` or `header_timeout`, which
+    /// usually come from a misconfigured environment variable. `Listener::run` checks it first.
+    pub fn validate(&self) -> Result<(), ListenerError> {
+        let fields: [(&'static str, bool); 4] = [
+            ("threads", self.threads == Some(0)),
+            ("max_uri_length", self.max_uri_length == 0),
+            ("read_timeout", self.read_timeout.is_zero()),
+            ("header_timeout", self.header_timeout.is_zero()),
+        ];
+
+        match fields.into_iter().find(|&(_, zero): &(&'static str, bool)| zero) {
+            Some((name, _)) => Err(ListenerError::InvalidOptions(name)),
+            None => Ok(()),
+        }
+    }
+}
+
+type TaskFactory<T> = dyn Fn(Option<StateHandle<T>>) -> LocalBoxFuture<'static, ()> + Send + Sync;
+
+struct BackgroundTask<T> {
+    every_worker: bool,
+    factory: Arc<TaskFactory<T>>,
+}
+
+impl<T> Clone for BackgroundTask<T> {
+    fn clone(&self) -> Self {
+        Self {
+            every_worker: self.every_worker,
+            factory: self.factory.clone(),
+        }
+    }
+}
+
+/// Serves a `Router` with the given `ListenerOptions`, configured through its `with_*` methods and
+/// started with `run`.
 pub struct Listener<T> {
-    state: Option<Arc<T>>,
-    router: Arc<Router<T>>,
+    router: Router<T>,
+    router_handle: RouterHandle<T>,
+    state: Option<StateSource<T>>,
     options: ListenerOptions,
+    health_checks: Option<HealthChecks<T>>,
+    security_headers: Option<Arc<SecurityHeaders>>,
+    default_headers: Option<Arc<[(String, String)]>>,
+    default_content_type: Option<Arc<str>>,
+    metrics: Option<Arc<MetricsHook>>,
+    traffic: Option<Arc<TrafficCounters>>,
+    request_timeout: Option<Duration>,
+    max_body_size: Option<usize>,
+    max_write_buffer: usize,
+    access_log: bool,
+    tasks: Vec<BackgroundTask<T>>,
 }
 
 impl<T> Listener<T>
 where
-    T: Send + Sync + 'static,
+    T: 'static,
 {
     pub fn new(router: Router<T>, options: ListenerOptions) -> Self {
         Self {
+            router,
             options,
+            router_handle: RouterHandle::new(Router::new()),
             state: None,
-            router: Arc::new(router),
+            health_checks: None,
+            security_headers: None,
+            default_headers: None,
+            default_content_type: None,
+            metrics: None,
+            traffic: None,
+            request_timeout: None,
+            max_body_size: Some(DEFAULT_MAX_BODY_SIZE),
+            max_write_buffer: Response::DEFAULT_MAX_WRITE_BUFFER,
+            access_log: false,
+            tasks: Vec::new(),
         }
     }
 
-    pub fn with_state(mut self, state: T) -> Self {
-        self.state = Some(Arc::new(state));
+    /// Shares one state value between every worker thread. Handlers take it as `Arc<T>` or `&T`.
+    pub fn with_state(mut self, state: T) -> Self
+    where
+        T: Send + Sync,
+    {
+        self.state = Some(StateSource::shared(state));
         self
     }
 
-    pub fn run(self) -> Result<(), ListenerError> {
-        let addr: SocketAddr = SocketAddr::from((self.options.host, self.options.port));
+    /// Builds the state on each worker thread, so state such as per-core connection pools or caches
+    /// never leaves the thread it was built on. It needs to be neither `Send` nor `Sync`, and can
+    /// hold `Rc`, `Cell` or `RefCell` instead of locks. Handlers take it as `Rc<T>` or `&T`.
+    pub fn with_state_factory<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        self.state = Some(StateSource::per_worker(factory));
+        self
+    }
+
+    pub fn with_health_checks(mut self, health_checks: HealthChecks<T>) -> Self {
+        self.health_checks = Some(health_checks);
+        self
+    }
+
+    pub fn with_security_headers(mut self, security_headers: SecurityHeaders) -> Self {
+        self.security_headers = Some(Arc::new(security_headers));
+        self
+    }
+
+    pub fn with_default_headers(mut self, default_headers: Vec<(String, String)>) -> Self {
+        self.default_headers = Some(Arc::from(default_headers));
+        self
+    }
+
+    /// Labels responses that have a body but no `Content-Type` (such as
+    /// `Response::new(status).body(...)`), which are otherwise sent without one.
+    pub fn with_default_content_type(mut self, content_type: &str) -> Self {
+        self.default_content_type = Some(Arc::from(content_type));
+        self
+    }
 
-        let threads: usize = self.options.threads.filter(|&n: &usize| n >= 1).unwrap_or_else(|| {
-            thread::available_parallelism()
-                .map(|n: NonZero<usize>| n.get())
-                .unwrap_or(1)
+    pub fn with_metrics<F>(mut self, metrics: F) -> Self
+    where
+        F: Fn(&RequestMetrics) + Send + Sync + 'static,
+    {
+        self.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Adds up the bytes received and sent in a `TrafficCounters` the caller keeps a handle to.
+    pub fn with_traffic_counters(mut self, traffic: Arc<TrafficCounters>) -> Self {
+        self.traffic = Some(traffic);
+        self
+    }
+
+    /// Bounds how long a handler may run before it is dropped and the client gets
+    /// `503 Service Unavailable`.
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// Answers requests whose body is declared or received larger than the limit with
+    /// `413 Payload Too Large` before the rest of it is read; bodies of `stream_body` routes are only
+    /// checked against their declared `Content-Length`. Replaces `DEFAULT_MAX_BODY_SIZE`, and `None`
+    /// lifts the limit, leaving body sizes to the handlers. A route's own `max_body_size` takes
+    /// precedence over it.
+    pub fn with_max_body_size<L>(mut self, max_body_size: L) -> Self
+    where
+        L: Into<Option<usize>>,
+    {
+        self.max_body_size = max_body_size.into();
+        self
+    }
+
+    /// The largest body copied into the same write as the response head
+    /// (`Response::DEFAULT_MAX_WRITE_BUFFER` by default). Larger bodies are written separately, still
+    /// with their `Content-Length` (see `Response::send_buffered`).
+    pub fn with_max_write_buffer(mut self, max_write_buffer: usize) -> Self {
+        self.max_write_buffer = max_write_buffer;
+        self
+    }
+
+    /// Prints one line per response written by a handler, with the bytes received and sent for it,
+    /// and the address of every client dropped for missing a read or header deadline.
+    pub fn with_access_log(mut self, enabled: bool) -> Self {
+        self.access_log = enabled;
+        self
+    }
+
+    /// Runs a future once, on the first worker's runtime, before the workers accept connections. It
+    /// receives the state that worker's handlers get and shares the worker with its connections, so
+    /// it must not block the thread. There is no shutdown signal yet: it stops when it returns or
+    /// when the worker's runtime is dropped.
+    pub fn with_background_task<F, K>(self, task: F) -> Self
+    where
+        F: Fn(Option<StateHandle<T>>) -> K + Send + Sync + 'static,
+        K: Future<Output = ()> + 'static,
+    {
+        self.with_task(false, task)
+    }
+
+    /// Like `with_background_task`, but runs one future on every worker.
+    pub fn with_worker_task<F, K>(self, task: F) -> Self
+    where
+        F: Fn(Option<StateHandle<T>>) -> K + Send + Sync + 'static,
+        K: Future<Output = ()> + 'static,
+    {
+        self.with_task(true, task)
+    }
+
+    fn with_task<F, K>(mut self, every_worker: bool, task: F) -> Self
+    where
+        F: Fn(Option<StateHandle<T>>) -> K + Send + Sync + 'static,
+        K: Future<Output = ()> + 'static,
+    {
+        self.tasks.push(BackgroundTask {
+            every_worker,
+            factory: Arc::new(move |state: Option<StateHandle<T>>| Box::pin(task(state))),
         });
 
+        self
+    }
+
+    /// Returns a `RouterHandle` that can replace the router while the listener runs.
+    pub fn router_handle(&self) -> RouterHandle<T> {
+        self.router_handle.clone()
+    }
+
+    /// Refuses to start with `ListenerError::MissingState` when a route takes the application state
+    /// but neither `with_state` nor `with_state_factory` was called, and with
+    /// `ListenerError::StateKindMismatch` when a route takes it as an `Arc<T>` or `Rc<T>` that the
+    /// listener's kind of state cannot provide. Routers installed later through a `RouterHandle`
+    /// are not checked; those routes answer `500` instead.
+    pub fn run(mut self) -> Result<(), ListenerError> {
+        self.options.validate()?;
+
+        if cfg!(panic = "abort") {
+            eprintln!(
+                "Built with `panic = \"abort\"`: a handler that panics will stop the server instead of answering 500"
+            );
+        }
+
+        if let Some(health_checks) = self.health_checks.take() {
+            health_checks.register(&mut self.router)?;
+        }
+
+        let state_kind: Option<StateKind> = self.state.as_ref().map(StateSource::kind);
+
+        if state_kind.is_none()
+            && let Some(route) = self.router.routes().find(|route: &&Route<T>| route.meta.requires_state)
+        {
+            return Err(ListenerError::MissingState(route.method, route.path));
+        }
+
+        if let Some(state_kind) = state_kind
+            && let Some((route, kind)) = self.router.routes().find_map(|route: &Route<T>| {
+                Some((route, route.meta.state_kind.filter(|&kind: &StateKind| kind != state_kind)?))
+            })
+        {
+            return Err(ListenerError::StateKindMismatch(route.method, route.path, kind));
+        }
+
+        self.router_handle.replace(self.router);
+        let addr: SocketAddr = SocketAddr::from((self.options.host, self.options.port));
+        let dual_stack: bool = self.options.dual_stack;
+        let server_timing: bool = self.options.server_timing;
+        let max_uri_length: usize = self.options.max_uri_length;
+        let (request_timeout, max_body_size, access_log): (Option<Duration>, Option<usize>, bool) =
+            (self.request_timeout, self.max_body_size, self.access_log);
+        let max_write_buffer: usize = self.max_write_buffer;
+        let pipeline_batching: bool = self.options.pipeline_batching;
+        let trusted_proxies: Arc<TrustedProxies> = Arc::new(self.options.trusted_proxies);
+        let (read_timeout, header_timeout): (Duration, Duration) =
+            (self.options.read_timeout, self.options.header_timeout);
+
+        let threads: Option<usize> = self.options.threads;
+        let router_handle: RouterHandle<T> = self.router_handle.clone();
+
         println!("Listener running on http://{addr}");
-        let handles: Vec<JoinHandle<Result<(), ListenerError>>> = (0..threads)
-            .map(|idx: usize| {
-                let shared_router: Arc<Router<T>> = self.router.clone();
-                let shared_state: Option<Arc<T>> = self.state.clone();
-
-                thread::spawn(move || -> Result<(), ListenerError> {
-                    let mut runtime: FusionRuntime<TimeDriver<IoUringDriver>, TimeDriver<LegacyDriver>> =
-                        RuntimeBuilder::<FusionDriver>::new()
-                            .enable_all()
-                            .with_entries(DEFAULT_RING_ENTRIES)
-                            .build()
-                            .map_err(|e: Error| ListenerError::Runtime(idx, e))?;
-
-                    runtime.block_on(async {
-                        let listener: TcpListener =
-                            TcpListener::bind(addr).map_err(|e: Error| ListenerError::Bind(addr, idx, e))?;
-
-                        loop {
-                            match listener.accept().await {
-                                Ok((stream, _)) => {
-                                    let thread_router: Arc<Router<T>> = shared_router.clone();
-                                    let thread_state: Option<Arc<T>> = shared_state.clone();
-
-                                    if let Err(e) = stream.set_nodelay(true) {
-                                        eprintln!("Failed to set 'TCP_NODELAY' on worker #{idx}: {e:?}");
-                                    }
-
-                                    monoio::spawn(async move {
-                                        Self::handle_connection(stream, thread_router, thread_state).await;
-                                    });
-                                }
-                                Err(e) => {
-                                    eprintln!("Failed to accept connection on worker #{idx}: {e:?}");
-                                }
+        run_workers(threads, |idx: usize| {
+            let router_handle: RouterHandle<T> = router_handle.clone();
+            let trusted_proxies: Arc<TrustedProxies> = trusted_proxies.clone();
+            let tasks: Vec<BackgroundTask<T>> = self.tasks.clone();
+            let state_source: Option<StateSource<T>> = self.state.clone();
+            let shared_security_headers: Option<Arc<SecurityHeaders>> = self.security_headers.clone();
+            let shared_default_headers: Option<Arc<[(String, String)]>> = self.default_headers.clone();
+            let shared_default_content_type: Option<Arc<str>> = self.default_content_type.clone();
+            let shared_metrics: Option<Arc<MetricsHook>> = self.metrics.clone();
+            let shared_traffic: Option<Arc<TrafficCounters>> = self.traffic.clone();
+
+            move || async move {
+                let worker_state: Option<StateHandle<T>> = state_source.as_ref().map(StateSource::build);
+
+                let listener: TcpListener =
+                    bind(addr, dual_stack).map_err(|e: Error| ListenerError::Bind(addr, idx, e))?;
+
+                Self::spawn_tasks(&tasks, idx, &worker_state);
+
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, peer_addr)) => {
+                            if let Err(e) = stream.set_nodelay(true) {
+                                eprintln!("Failed to set 'TCP_NODELAY' on worker #{idx}: {e:?}");
                             }
-                        }
 
-                        #[allow(unreachable_code)]
-                        Ok(())
-                    })
-                })
-            })
-            .collect();
+                            let connection: Connection<T> = Connection {
+                                stream,
+                                peer_addr,
+                                trusted_proxies: trusted_proxies.clone(),
+                                secure: false,
+                                server_timing,
+                                read_timeout,
+                                header_timeout,
+                                max_uri_length,
+                                request_timeout,
+                                max_body_size,
+                                max_write_buffer,
+                                pipeline_batching,
+                                access_log,
+                                router: router_handle.current(),
+                                router_handle: Some(router_handle.clone()),
+                                state: worker_state.clone(),
+                                security_headers: shared_security_headers.clone(),
+                                default_headers: shared_default_headers.clone(),
+                                default_content_type: shared_default_content_type.clone(),
+                                metrics: shared_metrics.clone(),
+                                traffic: shared_traffic.clone(),
+                                buffered: ReadBuffer::default(),
+                                pending_writes: Vec::new(),
+                                probe_buffer: Vec::new(),
+                            };
 
-        for (idx, handler) in handles.into_iter().enumerate() {
-            match handler.join() {
-                Ok(Ok(())) => {}
-                Ok(Err(e)) => return Err(e),
-                Err(e) => {
-                    let msg: &str = e.downcast_ref::<&'static str>().copied().unwrap_or("unknown cause");
-                    return Err(ListenerError::ThreadPanic(idx, msg.into()));
+                            monoio::spawn(async move {
+                                Self::handle_connection(connection).await;
+                            });
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to accept connection on worker #{idx}: {e:?}");
+                        }
+                    }
                 }
+
+                #[allow(unreachable_code)]
+                Ok(())
             }
-        }
+        })
+    }
 
-        Ok(())
+    fn spawn_tasks(tasks: &[BackgroundTask<T>], idx: usize, state: &Option<StateHandle<T>>) {
+        tasks
+            .iter()
+            .filter(|task: &&BackgroundTask<T>| task.every_worker || idx == 0)
+            .for_each(|task: &BackgroundTask<T>| {
+                monoio::spawn((task.factory)(state.clone()));
+            });
     }
 
-    async fn handle_connection(stream: TcpStream, router: Arc<Router<T>>, state: Option<Arc<T>>) {
-        let mut connection: Connection<T> = Connection { router, stream, state };
+    async fn handle_connection<S>(mut connection: Connection<T, S>)
+    where
+        S: CancelableAsyncReadRent + AsyncWriteRent,
+    {
         let mut buffer: Vec<u8> = vec![0; BUFFER_SIZE];
 
         loop {
             match connection.process_request(buffer).await {
-                Ok(connection_buffer) => buffer = connection_buffer,
+                Ok((connection_buffer, true)) => {
+                    if let Some(router_handle) = &connection.router_handle {
+                        connection.router = router_handle.current();
+                    }
+
+                    buffer = connection_buffer
+                }
+                Ok((_, false)) => break,
                 Err(ListenerError::ConnectionClosed) => break,
-                Err(ListenerError::Http(e)) => {
-                    Response::new(e.status).send(&mut connection.stream).await.ok();
+                Err(e @ ListenerError::ReadTimeout(_)) => {
+                    if connection.access_log {
+                        println!("Dropping connection from {}: {e}", connection.peer_addr);
+                    }
+
                     break;
                 }
+                Err(ListenerError::Http(e)) => {
+                    connection.send_error(e).await;
+                    return;
+                }
                 Err(_) => unreachable!(),
             }
         }
+
+        connection.flush_pending().await.ok();
+    }
+}
+
+/// Runs one worker per thread (one per core unless `threads` says otherwise), each on its own
+/// runtime, and waits for all of them. `make_worker` is called on the current thread with the
+/// worker's index, and the closure it returns is then called on the worker's thread to build the
+/// future that runs there.
+pub(crate) fn run_workers<M, W, K>(threads: Option<usize>, make_worker: M) -> Result<(), ListenerError>
+where
+    M: Fn(usize) -> W,
+    W: FnOnce() -> K + Send + 'static,
+    K: Future<Output = Result<(), ListenerError>>,
+{
+    let threads: usize = threads.filter(|&n: &usize| n >= 1).unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|n: NonZero<usize>| n.get())
+            .unwrap_or(1)
+    });
+
+    let handles: Vec<JoinHandle<Result<(), ListenerError>>> = (0..threads)
+        .map(|idx: usize| {
+            let worker: W = make_worker(idx);
+
+            thread::spawn(move || -> Result<(), ListenerError> {
+                let mut runtime: FusionRuntime<TimeDriver<IoUringDriver>, TimeDriver<LegacyDriver>> =
+                    RuntimeBuilder::<FusionDriver>::new()
+                        .enable_all()
+                        .with_entries(DEFAULT_RING_ENTRIES)
+                        .build()
+                        .map_err(|e: Error| ListenerError::Runtime(idx, e))?;
+
+                runtime.block_on(worker())
+            })
+        })
+        .collect();
+
+    for (idx, handler) in handles.into_iter().enumerate() {
+        match handler.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(e),
+            Err(e) => return Err(ListenerError::ThreadPanic(idx, panic_message(&e).into())),
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn bind(addr: SocketAddr, dual_stack: bool) -> Result<TcpListener, Error> {
+    let socket: Socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+
+    socket.set_reuse_address(true)?;
+    socket.set_reuse_port(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(LISTEN_BACKLOG)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use forge_http::{BodyStream, Bytes, HttpMethod, HttpStatus, Request, ResponseWriter};
+    use forge_macros::{get, post};
+    use monoio::BufResult;
+    use monoio::buf::{IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+    use monoio::io::{AsyncReadRent, CancelHandle};
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::future::{self, Future};
+    use std::io;
+    use std::net::Ipv4Addr;
+    use std::rc::Rc;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    struct MockStream {
+        reads: VecDeque<&'static [u8]>,
+        written: Rc<RefCell<Vec<u8>>>,
+        writes: Option<Rc<RefCell<usize>>>,
+        reset: bool,
+    }
+
+    impl MockStream {
+        fn new(reads: &[&'static [u8]], written: Rc<RefCell<Vec<u8>>>) -> Self {
+            Self {
+                reads: reads.iter().copied().collect(),
+                written,
+                writes: None,
+                reset: false,
+            }
+        }
+    }
+
+    impl AsyncReadRent for MockStream {
+        fn read<T: IoBufMut>(&mut self, mut buf: T) -> impl Future<Output = BufResult<usize, T>> {
+            if self.reads.is_empty() && self.reset {
+                return future::ready((Err(io::ErrorKind::ConnectionReset.into()), buf));
+            }
+
+            let chunk: &[u8] = self.reads.pop_front().unwrap_or_default();
+            let len: usize = chunk.len().min(buf.bytes_total());
+
+            unsafe {
+                std::ptr::copy_nonoverlapping(chunk.as_ptr(), buf.write_ptr(), len);
+                buf.set_init(len);
+            }
+
+            future::ready((Ok(len), buf))
+        }
+
+        fn readv<T: IoVecBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+            future::ready((Err(io::ErrorKind::Unsupported.into()), buf))
+        }
+    }
+
+    impl CancelableAsyncReadRent for MockStream {
+        fn cancelable_read<T: IoBufMut>(
+            &mut self,
+            buf: T,
+            _: CancelHandle,
+        ) -> impl Future<Output = BufResult<usize, T>> {
+            self.read(buf)
+        }
+
+        fn cancelable_readv<T: IoVecBufMut>(
+            &mut self,
+            buf: T,
+            _: CancelHandle,
+        ) -> impl Future<Output = BufResult<usize, T>> {
+            self.readv(buf)
+        }
+    }
+
+    impl AsyncWriteRent for MockStream {
+        fn write<T: IoBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+            let bytes: &[u8] = unsafe { std::slice::from_raw_parts(buf.read_ptr(), buf.bytes_init()) };
+            self.written.borrow_mut().extend_from_slice(bytes);
+            self.writes
+                .iter()
+                .for_each(|writes: &Rc<RefCell<usize>>| *writes.borrow_mut() += 1);
+            future::ready((Ok(bytes.len()), buf))
+        }
+
+        fn writev<T: IoVecBuf>(&mut self, buf_vec: T) -> impl Future<Output = BufResult<usize, T>> {
+            future::ready((Err(io::ErrorKind::Unsupported.into()), buf_vec))
+        }
+
+        fn flush(&mut self) -> impl Future<Output = io::Result<()>> {
+            future::ready(Ok(()))
+        }
+
+        fn shutdown(&mut self) -> impl Future<Output = io::Result<()>> {
+            future::ready(Ok(()))
+        }
+    }
+
+    #[get("/")]
+    async fn hello_handler() -> Response<'static> {
+        Response::new(HttpStatus::Ok).text("hello")
+    }
+
+    static SLOW_HANDLER_DROPPED: AtomicBool = AtomicBool::new(false);
+
+    struct DropFlag;
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            SLOW_HANDLER_DROPPED.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[get("/slow")]
+    async fn slow_handler() -> Response<'static> {
+        let _flag: DropFlag = DropFlag;
+        future::pending::<()>().await;
+        Response::new(HttpStatus::Ok)
+    }
+
+    #[get("/late")]
+    async fn late_handler() -> Response<'static> {
+        monoio::time::sleep(Duration::from_millis(1)).await;
+        Response::new(HttpStatus::Ok).text("late")
+    }
+
+    #[get("/origin")]
+    async fn origin_handler(req: Request<'_>) -> Response<'static> {
+        let real_ip: String = req.real_ip().map(|ip: IpAddr| ip.to_string()).unwrap_or_default();
+        Response::new(HttpStatus::Ok).text(format!("{real_ip} {}://{}", req.scheme(), req.host().unwrap_or_default()))
+    }
+
+    #[post("/upload", stream_body = true)]
+    async fn upload_handler(mut req: Request<'_>) -> Response<'static> {
+        let mut body: BodyStream = req.body_stream();
+        let (mut chunks, mut bytes): (usize, usize) = (0, 0);
+
+        loop {
+            match body.chunk().await {
+                Ok(Some(chunk)) => {
+                    chunks += 1;
+                    bytes += chunk.len();
+                }
+                Ok(None) => break,
+                Err(e) => return e.into(),
+            }
+        }
+
+        Response::new(HttpStatus::Ok).text(format!("{chunks} chunks, {bytes} bytes"))
+    }
+
+    #[post("/small", max_body_size = 4)]
+    async fn small_handler(body: Bytes<'_>) -> Response<'static> {
+        Response::new(HttpStatus::Ok).text(format!("{} bytes", body.len()))
+    }
+
+    #[post("/large", max_body_size = 16)]
+    async fn large_handler(body: Bytes<'_>) -> Response<'static> {
+        Response::new(HttpStatus::Ok).text(format!("{} bytes", body.len()))
+    }
+
+    #[get("/count")]
+    async fn count_handler(state: Arc<AtomicUsize>) -> Response<'static> {
+        Response::new(HttpStatus::Ok).text(state.fetch_add(1, Ordering::Relaxed).to_string())
+    }
+
+    #[get("/visits")]
+    async fn visits_handler(state: Rc<AtomicUsize>) -> Response<'static> {
+        Response::new(HttpStatus::Ok).text(state.fetch_add(1, Ordering::Relaxed).to_string())
+    }
+
+    #[post("/echo")]
+    async fn echo_handler(req: Request<'_>) -> Response<'static> {
+        Response::new(HttpStatus::Ok).text(format!("{} bytes", req.body.len()))
+    }
+
+    #[post("/discard", stream_body = true)]
+    async fn discard_handler() -> Response<'static> {
+        Response::new(HttpStatus::Accepted)
+    }
+
+    #[get("/stream", stream_response = true)]
+    async fn stream_handler(mut req: Request<'_>) -> Response<'static> {
+        let mut writer: ResponseWriter = req.response_writer().unwrap();
+        writer
+            .write_head(HttpStatus::Ok, [("Content-Type", "text/plain")])
+            .await
+            .unwrap();
+        writer.write_body_chunk("hello, ").await.unwrap();
+        writer.write_body_chunk("world").await.unwrap();
+        Response::new(HttpStatus::InternalServerError)
+    }
+
+    thread_local! {
+        static ROUTER_HANDLE: RefCell<Option<RouterHandle<()>>> = const { RefCell::new(None) };
+    }
+
+    #[post("/swap")]
+    async fn swap_handler() -> Response<'static> {
+        ROUTER_HANDLE.with_borrow(|handle: &Option<RouterHandle<()>>| {
+            handle
+                .as_ref()
+                .map(|handle: &RouterHandle<()>| handle.replace(Router::new()))
+        });
+
+        Response::new(HttpStatus::Ok).text("swapped")
+    }
+
+    async fn run(reads: &[&'static [u8]]) -> String {
+        run_with(reads, |_: &mut Connection<(), MockStream>| {}).await
+    }
+
+    async fn run_with<F>(reads: &[&'static [u8]], configure: F) -> String
+    where
+        F: FnOnce(&mut Connection<(), MockStream>),
+    {
+        let written: Rc<RefCell<Vec<u8>>> = Rc::default();
+        let mut router: Router<()> = Router::new();
+        router.register(hello_handler);
+        router.register(slow_handler);
+        router.register(late_handler);
+        router.register(upload_handler);
+        router.register(discard_handler);
+        router.register(echo_handler);
+        router.register(origin_handler);
+        router.register(stream_handler);
+        router.register(small_handler);
+        router.register(large_handler);
+
+        let mut connection: Connection<(), MockStream> = Connection {
+            stream: MockStream::new(reads, written.clone()),
+            peer_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+            trusted_proxies: Arc::default(),
+            secure: false,
+            state: None,
+            router: Arc::new(router),
+            router_handle: None,
+            security_headers: None,
+            default_headers: None,
+            default_content_type: None,
+            metrics: None,
+            traffic: None,
+            server_timing: false,
+            read_timeout: Duration::from_secs(1),
+            header_timeout: Duration::from_secs(1),
+            max_uri_length: 1024,
+            request_timeout: None,
+            max_body_size: None,
+            max_write_buffer: Response::DEFAULT_MAX_WRITE_BUFFER,
+            pipeline_batching: false,
+            access_log: false,
+            buffered: ReadBuffer::default(),
+            pending_writes: Vec::new(),
+            probe_buffer: Vec::new(),
+        };
+
+        configure(&mut connection);
+
+        Listener::handle_connection(connection).await;
+        String::from_utf8(written.take()).unwrap()
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_http_1_0_request_closes_connection() {
+        let written: String = run(&[b"GET / HTTP/1.0\r\n\r\n", b"GET / HTTP/1.0\r\n\r\n"]).await;
+
+        assert_eq!(written.matches("HTTP/1.0 200 OK").count(), 1);
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_connection_close_header_closes_connection() {
+        let written: String = run(&[
+            b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        ])
+        .await;
+
+        assert_eq!(written.matches("HTTP/1.1 200 OK").count(), 1);
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_http_1_1_request_reuses_connection() {
+        let written: String = run(&[
+            b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        ])
+        .await;
+
+        assert_eq!(written.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(!written.contains("Connection:"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_replaced_router_applies_to_next_request() {
+        let written: String = run_with(
+            &[
+                b"POST /swap HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n",
+                b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            ],
+            |connection: &mut Connection<(), MockStream>| {
+                let mut router: Router<()> = Router::new();
+                router.register(hello_handler);
+                router.register(swap_handler);
+
+                let router_handle: RouterHandle<()> = RouterHandle::new(router);
+                connection.router = router_handle.current();
+                connection.router_handle = Some(router_handle.clone());
+                ROUTER_HANDLE.set(Some(router_handle));
+            },
+        )
+        .await;
+
+        assert!(written.contains("swapped"));
+        assert!(written.contains("HTTP/1.1 404 Not Found"));
+    }
+
+    fn options() -> ListenerOptions {
+        ListenerOptions {
+            port: 0,
+            host: IpAddr::from(Ipv4Addr::LOCALHOST),
+            dual_stack: false,
+            server_timing: false,
+            read_timeout: Duration::from_secs(1),
+            header_timeout: Duration::from_secs(1),
+            max_uri_length: 1024,
+            pipeline_batching: false,
+            trusted_proxies: TrustedProxies::default(),
+            threads: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_options_reject_zero_values() {
+        assert!(options().validate().is_ok());
+        assert!(
+            ListenerOptions {
+                threads: None,
+                ..options()
+            }
+            .validate()
+            .is_ok()
+        );
+
+        let zero_threads: ListenerOptions = ListenerOptions {
+            threads: Some(0),
+            ..options()
+        };
+        assert!(matches!(zero_threads.validate(), Err(ListenerError::InvalidOptions("threads"))));
+
+        let zero_timeout: ListenerOptions = ListenerOptions {
+            read_timeout: Duration::ZERO,
+            ..options()
+        };
+        assert!(matches!(
+            zero_timeout.validate(),
+            Err(ListenerError::InvalidOptions("read_timeout"))
+        ));
+
+        let listener: Listener<()> = Listener::new(Router::new(), zero_threads);
+        assert!(matches!(listener.run(), Err(ListenerError::InvalidOptions("threads"))));
+    }
+
+    #[test]
+    fn test_body_size_is_limited_unless_lifted() {
+        let listener: Listener<()> = Listener::new(Router::new(), options());
+        assert_eq!(listener.max_body_size, Some(DEFAULT_MAX_BODY_SIZE));
+
+        let listener: Listener<()> = listener.with_max_body_size(64);
+        assert_eq!(listener.max_body_size, Some(64));
+        assert_eq!(listener.with_max_body_size(None).max_body_size, None);
+    }
+
+    #[test]
+    fn test_stateful_route_without_state_refuses_to_start() {
+        let mut router: Router<AtomicUsize> = Router::new();
+        router.register(count_handler);
+
+        assert!(matches!(
+            Listener::new(router, options()).run(),
+            Err(ListenerError::MissingState(HttpMethod::GET, "/count"))
+        ));
+
+        let mut fallback_router: Router<AtomicUsize> = Router::new();
+        fallback_router.register_fallback(count_handler);
+
+        assert!(matches!(
+            Listener::new(fallback_router, options()).run(),
+            Err(ListenerError::MissingState(HttpMethod::GET, "/count"))
+        ));
+    }
+
+    #[test]
+    fn test_route_for_other_state_kind_refuses_to_start() {
+        let mut router: Router<AtomicUsize> = Router::new();
+        router.register(count_handler);
+
+        assert!(matches!(
+            Listener::new(router, options())
+                .with_state_factory(AtomicUsize::default)
+                .run(),
+            Err(ListenerError::StateKindMismatch(HttpMethod::GET, "/count", StateKind::Shared))
+        ));
+
+        let mut router: Router<AtomicUsize> = Router::new();
+        router.register(visits_handler);
+
+        assert!(matches!(
+            Listener::new(router, options())
+                .with_state(AtomicUsize::default())
+                .run(),
+            Err(ListenerError::StateKindMismatch(HttpMethod::GET, "/visits", StateKind::Local))
+        ));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_connect_without_handler_is_not_implemented() {
+        let written: String = run(&[b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n"]).await;
+
+        assert!(written.starts_with("HTTP/1.1 501 Not Implemented"));
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_forwarded_headers_only_trusted_from_proxies() {
+        const FORWARDED: &[u8] =
+            b"GET /origin HTTP/1.1\r\nHost: internal:8080\r\nX-Forwarded-For: 203.0.113.9\r\nX-Forwarded-Proto: https\r\nX-Forwarded-Host: example.com\r\n\r\n";
+
+        let untrusted: String = run(&[FORWARDED]).await;
+        assert!(untrusted.ends_with("127.0.0.1 http://internal:8080"));
+
+        let trusted: String = run_with(&[FORWARDED], |connection: &mut Connection<(), MockStream>| {
+            connection.trusted_proxies = Arc::new("127.0.0.0/8".parse().unwrap());
+        })
+        .await;
+        assert!(trusted.ends_with("203.0.113.9 https://example.com"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_background_tasks_run_once_or_on_every_worker() {
+        static RUNS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+        let task = |every_worker: bool, name: &'static str| BackgroundTask::<()> {
+            every_worker,
+            factory: Arc::new(move |_: Option<StateHandle<()>>| {
+                Box::pin(async move { RUNS.lock().unwrap().push(name) })
+            }),
+        };
+
+        let tasks: [BackgroundTask<()>; 2] = [task(false, "once"), task(true, "worker")];
+        Listener::spawn_tasks(&tasks, 0, &None);
+        Listener::spawn_tasks(&tasks, 1, &None);
+        monoio::time::sleep(Duration::from_millis(1)).await;
+
+        let mut runs: Vec<&str> = RUNS.lock().unwrap().clone();
+        runs.sort_unstable();
+        assert_eq!(runs, ["once", "worker", "worker"]);
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_pipeline_batching_writes_pipelined_responses_together() {
+        let pipelined: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let writes: Rc<RefCell<usize>> = Rc::default();
+
+        let written: String = run_with(&[pipelined], |connection: &mut Connection<(), MockStream>| {
+            connection.pipeline_batching = true;
+            connection.stream.writes = Some(writes.clone());
+        })
+        .await;
+
+        assert_eq!(written.matches("HTTP/1.1 200 OK").count(), 3);
+        assert_eq!(*writes.borrow(), 1);
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_pipelined_request_is_kept_for_next_read() {
+        let written: String =
+            run(&[b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n"]).await;
+
+        assert_eq!(written.matches("HTTP/1.1 200 OK").count(), 2);
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_short_request_after_long_one_sees_no_stale_bytes() {
+        let written: String = run(&[
+            b"GET /origin HTTP/1.1\r\nHost: a-rather-long-host.example.com\r\nX-Padding: 0123456789\r\n\r\n",
+            b"GET /origin HTTP/1.1\r\nHost: b\r\n\r\n",
+            b"GET /origin HTTP/1.1\r\nHost: another-long-host.example.com\r\n\r\nGET /origin HT",
+            b"TP/1.1\r\nHost: c\r\n\r\n",
+        ])
+        .await;
+
+        let bodies: Vec<&str> = written
+            .split("HTTP/1.1 200 OK")
+            .skip(1)
+            .map(|response: &str| response.rsplit("\r\n\r\n").next().unwrap())
+            .collect();
+
+        assert_eq!(
+            bodies,
+            [
+                "127.0.0.1 http://a-rather-long-host.example.com",
+                "127.0.0.1 http://b",
+                "127.0.0.1 http://another-long-host.example.com",
+                "127.0.0.1 http://c",
+            ]
+        );
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_client_disconnect_drops_handler() {
+        let written: String = run_with(
+            &[b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n"],
+            |connection: &mut Connection<(), MockStream>| connection.stream.reset = true,
+        )
+        .await;
+
+        assert!(written.is_empty());
+        assert!(SLOW_HANDLER_DROPPED.load(Ordering::SeqCst));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_half_closed_client_still_gets_response() {
+        let written: String = run(&[b"GET /late HTTP/1.1\r\nHost: localhost\r\n\r\n"]).await;
+
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.contains("Connection: close\r\n"));
+        assert!(written.ends_with("late"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_request_split_across_reads() {
+        let written: String = run(&[b"GET / HT", b"TP/1.1\r\nHost: local", b"host\r\n", b"\r\n"]).await;
+
+        assert_eq!(written.matches("HTTP/1.1 200 OK").count(), 1);
+        assert!(written.ends_with("hello"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_empty_lines_before_request_are_ignored() {
+        let written: String = run(&[b"\r\n", b"\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n"]).await;
+
+        assert_eq!(written.matches("HTTP/1.1 200 OK").count(), 1);
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_oversized_head_is_rejected() {
+        let header_line: &'static [u8] =
+            b"X-Filler: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n";
+        let mut reads: Vec<&'static [u8]> = vec![b"GET / HTTP/1.1\r\nHost: localhost\r\n"];
+        reads.extend(std::iter::repeat_n(header_line, MAX_HEAD_SIZE / header_line.len() + 1));
+
+        let written: String = run(&reads).await;
+
+        assert!(written.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_overlong_uri_is_rejected_before_routing() {
+        let request: &'static [u8] = format!("GET /{} HTTP/1.1\r\nHost: localhost\r\n\r\n", "a".repeat(2048))
+            .leak()
+            .as_bytes();
+
+        let written: String = run(&[request]).await;
+
+        assert!(written.starts_with("HTTP/1.1 414 URI Too Long\r\n"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_metrics_hook_receives_each_request() {
+        let recorded: Arc<Mutex<Vec<RequestMetrics>>> = Arc::default();
+        let sink: Arc<Mutex<Vec<RequestMetrics>>> = recorded.clone();
+        let hook: Arc<MetricsHook> = Arc::new(move |metrics: &RequestMetrics| sink.lock().unwrap().push(*metrics));
+
+        let traffic: Arc<TrafficCounters> = Arc::default();
+        let counters: Arc<TrafficCounters> = traffic.clone();
+
+        let written: String = run_with(
+            &[b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n"],
+            |connection: &mut Connection<(), MockStream>| {
+                connection.metrics = Some(hook);
+                connection.traffic = Some(counters);
+            },
+        )
+        .await;
+
+        let recorded: Vec<RequestMetrics> = recorded.lock().unwrap().clone();
+        assert_eq!(recorded.len(), 2);
+        assert!(recorded.iter().all(|metrics: &RequestMetrics| {
+            metrics.method == HttpMethod::GET
+                && metrics.route == "/"
+                && metrics.status == HttpStatus::Ok
+                && metrics.bytes_in == 35
+                && metrics.bytes_out == written.len() / 2
+        }));
+        assert_eq!(traffic.bytes_in(), 70);
+        assert_eq!(traffic.bytes_out(), written.len() as u64);
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_binary_body_is_accepted() {
+        let written: String =
+            run(&[b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\n\xff\xfe\x00\x80"]).await;
+
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.ends_with("4 bytes"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_dribbled_request_is_framed_across_reads() {
+        let written: String = run(&[
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r",
+            b"\n2\r\nab\r\n3\r",
+            b"\ncde",
+            b"\r\n0\r\n",
+            b"\r\n",
+        ])
+        .await;
+
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.ends_with("5 bytes"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_streamed_body_is_read_as_it_arrives() {
+        let written: String = run(&[
+            b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nabc",
+            b"defg",
+            b"hijGET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        ])
+        .await;
+
+        assert!(written.contains("3 chunks, 10 bytes"));
+        assert_eq!(written.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(written.ends_with("hello"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_streamed_chunked_body() {
+        let written: String = run(&[
+            b"POST /upload HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n",
+            b"5\r\npedia\r\n0\r\n\r\n",
+        ])
+        .await;
+
+        assert!(written.contains("2 chunks, 9 bytes"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_malformed_streamed_body_fails_the_read() {
+        let written: String =
+            run(&[b"POST /upload HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"]).await;
+
+        assert!(written.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_error_responses_carry_listener_headers() {
+        let written: String = run_with(
+            &[b"GET / HTTP/1.1\r\nHost: localhost\r\nContent-Length: x\r\n\r\n"],
+            |connection: &mut Connection<(), MockStream>| {
+                connection.security_headers = Some(Arc::new(SecurityHeaders::default()));
+                connection.default_headers = Some(Arc::from([("Server".to_string(), "forge".to_string())]));
+                connection.default_content_type = Some(Arc::from("text/plain; charset=utf-8"));
+            },
+        )
+        .await;
+
+        assert!(written.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(written.contains("X-Content-Type-Options: nosniff\r\n"));
+        assert!(written.contains("X-Frame-Options: DENY\r\n"));
+        assert!(written.contains("Server: forge\r\n"));
+        assert!(written.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_unread_streamed_body_closes_connection() {
+        let written: String = run(&[
+            b"POST /discard HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nabc",
+            b"defghij",
+            b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        ])
+        .await;
+
+        assert!(written.starts_with("HTTP/1.1 202 Accepted\r\n"));
+        assert!(written.contains("Connection: close\r\n"));
+        assert!(!written.contains("HTTP/1.1 200 OK"));
+
+        let written: String =
+            run(&[b"POST /discard HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nabcGET / HTTP/1.1\r\nHost: localhost\r\n\r\n"]).await;
+
+        assert!(!written.contains("Connection: close"));
+        assert_eq!(written.matches("HTTP/1.1 200 OK").count(), 1);
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_streamed_response_is_sent_as_written() {
+        let written: String = run(&[
+            b"GET /stream HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        ])
+        .await;
+        let (streamed, next): (&str, &str) = written.split_once("0\r\n\r\n").unwrap();
+
+        assert!(streamed.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(streamed.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(streamed.ends_with("\r\n\r\n7\r\nhello, \r\n5\r\nworld\r\n"));
+        assert!(next.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(next.ends_with("hello"));
+
+        let written: String = run(&[b"GET /stream HTTP/1.0\r\n\r\n", b"GET / HTTP/1.0\r\n\r\n"]).await;
+
+        assert!(written.contains("Connection: close\r\n"));
+        assert!(!written.contains("Transfer-Encoding"));
+        assert!(written.ends_with("\r\n\r\nhello, world"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_request_timeout_drops_slow_handler() {
+        let written: String = run_with(
+            &[
+                b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n",
+                b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            ],
+            |connection: &mut Connection<(), MockStream>| connection.request_timeout = Some(Duration::from_millis(10)),
+        )
+        .await;
+
+        assert!(written.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(written.ends_with("hello"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_max_body_size_rejects_declared_and_received_bodies() {
+        let limit_body = |connection: &mut Connection<(), MockStream>| connection.max_body_size = Some(4);
+
+        let declared: String = run_with(
+            &[b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\n"],
+            limit_body,
+        )
+        .await;
+        assert!(declared.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+        let received: String = run_with(
+            &[
+                b"GET / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n",
+                b"5\r\nhello\r\n",
+            ],
+            limit_body,
+        )
+        .await;
+        assert!(received.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+        let within: String = run_with(
+            &[b"GET / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\nabcd"],
+            limit_body,
+        )
+        .await;
+        assert!(within.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[monoio::test(timer_enabled = true)]
+    async fn test_route_body_limit_replaces_listener_limit_while_reading() {
+        let received: String = run(&[
+            b"POST /small HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n",
+            b"5\r\nhello\r\n",
+        ])
+        .await;
+        assert!(received.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+        let within: String = run_with(
+            &[b"POST /large HTTP/1.1\r\nHost: localhost\r\nContent-Length: 8\r\n\r\nabcdefgh"],
+            |connection: &mut Connection<(), MockStream>| connection.max_body_size = Some(4),
+        )
+        .await;
+        assert!(within.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(within.ends_with("8 bytes"));
     }
 }
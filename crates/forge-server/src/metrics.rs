@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use forge_http::{HttpMethod, HttpStatus};
+
+pub(crate) type MetricsHook = dyn Fn(&RequestMetrics) + Send + Sync;
+
+/// Time spent in each phase of a request that reached a handler, passed to the hook set with
+/// `Listener::with_metrics`. `read` starts once the first bytes of the request are available, so
+/// a keep-alive connection idling between requests is not counted against it. Requests rejected
+/// before routing succeeds (malformed input, unknown paths, oversized heads) are not reported.
+///
+/// `bytes_in` counts the request as it arrived, head and (possibly chunked) body, and `bytes_out`
+/// the response as it was written, head and framing included.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestMetrics {
+    pub method: HttpMethod,
+    pub route: &'static str,
+    pub status: HttpStatus,
+    pub read: Duration,
+    pub parse: Duration,
+    pub route_match: Duration,
+    pub handler: Duration,
+    pub write: Duration,
+    pub bytes_in: usize,
+    pub bytes_out: usize,
+}
+
+/// Running totals of `RequestMetrics::bytes_in` and `bytes_out` across every worker, kept once
+/// passed to `Listener::with_traffic_counters`. The same requests are counted as are reported to
+/// the metrics hook.
+#[derive(Debug, Default)]
+pub struct TrafficCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl TrafficCounters {
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record(&self, bytes_in: usize, bytes_out: usize) {
+        self.bytes_in.fetch_add(bytes_in as u64, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes_out as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::Error;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use super::ListenerError;
+use super::listener::{bind, run_workers};
+use super::state_source::StateSource;
+use forge_router::StateHandle;
+use forge_router::handler::LocalBoxFuture;
+use monoio::net::{TcpListener, TcpStream};
+
+/// `host`, `dual_stack` and `threads` mean the same as on `ListenerOptions`.
+pub struct RawListenerOptions {
+    pub port: u16,
+    pub host: IpAddr,
+    pub dual_stack: bool,
+    pub threads: Option<usize>,
+}
+
+type StreamHandler<T> =
+    dyn Fn(TcpStream, SocketAddr, Option<StateHandle<T>>) -> LocalBoxFuture<'static, ()> + Send + Sync;
+
+/// Accepts connections the way `Listener` does, one runtime per worker thread with the port
+/// shared through `SO_REUSEPORT`, but hands each one to `handler` as a raw `TcpStream` instead of
+/// parsing HTTP from it. `read_frame` and `write_frame` speak a length-prefixed protocol over that
+/// stream; anything else can be read and written directly.
+///
+/// A raw listener has no router, and there is no way to serve HTTP and a raw protocol on the same
+/// port. To run both, give each listener its own port and call `run` on one of them from a thread
+/// of its own, since `run` blocks until its workers stop. State is shared or built per worker as
+/// with `Listener::with_state` and `Listener::with_state_factory`.
+pub struct RawListener<T> {
+    handler: Arc<StreamHandler<T>>,
+    state: Option<StateSource<T>>,
+    options: RawListenerOptions,
+}
+
+impl<T> RawListener<T>
+where
+    T: 'static,
+{
+    pub fn new<F, K>(options: RawListenerOptions, handler: F) -> Self
+    where
+        F: Fn(TcpStream, SocketAddr, Option<StateHandle<T>>) -> K + Send + Sync + 'static,
+        K: Future<Output = ()> + 'static,
+    {
+        Self {
+            options,
+            handler: Arc::new(move |stream: TcpStream, peer_addr: SocketAddr, state: Option<StateHandle<T>>| {
+                Box::pin(handler(stream, peer_addr, state))
+            }),
+            state: None,
+        }
+    }
+
+    pub fn with_state(mut self, state: T) -> Self
+    where
+        T: Send + Sync,
+    {
+        self.state = Some(StateSource::shared(state));
+        self
+    }
+
+    pub fn with_state_factory<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        self.state = Some(StateSource::per_worker(factory));
+        self
+    }
+
+    pub fn run(self) -> Result<(), ListenerError> {
+        let addr: SocketAddr = SocketAddr::from((self.options.host, self.options.port));
+        let dual_stack: bool = self.options.dual_stack;
+
+        println!("Raw listener running on {addr}");
+        run_workers(self.options.threads, |idx: usize| {
+            let handler: Arc<StreamHandler<T>> = self.handler.clone();
+            let state_source: Option<StateSource<T>> = self.state.clone();
+
+            move || async move {
+                let worker_state: Option<StateHandle<T>> = state_source.as_ref().map(StateSource::build);
+
+                let listener: TcpListener =
+                    bind(addr, dual_stack).map_err(|e: Error| ListenerError::Bind(addr, idx, e))?;
+
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, peer_addr)) => {
+                            if let Err(e) = stream.set_nodelay(true) {
+                                eprintln!("Failed to set 'TCP_NODELAY' on worker #{idx}: {e:?}");
+                            }
+
+                            monoio::spawn(handler(stream, peer_addr, worker_state.clone()));
+                        }
+                        Err(e) => {
+                            eprintln!("Failed to accept connection on worker #{idx}: {e:?}");
+                        }
+                    }
+                }
+
+                #[allow(unreachable_code)]
+                Ok(())
+            }
+        })
+    }
+}
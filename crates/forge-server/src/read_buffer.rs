@@ -0,0 +1,111 @@
+use std::mem;
+
+/// Bytes read from a connection that no request has claimed yet. `take` hands out exactly the
+/// bytes of one request, and whatever follows them (the start of a pipelined request) stays here
+/// for the next one. Consumed bytes are only skipped over until the next `extend`, which moves the
+/// unread bytes to the front before appending, so nothing left from an earlier request is ever
+/// read again.
+#[derive(Debug, Default)]
+pub(crate) struct ReadBuffer {
+    bytes: Vec<u8>,
+    consumed: usize,
+}
+
+impl ReadBuffer {
+    pub(crate) fn unread(&self) -> &[u8] {
+        &self.bytes[self.consumed..]
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.unread().is_empty()
+    }
+
+    pub(crate) fn extend(&mut self, bytes: &[u8]) {
+        self.compact();
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn consume(&mut self, len: usize) {
+        self.consumed += len.min(self.unread().len());
+
+        if self.consumed == self.bytes.len() {
+            self.bytes.clear();
+            self.consumed = 0;
+        }
+    }
+
+    /// Drops the empty lines a client may send between requests.
+    pub(crate) fn skip_empty_lines(&mut self) {
+        let empty_lines: usize = self
+            .unread()
+            .iter()
+            .take_while(|byte: &&u8| matches!(byte, b'\r' | b'\n'))
+            .count();
+
+        self.consume(empty_lines);
+    }
+
+    /// Moves the first `len` unread bytes into `message`, replacing what it held. When they are all
+    /// that is buffered the two vectors are swapped instead of copied.
+    pub(crate) fn take(&mut self, len: usize, mut message: Vec<u8>) -> Vec<u8> {
+        message.clear();
+
+        if self.consumed == 0 && len == self.bytes.len() {
+            return mem::replace(&mut self.bytes, message);
+        }
+
+        message.extend_from_slice(&self.unread()[..len]);
+        self.consume(len);
+        message
+    }
+
+    pub(crate) fn take_all(&mut self) -> Vec<u8> {
+        self.compact();
+        mem::take(&mut self.bytes)
+    }
+
+    fn compact(&mut self) {
+        if self.consumed > 0 {
+            self.bytes.copy_within(self.consumed.., 0);
+            self.bytes.truncate(self.bytes.len() - self.consumed);
+            self.consumed = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_message_after_long_one_sees_no_stale_bytes() {
+        let mut buffer: ReadBuffer = ReadBuffer::default();
+        buffer.extend(b"GET /a/long/path HTTP/1.1\r\n\r\n");
+
+        let long: Vec<u8> = buffer.take(buffer.unread().len(), Vec::new());
+        assert_eq!(long, b"GET /a/long/path HTTP/1.1\r\n\r\n");
+        assert!(buffer.is_empty());
+
+        buffer.extend(b"GET / HTTP/1.1\r\n\r\n");
+        let short: Vec<u8> = buffer.take(buffer.unread().len(), long);
+        assert_eq!(short, b"GET / HTTP/1.1\r\n\r\n");
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_pipelined_bytes_are_compacted_to_the_front() {
+        let mut buffer: ReadBuffer = ReadBuffer::default();
+        buffer.extend(b"\r\nGET /first HTTP/1.1\r\n\r\nGET /sec");
+        buffer.skip_empty_lines();
+
+        let first: Vec<u8> = buffer.take(23, Vec::new());
+        assert_eq!(first, b"GET /first HTTP/1.1\r\n\r\n");
+        assert_eq!(buffer.unread(), b"GET /sec");
+
+        buffer.extend(b"ond HTTP/1.1\r\n\r\n");
+        assert_eq!(buffer.consumed, 0);
+        assert_eq!(buffer.bytes, b"GET /second HTTP/1.1\r\n\r\n");
+        assert_eq!(buffer.take_all(), b"GET /second HTTP/1.1\r\n\r\n");
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::{Arc, PoisonError, RwLock, RwLockWriteGuard};
+
+use forge_router::Router;
+
+/// Swaps the router of a running `Listener`, returned by `Listener::router_handle`. `replace`
+/// finalizes the new router and installs it for every worker at once: a request that starts after
+/// `replace` returns is routed by the new router, while requests already being handled finish on
+/// the router they started with, which is dropped once the last of them is done.
+///
+/// The router sits behind a `RwLock`, so unlocking it in `replace` happens-before every later read
+/// of it and a worker never sees a partially built router. Each request reads it once, when it
+/// starts, and keeps its own `Arc` to the result. The listener installs the router it was built
+/// with when `run` starts, replacing anything installed before that. Routes added by
+/// `Listener::with_health_checks` are only part of that first router; register `HealthChecks` on a
+/// replacement to keep them.
+pub struct RouterHandle<T> {
+    current: Arc<RwLock<Arc<Router<T>>>>,
+}
+
+impl<T> Clone for RouterHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            current: Arc::clone(&self.current),
+        }
+    }
+}
+
+impl<T> RouterHandle<T>
+where
+    T: 'static,
+{
+    pub(crate) fn new(router: Router<T>) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(router))),
+        }
+    }
+
+    /// Installs `router` and returns the one it replaced.
+    pub fn replace(&self, mut router: Router<T>) -> Arc<Router<T>> {
+        router.finalize();
+        let router: Arc<Router<T>> = Arc::new(router);
+        let mut current: RwLockWriteGuard<Arc<Router<T>>> =
+            self.current.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, router)
+    }
+
+    pub fn current(&self) -> Arc<Router<T>> {
+        Arc::clone(&self.current.read().unwrap_or_else(PoisonError::into_inner))
+    }
+}
@@ -0,0 +1,89 @@
+use std::borrow::Cow;
+
+use forge_http::Response;
+
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    pub strict_transport_security: Option<Cow<'static, str>>,
+    pub content_type_options: Option<Cow<'static, str>>,
+    pub frame_options: Option<Cow<'static, str>>,
+    pub content_security_policy: Option<Cow<'static, str>>,
+    pub referrer_policy: Option<Cow<'static, str>>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            strict_transport_security: Some(Cow::Borrowed("max-age=31536000; includeSubDomains")),
+            content_type_options: Some(Cow::Borrowed("nosniff")),
+            frame_options: Some(Cow::Borrowed("DENY")),
+            content_security_policy: None,
+            referrer_policy: None,
+        }
+    }
+}
+
+impl SecurityHeaders {
+    pub fn apply<'a>(&self, response: Response<'a>, secure: bool) -> Response<'a> {
+        let hsts: Option<&Cow<'static, str>> = self.strict_transport_security.as_ref().filter(|_| secure);
+
+        [
+            ("Strict-Transport-Security", hsts),
+            ("X-Content-Type-Options", self.content_type_options.as_ref()),
+            ("X-Frame-Options", self.frame_options.as_ref()),
+            ("Content-Security-Policy", self.content_security_policy.as_ref()),
+            ("Referrer-Policy", self.referrer_policy.as_ref()),
+        ]
+        .into_iter()
+        .fold(
+            response,
+            |response: Response<'a>, (key, value): (&'static str, Option<&Cow<'static, str>>)| match value {
+                Some(value) if response.get_header(key).is_none() => response.header(key, value.clone()),
+                _ => response,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use forge_http::HttpStatus;
+
+    #[test]
+    fn test_defaults_over_plain_connection() {
+        let response: Response = SecurityHeaders::default().apply(Response::new(HttpStatus::Ok), false);
+
+        assert_eq!(response.get_header("x-content-type-options"), Some("nosniff"));
+        assert_eq!(response.get_header("X-Frame-Options"), Some("DENY"));
+        assert_eq!(response.get_header("Strict-Transport-Security"), None);
+        assert_eq!(response.get_header("Content-Security-Policy"), None);
+        assert_eq!(response.get_header("Referrer-Policy"), None);
+    }
+
+    #[test]
+    fn test_hsts_only_on_secure_connection() {
+        let response: Response = SecurityHeaders::default().apply(Response::new(HttpStatus::Ok), true);
+
+        assert_eq!(
+            response.get_header("Strict-Transport-Security"),
+            Some("max-age=31536000; includeSubDomains")
+        );
+    }
+
+    #[test]
+    fn test_handler_headers_take_precedence() {
+        let headers: SecurityHeaders = SecurityHeaders {
+            content_security_policy: Some(Cow::Owned(String::from("default-src 'self'"))),
+            referrer_policy: Some(Cow::Borrowed("no-referrer")),
+            ..SecurityHeaders::default()
+        };
+
+        let response: Response = Response::new(HttpStatus::Ok).header("X-Frame-Options", "SAMEORIGIN");
+        let response: Response = headers.apply(response, false);
+
+        assert_eq!(response.get_header("X-Frame-Options"), Some("SAMEORIGIN"));
+        assert_eq!(response.get_header("Content-Security-Policy"), Some("default-src 'self'"));
+        assert_eq!(response.get_header("Referrer-Policy"), Some("no-referrer"));
+    }
+}
@@ -0,0 +1,57 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use forge_router::{StateHandle, StateKind};
+
+type StateFactory<T> = dyn Fn() -> StateHandle<T> + Send + Sync;
+
+/// Where a listener's workers get their state from. It is called once on each worker thread, and
+/// only a `shared` value has to be `Send + Sync`, since it is the only one that crosses threads.
+pub(crate) struct StateSource<T> {
+    kind: StateKind,
+    factory: Arc<StateFactory<T>>,
+}
+
+impl<T> Clone for StateSource<T> {
+    fn clone(&self) -> Self {
+        Self {
+            kind: self.kind,
+            factory: self.factory.clone(),
+        }
+    }
+}
+
+impl<T> StateSource<T>
+where
+    T: 'static,
+{
+    pub(crate) fn shared(state: T) -> Self
+    where
+        T: Send + Sync,
+    {
+        let state: Arc<T> = Arc::new(state);
+
+        Self {
+            kind: StateKind::Shared,
+            factory: Arc::new(move || StateHandle::Shared(Arc::clone(&state))),
+        }
+    }
+
+    pub(crate) fn per_worker<F>(factory: F) -> Self
+    where
+        F: Fn() -> T + Send + Sync + 'static,
+    {
+        Self {
+            kind: StateKind::Local,
+            factory: Arc::new(move || StateHandle::Local(Rc::new(factory()))),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> StateKind {
+        self.kind
+    }
+
+    pub(crate) fn build(&self) -> StateHandle<T> {
+        (self.factory)()
+    }
+}
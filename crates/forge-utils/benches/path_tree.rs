@@ -0,0 +1,77 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use forge_utils::{PathTree, Segment};
+
+const RESOURCES: usize = 50;
+const ROUNDS: usize = 20_000;
+
+fn routes() -> Vec<String> {
+    (0..RESOURCES)
+        .flat_map(|idx: usize| {
+            [
+                format!("/api/v1/resource{idx}"),
+                format!("/api/v1/resource{idx}/:id"),
+                format!("/api/v1/resource{idx}/:id/items"),
+                format!("/api/v1/resource{idx}/:id/items/:item"),
+            ]
+        })
+        .collect()
+}
+
+fn lookups() -> Vec<String> {
+    (0..RESOURCES)
+        .step_by(7)
+        .flat_map(|idx: usize| {
+            [
+                format!("/api/v1/resource{idx}"),
+                format!("/api/v1/resource{idx}/42"),
+                format!("/api/v1/resource{idx}/42/items"),
+                format!("/api/v1/resource{idx}/42/items/7"),
+                format!("/api/v1/resource{idx}/42/missing"),
+            ]
+        })
+        .collect()
+}
+
+fn segments(path: &str) -> impl Iterator<Item = Segment<'_>> {
+    path.split('/')
+        .filter(|s: &&str| !s.is_empty())
+        .map(|s: &str| match s.strip_prefix(':') {
+            Some(name) => Segment::Param(name),
+            None => Segment::Exact(s),
+        })
+}
+
+fn measure(tree: &PathTree<usize>, lookups: &[String]) -> Duration {
+    let start: Instant = Instant::now();
+
+    for _ in 0..ROUNDS {
+        for path in lookups {
+            black_box(tree.find(path.split('/').filter(|s: &&str| !s.is_empty())));
+        }
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    let routes: Vec<String> = routes();
+    let lookups: Vec<String> = lookups();
+    let mut tree: PathTree<usize> = PathTree::new();
+
+    for (idx, route) in routes.iter().enumerate() {
+        tree.insert(segments(route), idx);
+    }
+
+    let total: f64 = (ROUNDS * lookups.len()) as f64;
+    let per_lookup = |elapsed: Duration| -> f64 { elapsed.as_nanos() as f64 / total };
+
+    let tree_elapsed: Duration = measure(&tree, &lookups);
+    tree.compile();
+    let compiled_elapsed: Duration = measure(&tree, &lookups);
+
+    println!("{} routes, {} lookups per round, {ROUNDS} rounds", routes.len(), lookups.len());
+    println!("tree:     {:>8.1} ns/lookup", per_lookup(tree_elapsed));
+    println!("compiled: {:>8.1} ns/lookup", per_lookup(compiled_elapsed));
+}
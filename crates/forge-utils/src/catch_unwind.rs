@@ -0,0 +1,88 @@
+use std::any::Any;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+pub type PanicPayload = Box<dyn Any + Send>;
+
+/// Turns a panic while polling `future` into an `Err` holding its payload. This only works when
+/// the binary is built with `panic = "unwind"`: with `panic = "abort"` the process exits at the
+/// panic, before there is anything to catch.
+pub struct CatchUnwind<F> {
+    future: F,
+}
+
+impl<F> CatchUnwind<F>
+where
+    F: Future + Unpin,
+{
+    pub fn new(future: F) -> Self {
+        Self { future }
+    }
+}
+
+impl<F> Future for CatchUnwind<F>
+where
+    F: Future + Unpin,
+{
+    type Output = Result<F::Output, PanicPayload>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let future: Pin<&mut F> = Pin::new(&mut self.future);
+
+        match panic::catch_unwind(AssertUnwindSafe(|| future.poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+pub fn panic_message(payload: &PanicPayload) -> &str {
+    payload
+        .downcast_ref::<&'static str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::Waker;
+
+    fn poll_once<F: Future + Unpin>(future: F) -> Poll<Result<F::Output, PanicPayload>> {
+        let mut cx: Context = Context::from_waker(Waker::noop());
+        Pin::new(&mut CatchUnwind::new(future)).poll(&mut cx)
+    }
+
+    #[test]
+    fn test_passes_output_through() {
+        let result: Poll<Result<u8, PanicPayload>> = poll_once(Box::pin(async { 7 }));
+        assert!(matches!(result, Poll::Ready(Ok(7))));
+    }
+
+    #[test]
+    fn test_catches_panic_payload() {
+        let query: String = String::from("SELECT 1");
+
+        let result: Poll<Result<(), PanicPayload>> = poll_once(Box::pin(async move {
+            panic!("worker failed on {query}");
+        }));
+
+        match result {
+            Poll::Ready(Err(payload)) => assert_eq!(panic_message(&payload), "worker failed on SELECT 1"),
+            _ => panic!("Expected the panic to be caught"),
+        }
+    }
+
+    #[test]
+    fn test_static_str_payload() {
+        let payload: PanicPayload = Box::new("static message");
+        assert_eq!(panic_message(&payload), "static message");
+
+        let unknown: PanicPayload = Box::new(42);
+        assert_eq!(panic_message(&unknown), "unknown cause");
+    }
+}
@@ -1,5 +1,9 @@
+pub mod catch_unwind;
 pub mod lru_cache;
 pub mod path_tree;
+pub mod percent;
 
+pub use catch_unwind::{CatchUnwind, PanicPayload, panic_message};
 pub use lru_cache::LruCache;
 pub use path_tree::{PathMatch, PathTree, Segment};
+pub use percent::{EncodeSet, percent_decode, percent_encode};
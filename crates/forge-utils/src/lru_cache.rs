@@ -1,12 +1,39 @@
 use std::collections::{HashMap, VecDeque};
-use std::future::Future;
+use std::future::{self, Future};
 use std::hash::Hash;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
 
 #[derive(Debug)]
 pub struct LruCache<K, V> {
     capacity: usize,
     order: VecDeque<K>,
     map: HashMap<K, V>,
+    in_flight: HashMap<K, Vec<Waker>>,
+}
+
+/// Held by the caller running the fetcher in `get_or_fetch_shared`. Dropping it, whether the fetch
+/// finished or the caller was cancelled, releases the key and wakes everyone waiting on it.
+struct FetchGuard<'c, K, V>
+where
+    K: Hash + Eq,
+{
+    cache: &'c Mutex<LruCache<K, V>>,
+    key: K,
+}
+
+impl<K, V> Drop for FetchGuard<'_, K, V>
+where
+    K: Hash + Eq,
+{
+    fn drop(&mut self) {
+        let waiters: Option<Vec<Waker>> = lock(self.cache).in_flight.remove(&self.key);
+        waiters.into_iter().flatten().for_each(Waker::wake);
+    }
+}
+
+fn lock<K, V>(cache: &Mutex<LruCache<K, V>>) -> MutexGuard<'_, LruCache<K, V>> {
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
 impl<K, V> LruCache<K, V>
@@ -19,6 +46,7 @@ where
             capacity,
             map: HashMap::new(),
             order: VecDeque::with_capacity(capacity),
+            in_flight: HashMap::new(),
         }
     }
 
@@ -37,6 +65,56 @@ where
         Ok(val)
     }
 
+    /// Like `get_or_fetch`, but for a cache shared behind a `Mutex`, which is not held while the
+    /// fetcher runs. Only the first caller to miss a key runs its fetcher; callers that miss the
+    /// same key meanwhile wait for that fetch and get its value. A failed fetch is not shared: the
+    /// error goes to the caller that ran it, and one of the waiters runs its own fetcher next. The
+    /// same happens when the running caller is dropped, so cancelling it (for example with a
+    /// timeout around this call) never leaves the others waiting forever.
+    pub async fn get_or_fetch_shared<T, F, E>(cache: &Mutex<Self>, key: K, fetcher: T) -> Result<V, E>
+    where
+        T: FnOnce(&K) -> F,
+        F: Future<Output = Result<V, E>>,
+    {
+        let cached: Option<V> = future::poll_fn(|cx: &mut Context| {
+            let mut cache: MutexGuard<Self> = lock(cache);
+
+            if let Some(val) = cache.get(&key) {
+                return Poll::Ready(Some(val));
+            }
+
+            match cache.in_flight.get_mut(&key) {
+                Some(waiters) => {
+                    if !waiters.iter().any(|waker: &Waker| waker.will_wake(cx.waker())) {
+                        waiters.push(cx.waker().clone());
+                    }
+
+                    Poll::Pending
+                }
+                None => {
+                    cache.in_flight.insert(key.clone(), Vec::new());
+                    Poll::Ready(None)
+                }
+            }
+        })
+        .await;
+
+        if let Some(val) = cached {
+            return Ok(val);
+        }
+
+        let guard: FetchGuard<K, V> = FetchGuard { cache, key };
+        let val: V = fetcher(&guard.key).await?;
+        lock(cache).insert(guard.key.clone(), val.clone());
+        Ok(val)
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let val: V = self.map.get(key).cloned()?;
+        self.touch(key);
+        Some(val)
+    }
+
     fn touch(&mut self, key: &K) {
         if self.order.back().is_some_and(|last: &K| last == key) {
             return;
@@ -49,7 +127,7 @@ where
         self.order.push_back(key.clone());
     }
 
-    fn insert(&mut self, key: K, val: V) {
+    pub fn insert(&mut self, key: K, val: V) {
         if self.capacity == 0 {
             return;
         }
@@ -70,3 +148,63 @@ where
         self.order.push_back(key);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::pin::{Pin, pin};
+
+    fn poll<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+        future.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_concurrent_misses_share_one_fetch() {
+        let cache: Mutex<LruCache<&str, u32>> = Mutex::new(LruCache::new(4));
+        let fetches: Cell<usize> = Cell::new(0);
+        let ready: Cell<bool> = Cell::new(false);
+
+        let fetch = |_: &&str| {
+            fetches.set(fetches.get() + 1);
+            future::poll_fn(|_: &mut Context| match ready.get() {
+                true => Poll::Ready(Ok::<u32, ()>(7)),
+                false => Poll::Pending,
+            })
+        };
+
+        let mut first = pin!(LruCache::get_or_fetch_shared(&cache, "users", fetch));
+        let mut second = pin!(LruCache::get_or_fetch_shared(&cache, "users", fetch));
+
+        assert!(poll(first.as_mut()).is_pending());
+        assert!(poll(second.as_mut()).is_pending());
+
+        ready.set(true);
+        assert_eq!(poll(first.as_mut()), Poll::Ready(Ok(7)));
+        assert_eq!(poll(second.as_mut()), Poll::Ready(Ok(7)));
+        assert_eq!(fetches.get(), 1);
+    }
+
+    #[test]
+    fn test_waiter_takes_over_when_fetch_is_cancelled_or_fails() {
+        let cache: Mutex<LruCache<&str, u32>> = Mutex::new(LruCache::new(4));
+        let mut waiter = pin!(LruCache::get_or_fetch_shared(&cache, "users", |_: &&str| async {
+            Ok::<u32, &str>(3)
+        }));
+
+        {
+            let mut cancelled = pin!(LruCache::get_or_fetch_shared(&cache, "users", |_: &&str| {
+                future::pending::<Result<u32, &str>>()
+            }));
+            assert!(poll(cancelled.as_mut()).is_pending());
+            assert!(poll(waiter.as_mut()).is_pending());
+        }
+
+        let mut failed = pin!(LruCache::get_or_fetch_shared(&cache, "posts", |_: &&str| async { Err("boom") }));
+        assert_eq!(poll(failed.as_mut()), Poll::Ready(Err("boom")));
+        assert_eq!(lock(&cache).get(&"posts"), None);
+
+        assert_eq!(poll(waiter.as_mut()), Poll::Ready(Ok(3)));
+        assert_eq!(lock(&cache).get(&"users"), Some(3));
+    }
+}
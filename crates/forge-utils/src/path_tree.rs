@@ -1,4 +1,10 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+const LINEAR_SCAN_EDGES: usize = 8;
 
 #[derive(Debug)]
 pub enum Segment<'a> {
@@ -6,15 +12,37 @@ pub enum Segment<'a> {
     Param(&'a str),
 }
 
+/// `tail` holds the segments a fallback match did not consume, joined with `/`, so a fallback
+/// registered at `/static` and matched against `/static/css/site.css` has the tail `css/site.css`.
+/// It is empty for exact matches and for a fallback matched at its own path.
 #[derive(Debug)]
 pub struct PathMatch<'a, 'b, T> {
     pub value: &'a T,
+    pub is_fallback: bool,
     pub params: Vec<(&'a str, &'b str)>,
+    pub tail: Cow<'b, str>,
+}
+
+impl<T> PathMatch<'_, '_, T> {
+    pub fn tail(&self) -> &str {
+        &self.tail
+    }
 }
 
+/// Routes are inserted into a tree of hash maps. `compile` flattens it into one array of nodes laid
+/// out breadth-first, with the exact children of each node stored as a contiguous slice: small
+/// slices are scanned linearly and larger ones are sorted by segment hash and binary searched.
+/// Matching semantics are identical in both forms; inserting into a compiled tree turns it back
+/// into the map form until it is compiled again. `cargo bench -p forge-utils` compares the two.
 #[derive(Debug)]
 pub struct PathTree<T> {
-    root: Node<T>,
+    repr: Repr<T>,
+}
+
+#[derive(Debug)]
+enum Repr<T> {
+    Tree(Node<T>),
+    Compiled(CompiledTree<T>),
 }
 
 impl<T> Default for PathTree<T> {
@@ -26,6 +54,7 @@ impl<T> Default for PathTree<T> {
 #[derive(Debug)]
 pub struct Node<T> {
     value: Option<T>,
+    fallback: Option<T>,
     exact_child: HashMap<String, Node<T>>,
     param_child: Option<(String, Box<Node<T>>)>,
 }
@@ -34,22 +63,308 @@ impl<T> Default for Node<T> {
     fn default() -> Self {
         Self {
             value: None,
+            fallback: None,
             param_child: None,
             exact_child: HashMap::new(),
         }
     }
 }
 
+#[derive(Debug)]
+struct CompiledTree<T> {
+    nodes: Vec<CompiledNode<T>>,
+    edges: Vec<(Box<str>, usize)>,
+    edge_hashes: Vec<u64>,
+}
+
+#[derive(Debug)]
+struct CompiledNode<T> {
+    value: Option<T>,
+    fallback: Option<T>,
+    exact_edges: Range<usize>,
+    param_child: Option<(Box<str>, usize)>,
+}
+
+impl<T> Default for CompiledTree<T> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            edge_hashes: Vec::new(),
+        }
+    }
+}
+
+trait Cursor<'a, T>: Copy {
+    fn value(self) -> Option<&'a T>;
+    fn fallback(self) -> Option<&'a T>;
+    fn exact_child(self, path: &str) -> Option<Self>;
+    fn param_child(self) -> Option<(&'a str, Self)>;
+}
+
+impl<'a, T> Cursor<'a, T> for &'a Node<T> {
+    fn value(self) -> Option<&'a T> {
+        self.value.as_ref()
+    }
+
+    fn fallback(self) -> Option<&'a T> {
+        self.fallback.as_ref()
+    }
+
+    fn exact_child(self, path: &str) -> Option<Self> {
+        self.exact_child.get(path)
+    }
+
+    fn param_child(self) -> Option<(&'a str, Self)> {
+        self.param_child
+            .as_ref()
+            .map(|(key, child): &'a (String, Box<Node<T>>)| (key.as_str(), child.as_ref()))
+    }
+}
+
+impl<'a, T> Cursor<'a, T> for (&'a CompiledTree<T>, usize) {
+    fn value(self) -> Option<&'a T> {
+        self.0.nodes[self.1].value.as_ref()
+    }
+
+    fn fallback(self) -> Option<&'a T> {
+        self.0.nodes[self.1].fallback.as_ref()
+    }
+
+    fn exact_child(self, path: &str) -> Option<Self> {
+        let (tree, idx): (&CompiledTree<T>, usize) = self;
+        let exact_edges: Range<usize> = tree.nodes[idx].exact_edges.clone();
+
+        if exact_edges.len() <= LINEAR_SCAN_EDGES {
+            return tree.edges[exact_edges]
+                .iter()
+                .find(|(key, _): &&(Box<str>, usize)| key.as_ref() == path)
+                .map(|(_, child): &(Box<str>, usize)| (tree, *child));
+        }
+
+        let hash: u64 = segment_hash(path);
+
+        let hashes: &[u64] = &tree.edge_hashes[exact_edges.clone()];
+        let first: usize = exact_edges.start + hashes.partition_point(|&edge_hash: &u64| edge_hash < hash);
+
+        tree.edge_hashes[first..exact_edges.end]
+            .iter()
+            .take_while(|&&edge_hash: &&u64| edge_hash == hash)
+            .zip(&tree.edges[first..exact_edges.end])
+            .find(|(_, (key, _)): &(&u64, &(Box<str>, usize))| key.as_ref() == path)
+            .map(|(_, (_, child)): (&u64, &(Box<str>, usize))| (tree, *child))
+    }
+
+    fn param_child(self) -> Option<(&'a str, Self)> {
+        let (tree, idx): (&CompiledTree<T>, usize) = self;
+
+        tree.nodes[idx]
+            .param_child
+            .as_ref()
+            .map(|(key, child): &'a (Box<str>, usize)| (key.as_ref(), (tree, *child)))
+    }
+}
+
+impl<T> CompiledTree<T> {
+    fn new(root: Node<T>) -> Self {
+        let mut tree: Self = Self::default();
+
+        let mut queue: VecDeque<Node<T>> = VecDeque::from([root]);
+        let mut next_idx: usize = 1;
+
+        while let Some(node) = queue.pop_front() {
+            let mut exact_child: Vec<(u64, String, Node<T>)> = node
+                .exact_child
+                .into_iter()
+                .map(|(key, child): (String, Node<T>)| (segment_hash(&key), key, child))
+                .collect();
+
+            exact_child.sort_unstable_by(|(a_hash, a, _): &(u64, String, Node<T>), (b_hash, b, _)| {
+                (a_hash, a).cmp(&(b_hash, b))
+            });
+
+            let start: usize = tree.edges.len();
+
+            for (hash, key, child) in exact_child {
+                tree.edge_hashes.push(hash);
+                tree.edges.push((key.into_boxed_str(), next_idx));
+                queue.push_back(child);
+                next_idx += 1;
+            }
+
+            let param_child: Option<(Box<str>, usize)> =
+                node.param_child.map(|(key, child): (String, Box<Node<T>>)| {
+                    queue.push_back(*child);
+                    next_idx += 1;
+                    (key.into_boxed_str(), next_idx - 1)
+                });
+
+            tree.nodes.push(CompiledNode {
+                param_child,
+                value: node.value,
+                fallback: node.fallback,
+                exact_edges: start..tree.edges.len(),
+            });
+        }
+
+        tree
+    }
+
+    fn into_node(self) -> Node<T> {
+        let mut nodes: Vec<Option<CompiledNode<T>>> = self.nodes.into_iter().map(Some).collect();
+        Self::take_node(&mut nodes, &self.edges, 0)
+    }
+
+    fn take_node(nodes: &mut [Option<CompiledNode<T>>], edges: &[(Box<str>, usize)], idx: usize) -> Node<T> {
+        let Some(node) = nodes[idx].take() else {
+            return Node::default();
+        };
+
+        Node {
+            value: node.value,
+            fallback: node.fallback,
+            exact_child: edges[node.exact_edges]
+                .iter()
+                .map(|(key, child): &(Box<str>, usize)| (key.to_string(), Self::take_node(nodes, edges, *child)))
+                .collect(),
+            param_child: node.param_child.map(|(key, child): (Box<str>, usize)| {
+                (key.into_string(), Box::new(Self::take_node(nodes, edges, child)))
+            }),
+        }
+    }
+}
+
+fn segment_hash(segment: &str) -> u64 {
+    segment.bytes().fold(FNV_OFFSET_BASIS, |hash: u64, byte: u8| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
 impl<T> PathTree<T> {
     pub fn new() -> Self {
-        Self { root: Node::default() }
+        Self {
+            repr: Repr::Tree(Node::default()),
+        }
+    }
+
+    pub fn compile(&mut self) {
+        if let Repr::Tree(root) = &mut self.repr {
+            let root: Node<T> = std::mem::take(root);
+            self.repr = Repr::Compiled(CompiledTree::new(root));
+        }
+    }
+
+    pub fn is_compiled(&self) -> bool {
+        matches!(self.repr, Repr::Compiled(_))
     }
 
     pub fn insert<'a, I>(&mut self, segments: I, value: T) -> Option<T>
     where
         I: Iterator<Item = Segment<'a>>,
     {
-        let mut current: &mut Node<T> = &mut self.root;
+        self.walk_or_create(segments).value.replace(value)
+    }
+
+    pub fn insert_fallback<'a, I>(&mut self, segments: I, value: T) -> Option<T>
+    where
+        I: Iterator<Item = Segment<'a>>,
+    {
+        self.walk_or_create(segments).fallback.replace(value)
+    }
+
+    /// Returns the names of the first parameter in `segments` that sits where the tree already has
+    /// a parameter with a different name, as `(existing, incoming)`. Inserting such a path would
+    /// silently keep the existing name.
+    pub fn conflicting_param<'a, I>(&self, segments: I) -> Option<(&str, &'a str)>
+    where
+        I: Iterator<Item = Segment<'a>>,
+    {
+        match &self.repr {
+            Repr::Tree(root) => Self::conflicting_param_from(root, segments),
+            Repr::Compiled(tree) => Self::conflicting_param_from((tree, 0), segments),
+        }
+    }
+
+    fn conflicting_param_from<'t, 'a, C, I>(root: C, segments: I) -> Option<(&'t str, &'a str)>
+    where
+        C: Cursor<'t, T>,
+        I: Iterator<Item = Segment<'a>>,
+    {
+        let mut current: C = root;
+
+        for segment in segments {
+            current = match segment {
+                Segment::Exact(path) => current.exact_child(path)?,
+                Segment::Param(name) => {
+                    let (existing, child): (&str, C) = current.param_child()?;
+
+                    if existing != name {
+                        return Some((existing, name));
+                    }
+
+                    child
+                }
+            };
+        }
+
+        None
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.entries().map(|(value, _): (&T, bool)| value)
+    }
+
+    /// Every value with whether it was inserted as a fallback.
+    pub fn entries(&self) -> impl Iterator<Item = (&T, bool)> {
+        let root: &Node<T> = match &self.repr {
+            Repr::Tree(root) => root,
+            Repr::Compiled(tree) => {
+                return tree
+                    .nodes
+                    .iter()
+                    .flat_map(|node: &CompiledNode<T>| Self::node_entries(node.value.as_ref(), node.fallback.as_ref()))
+                    .collect::<Vec<(&T, bool)>>()
+                    .into_iter();
+            }
+        };
+
+        let mut values: Vec<(&T, bool)> = Vec::new();
+        let mut stack: Vec<&Node<T>> = vec![root];
+
+        while let Some(node) = stack.pop() {
+            values.extend(Self::node_entries(node.value.as_ref(), node.fallback.as_ref()));
+            stack.extend(node.exact_child.values());
+            stack.extend(
+                node.param_child
+                    .as_ref()
+                    .map(|(_, child): &(String, Box<Node<T>>)| child.as_ref()),
+            );
+        }
+
+        values.into_iter()
+    }
+
+    fn node_entries<'a>(value: Option<&'a T>, fallback: Option<&'a T>) -> impl Iterator<Item = (&'a T, bool)> {
+        value
+            .map(|value: &T| (value, false))
+            .into_iter()
+            .chain(fallback.map(|fallback: &T| (fallback, true)))
+    }
+
+    fn walk_or_create<'a, I>(&mut self, segments: I) -> &mut Node<T>
+    where
+        I: Iterator<Item = Segment<'a>>,
+    {
+        if let Repr::Compiled(tree) = &mut self.repr {
+            let tree: CompiledTree<T> = std::mem::take(tree);
+            self.repr = Repr::Tree(tree.into_node());
+        }
+
+        let Repr::Tree(root) = &mut self.repr else {
+            unreachable!()
+        };
+
+        let mut current: &mut Node<T> = root;
 
         for path in segments {
             match path {
@@ -65,27 +380,223 @@ impl<T> PathTree<T> {
             }
         }
 
-        current.value.replace(value)
+        current
     }
 
     pub fn find<'a, 'b, I>(&'a self, segments: I) -> Option<PathMatch<'a, 'b, T>>
     where
         I: Iterator<Item = &'b str>,
+    {
+        match &self.repr {
+            Repr::Tree(root) => Self::find_from(root, segments),
+            Repr::Compiled(tree) => Self::find_from((tree, 0), segments),
+        }
+    }
+
+    fn find_from<'a, 'b, C, I>(root: C, mut segments: I) -> Option<PathMatch<'a, 'b, T>>
+    where
+        C: Cursor<'a, T>,
+        I: Iterator<Item = &'b str>,
     {
         let mut params: Vec<(&str, &str)> = Vec::with_capacity(2);
-        let mut current: &Node<T> = &self.root;
+        let mut current: C = root;
+        let mut fallback: Option<(&T, usize)> = current.fallback().map(|value: &T| (value, 0));
+        let mut tail: Vec<&str> = Vec::new();
+        let mut unmatched: Option<&str> = None;
 
-        for path in segments {
-            if let Some(next_node) = current.exact_child.get(path) {
+        for path in segments.by_ref() {
+            if let Some(next_node) = current.exact_child(path) {
                 current = next_node
-            } else if let Some((key, next_node)) = &current.param_child {
-                params.push((key.as_str(), path));
+            } else if let Some((key, next_node)) = current.param_child() {
+                params.push((key, path));
                 current = next_node
             } else {
-                return None;
+                unmatched = Some(path);
+                break;
+            }
+
+            if let Some(value) = current.fallback() {
+                fallback = Some((value, params.len()));
+                tail.clear();
+            } else if fallback.is_some() {
+                tail.push(path);
+            }
+        }
+
+        if let Some(path) = unmatched {
+            if fallback.is_some() {
+                tail.push(path);
+                tail.extend(segments);
             }
+
+            return Self::fallback_match(fallback, params, tail);
+        }
+
+        match current.value() {
+            Some(value) => Some(PathMatch {
+                value,
+                params,
+                is_fallback: false,
+                tail: Cow::Borrowed(""),
+            }),
+            None => Self::fallback_match(fallback, params, tail),
+        }
+    }
+
+    fn fallback_match<'a, 'b>(
+        fallback: Option<(&'a T, usize)>,
+        mut params: Vec<(&'a str, &'b str)>,
+        tail: Vec<&'b str>,
+    ) -> Option<PathMatch<'a, 'b, T>> {
+        let (value, params_len): (&T, usize) = fallback?;
+        params.truncate(params_len);
+
+        let tail: Cow<str> = match tail.as_slice() {
+            [] => Cow::Borrowed(""),
+            [segment] => Cow::Borrowed(*segment),
+            segments => Cow::Owned(segments.join("/")),
+        };
+
+        Some(PathMatch {
+            value,
+            params,
+            tail,
+            is_fallback: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Matched = Option<(&'static str, bool, Vec<(String, String)>)>;
+
+    fn segments(path: &str) -> impl Iterator<Item = Segment<'_>> {
+        path.split('/')
+            .filter(|s: &&str| !s.is_empty())
+            .map(|s: &str| match s.strip_prefix(':') {
+                Some(name) => Segment::Param(name),
+                None => Segment::Exact(s),
+            })
+    }
+
+    fn find(tree: &PathTree<&'static str>, path: &str) -> Matched {
+        tree.find(path.split('/').filter(|s: &&str| !s.is_empty()))
+            .map(|m: PathMatch<&'static str>| {
+                let params: Vec<(String, String)> = m
+                    .params
+                    .iter()
+                    .map(|(key, value): &(&str, &str)| (key.to_string(), value.to_string()))
+                    .collect();
+
+                (*m.value, m.is_fallback, params)
+            })
+    }
+
+    #[test]
+    fn test_compiled_tree_matches_like_tree() {
+        let mut tree: PathTree<&'static str> = PathTree::new();
+
+        for route in [
+            "/",
+            "/users",
+            "/users/all",
+            "/users/:id",
+            "/users/:id/posts/:post",
+            "/a/b/c",
+        ] {
+            tree.insert(segments(route), route);
         }
 
-        current.value.as_ref().map(|value: &T| PathMatch { value, params })
+        tree.insert_fallback(segments("/api"), "/api/*");
+
+        let paths: [&str; 9] = [
+            "/",
+            "/users",
+            "/users/all",
+            "/users/42",
+            "/users/42/posts/7",
+            "/users/42/comments",
+            "/api/missing/deep",
+            "/a/b",
+            "/zzz",
+        ];
+
+        let expected: Vec<Matched> = paths.iter().map(|path: &&str| find(&tree, path)).collect();
+        tree.compile();
+
+        assert!(tree.is_compiled());
+        assert_eq!(
+            paths
+                .iter()
+                .map(|path: &&str| find(&tree, path))
+                .collect::<Vec<Matched>>(),
+            expected
+        );
+        assert_eq!(tree.values().count(), 7);
+        assert_eq!(
+            tree.entries().find(|(_, is_fallback): &(&&str, bool)| *is_fallback),
+            Some((&"/api/*", true))
+        );
+    }
+
+    #[test]
+    fn test_compiled_tree_with_wide_fan_out() {
+        let names: Vec<String> = (0..32).map(|idx: usize| format!("item{idx}")).collect();
+        let mut tree: PathTree<&'static str> = PathTree::new();
+
+        for name in &names {
+            tree.insert([Segment::Exact("wide"), Segment::Exact(name)].into_iter(), "exact");
+        }
+
+        tree.insert(segments("/wide/:name"), "param");
+        tree.compile();
+
+        for name in &names {
+            assert_eq!(find(&tree, &format!("/wide/{name}")), Some(("exact", false, vec![])));
+        }
+
+        assert_eq!(
+            find(&tree, "/wide/item99"),
+            Some(("param", false, vec![(String::from("name"), String::from("item99"))]))
+        );
+    }
+
+    #[test]
+    fn test_insert_after_compile() {
+        let mut tree: PathTree<&'static str> = PathTree::new();
+        tree.insert(segments("/users/:id"), "user");
+        tree.compile();
+
+        assert_eq!(tree.insert(segments("/users/all"), "all"), None);
+        assert!(!tree.is_compiled());
+        assert_eq!(find(&tree, "/users/all"), Some(("all", false, vec![])));
+        assert_eq!(
+            find(&tree, "/users/42"),
+            Some(("user", false, vec![(String::from("id"), String::from("42"))]))
+        );
+    }
+
+    #[test]
+    fn test_fallback_match_carries_unconsumed_tail() {
+        let mut tree: PathTree<&'static str> = PathTree::new();
+        tree.insert(segments("/static/favicon.ico"), "favicon");
+        tree.insert(segments("/static/css/site.css"), "site");
+        tree.insert_fallback(segments("/static"), "files");
+
+        for compiled in [false, true] {
+            if compiled {
+                tree.compile();
+            }
+
+            let tail = |path: &'static str| tree.find(path.split('/').filter(|s: &&str| !s.is_empty())).unwrap();
+
+            assert_eq!(tail("/static/favicon.ico").tail(), "");
+            assert_eq!(tail("/static").tail(), "");
+            assert_eq!(tail("/static/js/app.js").tail(), "js/app.js");
+            assert_eq!(tail("/static/css/print.css").tail(), "css/print.css");
+            assert_eq!(tail("/static/css/site.css/map").tail(), "css/site.css/map");
+        }
     }
 }
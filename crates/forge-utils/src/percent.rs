@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Which characters `percent_encode` leaves as they are. Unreserved characters (RFC 3986 §2.3) are
+/// kept by every set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeSet {
+    /// Keeps everything a path may contain: `pchar` plus `/`.
+    Path,
+    /// Keeps everything a query may contain: `pchar` plus `/` and `?`.
+    Query,
+    /// Keeps only unreserved characters, for a single segment, key or value.
+    Component,
+}
+
+impl EncodeSet {
+    fn keeps(self, byte: u8) -> bool {
+        let unreserved: bool = byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~');
+        let pchar: bool = matches!(
+            byte,
+            b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' | b':' | b'@'
+        );
+
+        match self {
+            EncodeSet::Component => unreserved,
+            EncodeSet::Path => unreserved || pchar || byte == b'/',
+            EncodeSet::Query => unreserved || pchar || matches!(byte, b'/' | b'?'),
+        }
+    }
+}
+
+/// Decodes `%XX` sequences. Sequences that are not followed by two hex digits are kept as written,
+/// `+` is not treated as a space, and decoded bytes that are not valid UTF-8 are replaced with
+/// U+FFFD. Input without any escapes is returned borrowed.
+pub fn percent_decode(input: &str) -> Cow<'_, str> {
+    let bytes: &[u8] = input.as_bytes();
+
+    if !bytes.contains(&b'%') {
+        return Cow::Borrowed(input);
+    }
+
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut index: usize = 0;
+
+    while index < bytes.len() {
+        let escaped: Option<u8> = match &bytes[index..] {
+            [b'%', high, low, ..] => hex_value(*high).zip(hex_value(*low)).map(|(h, l): (u8, u8)| h << 4 | l),
+            _ => None,
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    match String::from_utf8(decoded) {
+        Ok(decoded) => Cow::Owned(decoded),
+        Err(e) => Cow::Owned(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+    }
+}
+
+/// Encodes every byte `set` does not keep as `%XX`, using uppercase hex digits. Input that needs no
+/// encoding is returned borrowed.
+pub fn percent_encode(input: &str, set: EncodeSet) -> Cow<'_, str> {
+    let bytes: &[u8] = input.as_bytes();
+
+    let Some(first) = bytes.iter().position(|byte: &u8| !set.keeps(*byte)) else {
+        return Cow::Borrowed(input);
+    };
+
+    let mut encoded: String = String::with_capacity(bytes.len() + 16);
+    encoded.push_str(&input[..first]);
+
+    for byte in &bytes[first..] {
+        if set.keeps(*byte) {
+            encoded.push(char::from(*byte));
+        } else {
+            encoded.push('%');
+            encoded.push(char::from(HEX_DIGITS[usize::from(byte >> 4)]));
+            encoded.push(char::from(HEX_DIGITS[usize::from(byte & 0x0F)]));
+        }
+    }
+
+    Cow::Owned(encoded)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_rfc_3986_examples() {
+        assert_eq!(percent_decode("%7Esmith/home.html"), "~smith/home.html");
+        assert_eq!(percent_decode("a%2fb%2Fc"), "a/b/c");
+        assert_eq!(percent_decode("%E2%82%AC%20100"), "€ 100");
+        assert_eq!(percent_decode("a+b"), "a+b");
+    }
+
+    #[test]
+    fn test_decode_without_escapes_borrows() {
+        assert!(matches!(percent_decode("/users/42"), Cow::Borrowed("/users/42")));
+    }
+
+    #[test]
+    fn test_decode_keeps_malformed_sequences_literal() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%2"), "%2");
+        assert_eq!(percent_decode("%zz%41"), "%zzA");
+        assert_eq!(percent_decode("%%41"), "%A");
+        assert_eq!(percent_decode("%FF"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn test_encode_sets() {
+        assert_eq!(
+            percent_encode("/files/my report.pdf", EncodeSet::Path),
+            "/files/my%20report.pdf"
+        );
+        assert_eq!(percent_encode("q=a b&page=2?", EncodeSet::Query), "q=a%20b&page=2?");
+        assert_eq!(percent_encode("a/b?c=d&e", EncodeSet::Component), "a%2Fb%3Fc%3Dd%26e");
+        assert_eq!(percent_encode("€ 100%", EncodeSet::Component), "%E2%82%AC%20100%25");
+        assert!(matches!(
+            percent_encode("~user.name-1_", EncodeSet::Component),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let input: &str = "naïve path/with spaces?&=#%";
+
+        for set in [EncodeSet::Path, EncodeSet::Query, EncodeSet::Component] {
+            assert_eq!(percent_decode(&percent_encode(input, set)), input);
+        }
+    }
+}
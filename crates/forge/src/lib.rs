@@ -3,10 +3,18 @@ pub use forge_router;
 
 pub mod prelude {
     pub use forge_config::{Config, ConfigError};
-    pub use forge_database::{Database, DatabaseError, DatabaseOptions, DbValue, RowSet, SqlArg};
-    pub use forge_http::{Headers, HttpError, HttpStatus, Params, Request, Response};
-    pub use forge_router::Router;
-    pub use forge_server::{Listener, ListenerOptions};
+    pub use forge_database::{
+        Database, DatabaseError, DatabaseOptions, DbValue, PoolStatus, QueryBuilder, RowSet, SqlArg, sql_args,
+    };
+    pub use forge_http::{
+        BodyStream, Bytes, Headers, HttpError, HttpStatus, HttpVersion, IdempotencyCache, IntoResponse, Json, Params,
+        ParamsExt, Request, Response, ResponseWriter, merge_patch,
+    };
+    pub use forge_router::{Router, RouterError, StateHandle};
+    pub use forge_server::{
+        HealthChecks, Listener, ListenerError, ListenerOptions, RawListener, RawListenerOptions, RequestMetrics,
+        SecurityHeaders, TrafficCounters,
+    };
 }
 
 pub use forge_macros::{delete, get, head, options, patch, post, put, route};
@@ -1,4 +1,7 @@
-use std::{net::Ipv4Addr, sync::Arc};
+use std::error::Error;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
 
 use forge::prelude::*;
 use mimalloc::MiMalloc;
@@ -11,24 +14,51 @@ struct State {
     version: &'static str,
 }
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
     let mut router: Router<State> = Router::new();
 
     let listener_options: ListenerOptions = ListenerOptions {
         threads: Config::from_env("THREADS").ok(),
         port: Config::from_env("PORT").unwrap_or(3000),
-        host: Config::from_env("HOST").unwrap_or_else(|_| Ipv4Addr::new(127, 0, 0, 1)),
+        host: Config::from_env("HOST").unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        dual_stack: Config::bool_from_env("DUAL_STACK").unwrap_or(true),
+        server_timing: Config::bool_from_env("SERVER_TIMING").unwrap_or(false),
+        read_timeout: Duration::from_millis(Config::from_env("READ_TIMEOUT_MS").unwrap_or(5_000)),
+        header_timeout: Duration::from_millis(Config::from_env("HEADER_TIMEOUT_MS").unwrap_or(10_000)),
+        max_uri_length: Config::from_env("MAX_URI_LENGTH").unwrap_or(8 * 1024),
+        trusted_proxies: Config::from_env("TRUSTED_PROXIES").unwrap_or_default(),
+        pipeline_batching: Config::bool_from_env("PIPELINE_BATCHING").unwrap_or(false),
     };
 
     let database_options: DatabaseOptions = DatabaseOptions {
         url: Config::from_env("DB_URL").unwrap_or_default(),
         threads: Config::from_env("DB_THREADS").unwrap_or(8),
+        queue_size: Config::from_env("DB_QUEUE_SIZE").unwrap_or(4096),
         inflight_per_conn: Config::from_env("DB_INFLIGHT_PER_CONN").unwrap_or(32),
+        application_name: Config::from_env("DB_APPLICATION_NAME").ok(),
+        session_settings: Config::from_env("DB_STATEMENT_TIMEOUT_MS")
+            .ok()
+            .map(|timeout: u64| (String::from("statement_timeout"), timeout.to_string()))
+            .into_iter()
+            .collect(),
+        connect_timeout: Config::from_env("DB_CONNECT_TIMEOUT_MS")
+            .ok()
+            .map(Duration::from_millis),
+        prepare_error_ttl: Config::from_env("DB_PREPARE_ERROR_TTL_MS")
+            .ok()
+            .filter(|&ttl: &u64| ttl > 0)
+            .map(Duration::from_millis),
+        warmup_statements: Vec::new(),
+        queue_wait: Config::from_env("DB_QUEUE_WAIT_MS")
+            .ok()
+            .filter(|&wait: &u64| wait > 0)
+            .map(Duration::from_millis),
     };
 
     let state: State = State {
         version: "Forge Example v0.1.0",
-        db: Database::new(database_options).expect("failed to initialize database"),
+        db: Database::new(database_options)
+            .map_err(|e: DatabaseError| format!("failed to initialize database: {e}"))?,
     };
 
     router.register(ping);
@@ -38,10 +68,22 @@ fn main() {
     router.register(reset_database);
     router.register(populate_database);
 
+    router
+        .register_openapi("/openapi.json", "Forge Example", "0.1.0")
+        .map_err(|e: RouterError| format!("failed to register OpenAPI document: {e}"))?;
+
     Listener::new(router, listener_options)
         .with_state(state)
+        .with_request_timeout(Duration::from_millis(Config::from_env("REQUEST_TIMEOUT_MS").unwrap_or(30_000)))
+        .with_max_body_size(Config::from_env("MAX_BODY_SIZE").unwrap_or(1024 * 1024))
+        .with_access_log(Config::bool_from_env("ACCESS_LOG").unwrap_or(false))
+        .with_health_checks(
+            HealthChecks::new().readiness(|state: StateHandle<State>| async move { state.db.health_check().await }),
+        )
         .run()
-        .expect("failed to initialize server")
+        .map_err(|e: ListenerError| format!("failed to initialize server: {e}"))?;
+
+    Ok(())
 }
 
 #[forge::get("/ping")]
@@ -58,42 +100,39 @@ async fn version(state: Arc<State>) -> Response<'static> {
 async fn get_users(state: Arc<State>) -> Response<'static> {
     match state.db.query("SELECT * FROM users", vec![]).await {
         Ok(users) => Response::new(HttpStatus::Ok).json(users.as_objects()),
-        Err(e) => HttpError::new(HttpStatus::InternalServerError, e.to_string()).into(),
+        Err(e) => database_error(e),
     }
 }
 
 #[forge::post("/user/:username")]
 async fn create_user(request: Request<'_>, state: Arc<State>) -> Response<'static> {
-    let Some(username) = request.params.get("username") else {
+    let Some(username) = request.param("username") else {
         return HttpError::new(HttpStatus::BadRequest, "missing parameter \"username\"").into();
     };
 
     let sql: &str = "INSERT INTO users (username) VALUES ($1) RETURNING *";
-    let args: Vec<SqlArg> = vec![SqlArg::Text((*username).into())];
+    let args: Vec<SqlArg> = sql_args![username];
 
     match state.db.query(sql, args).await {
         Ok(user) => Response::new(HttpStatus::Created).json(user.as_objects()),
-        Err(e) => HttpError::new(HttpStatus::InternalServerError, e.to_string()).into(),
+        Err(e) => database_error(e),
     }
 }
 
 #[forge::post("/reset")]
 async fn reset_database(state: Arc<State>) -> Response<'static> {
-    if let Err(e) = state.db.query("DROP TABLE IF EXISTS users", vec![]).await {
-        return HttpError::new(HttpStatus::InternalServerError, e.to_string()).into();
-    }
-
     let sql: &str = r#"
+    DROP TABLE IF EXISTS users;
     CREATE TABLE users (
         id BIGSERIAL PRIMARY KEY,
         username TEXT UNIQUE NOT NULL,
         active BOOLEAN DEFAULT true
-    )
+    );
     "#;
 
-    match state.db.query(sql, vec![]).await {
+    match state.db.batch_execute(sql).await {
         Ok(..) => Response::new(HttpStatus::Ok).text("table \"users\" reseted successfully!"),
-        Err(e) => HttpError::new(HttpStatus::InternalServerError, e.to_string()).into(),
+        Err(e) => database_error(e),
     }
 }
 
@@ -101,15 +140,20 @@ async fn reset_database(state: Arc<State>) -> Response<'static> {
 async fn populate_database(state: Arc<State>) -> Response<'static> {
     let sql: &str = "INSERT INTO users (username, active) VALUES ($1, $2), ($3, $4)";
 
-    let args: Vec<SqlArg> = vec![
-        SqlArg::Text("john_doe".into()),
-        SqlArg::Bool(false),
-        SqlArg::Text("jane_doe".into()),
-        SqlArg::Bool(false),
-    ];
+    let args: Vec<SqlArg> = sql_args!["john_doe", false, "jane_doe", false];
 
     match state.db.query(sql, args).await {
         Ok(..) => Response::new(HttpStatus::Created).text("database successfully seeded!"),
-        Err(e) => HttpError::new(HttpStatus::InternalServerError, e.to_string()).into(),
+        Err(e) => database_error(e),
     }
 }
+
+fn database_error(e: DatabaseError) -> Response<'static> {
+    let status: HttpStatus = if e.is_overloaded() {
+        HttpStatus::ServiceUnavailable
+    } else {
+        HttpStatus::InternalServerError
+    };
+
+    HttpError::new(status, e.to_string()).into()
+}